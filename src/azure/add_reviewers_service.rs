@@ -1,72 +1,234 @@
 use super::api::{
-    AzurePullRequestService, AzureTeamService, Identifier, NewPullRequestReviewer,
-    PullRequestStatus, TeamMember,
+    AzurePullRequestChangesService, AzurePullRequestService, AzurePullRequestsService,
+    AzureTeamService, Identifier, NewPullRequestReviewer, PullRequest, PullRequestSearchCriteria,
+    PullRequestStatus, TeamMember, Vote,
 };
+use super::rotation::RotationState;
+use crate::metrics::metrics;
 use async_trait::async_trait;
 use color_eyre::Result;
+use futures::{StreamExt, TryFutureExt, TryStreamExt};
 use itertools::Itertools;
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 use serde::Deserialize;
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-#[derive(Deserialize, Debug)]
+/// Dev team whose members review the pull requests of each other, optionally
+/// together with a team of required reviewers.
+#[derive(Deserialize, Debug, Clone)]
 pub struct AzureTeam {
     name: String,
     required_reviewers_team: Option<String>,
 }
 
-pub struct ReviewersConfig<'a> {
+impl AzureTeam {
+    pub fn new(name: String, required_reviewers_team: Option<String>) -> Self {
+        Self {
+            name,
+            required_reviewers_team,
+        }
+    }
+
+    /// Names of the team and its required reviewers team.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(self.required_reviewers_team.as_deref())
+    }
+}
+
+/// How the optional reviewers are selected when their number is limited.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OptionalReviewersStrategy {
+    #[default]
+    Random,
+    /// Members waiting to review the fewest active pull requests in the repository.
+    LeastLoaded,
+    /// Members reviewing the author's active pull requests in the repository, or
+    /// whose active pull requests the author reviews.
+    RecentCollaborators,
+}
+
+/// Selection of the required reviewers by their affinity to the author's pull
+/// request, scored from the last completed pull requests of the repository.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthorAffinity {
+    /// Number of the last completed pull requests the scores are computed from.
+    #[serde(default = "default_affinity_history")]
+    pub history: usize,
+    /// Candidates waiting to review this many active pull requests are selected
+    /// only after the other candidates.
+    #[serde(default = "default_max_pending_reviews")]
+    pub max_pending_reviews: usize,
+}
+
+impl Default for AuthorAffinity {
+    fn default() -> Self {
+        Self {
+            history: default_affinity_history(),
+            max_pending_reviews: default_max_pending_reviews(),
+        }
+    }
+}
+
+fn default_affinity_history() -> usize {
+    50
+}
+
+fn default_max_pending_reviews() -> usize {
+    3
+}
+
+/// Number of required reviewers and the teams they are selected from.
+#[derive(Debug, Clone)]
+pub struct ReviewersConfig {
     required_reviewers_count: usize,
-    teams: &'a [AzureTeam],
+    teams: Vec<AzureTeam>,
+    optional_reviewers_count: Option<usize>,
+    optional_reviewers_strategy: OptionalReviewersStrategy,
+    excluded_reviewers: Vec<String>,
+    author_affinity: Option<AuthorAffinity>,
+    seed: Option<u64>,
+    rotation_state_file: Option<PathBuf>,
 }
 
-impl<'a> ReviewersConfig<'a> {
-    pub fn new(required_reviewers_count: usize, teams: &'a [AzureTeam]) -> Self {
+impl ReviewersConfig {
+    pub fn new(required_reviewers_count: usize, teams: Vec<AzureTeam>) -> Self {
         Self {
             required_reviewers_count,
             teams,
+            optional_reviewers_count: None,
+            optional_reviewers_strategy: OptionalReviewersStrategy::default(),
+            excluded_reviewers: Vec::new(),
+            author_affinity: None,
+            seed: None,
+            rotation_state_file: None,
         }
     }
+
+    /// Limits the optional reviewers selected from the umbrella team. Without the
+    /// limit every available and unavailable member is added.
+    pub fn with_optional_reviewers(
+        mut self,
+        count: usize,
+        strategy: OptionalReviewersStrategy,
+    ) -> Self {
+        self.optional_reviewers_count = Some(count);
+        self.optional_reviewers_strategy = strategy;
+        self
+    }
+
+    /// Names or mail addresses of the members who are never added as reviewers.
+    pub fn with_excluded_reviewers(mut self, excluded_reviewers: Vec<String>) -> Self {
+        self.excluded_reviewers = excluded_reviewers;
+        self
+    }
+
+    /// Orders the required reviewer candidates by their affinity to the author's
+    /// pull request instead of the round-robin order.
+    pub fn with_author_affinity(mut self, author_affinity: AuthorAffinity) -> Self {
+        self.author_affinity = Some(author_affinity);
+        self
+    }
+
+    /// Seed of the reviewers shuffling, which is derived from the repository and
+    /// the pull request by default.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Takes the required reviewers of each team in a round-robin rotation, which
+    /// continues from the state file in the next runs, instead of shuffling them.
+    pub fn with_rotation_state_file(mut self, path: PathBuf) -> Self {
+        self.rotation_state_file = Some(path);
+        self
+    }
+
+    /// Names of the configured dev teams and their required reviewers teams.
+    pub(super) fn team_names(&self) -> impl Iterator<Item = &str> {
+        self.teams.iter().flat_map(AzureTeam::names).unique()
+    }
+
+    pub(super) fn author_affinity(&self) -> Option<AuthorAffinity> {
+        self.author_affinity
+    }
+
+    pub(super) fn rotation_state_file(&self) -> Option<&Path> {
+        self.rotation_state_file.as_deref()
+    }
+
+    /// Seed of the reviewers shuffling for the pull request. The repository GUID of the
+    /// pull request is preferred over the given repository, which may be its name.
+    pub(super) fn seed(&self, repository_id: &str, pull_request: &PullRequest) -> u64 {
+        self.seed.unwrap_or_else(|| {
+            let repository_id = pull_request
+                .repository
+                .as_ref()
+                .map_or(repository_id, |repository| repository.id.as_str());
+            pull_request_seed(&repository_id.to_lowercase(), &pull_request.id.to_string())
+        })
+    }
+
+    pub fn required_reviewers_count(&self) -> usize {
+        self.required_reviewers_count
+    }
+
+    fn is_excluded(&self, member: &TeamMember) -> bool {
+        self.excluded_reviewers.iter().any(|excluded| {
+            excluded.eq_ignore_ascii_case(&member.name)
+                || member
+                    .mail_address
+                    .as_ref()
+                    .is_some_and(|mail| excluded.eq_ignore_ascii_case(mail))
+        })
+    }
 }
 
 #[async_trait]
 pub trait AddReviewersService {
     async fn add_reviewers<F>(&self, is_on_vacation: F) -> Result<()>
     where
-        F: Fn(&str) -> bool + Send + Sync;
+        F: Fn(&TeamMember) -> bool + Send + Sync;
 }
 
-type TeamMembersShuffler =
-    fn(Vec<TeamMember>, Vec<TeamMember>) -> (Vec<TeamMember>, Vec<TeamMember>);
+pub(super) type TeamMembersShuffler =
+    fn(Vec<TeamMember>, Vec<TeamMember>, u64) -> (Vec<TeamMember>, Vec<TeamMember>);
 
-pub struct AddReviewersServiceImpl<'a, Api>
+pub struct AddReviewersServiceImpl<Api>
 where
     Api: AzureTeamService,
     Api: AzurePullRequestService,
+    Api: AzurePullRequestsService,
+    Api: AzurePullRequestChangesService,
 {
     api: Api,
-    team_name: &'a str,
+    team_name: String,
     pull_request_id: String,
     repository_id: String,
-    config: ReviewersConfig<'a>,
+    config: ReviewersConfig,
     shuffle_teams: TeamMembersShuffler,
 }
 
-impl<'a, Api> AddReviewersServiceImpl<'a, Api>
+impl<Api> AddReviewersServiceImpl<Api>
 where
     Api: AzureTeamService,
     Api: AzurePullRequestService,
+    Api: AzurePullRequestsService,
+    Api: AzurePullRequestChangesService,
 {
     pub fn new(
         api: Api,
-        team_name: &'a str,
+        team_name: &str,
         pull_request_id: String,
         repository_id: String,
-        config: ReviewersConfig<'a>,
+        config: ReviewersConfig,
     ) -> Self {
         Self {
             api,
-            team_name,
+            team_name: team_name.to_string(),
             pull_request_id,
             repository_id,
             config,
@@ -77,88 +239,313 @@ where
     #[cfg(test)]
     pub fn new_with_shuffler(
         api: Api,
-        team_name: &'a str,
+        team_name: &str,
         pull_request_id: String,
         repository_id: String,
-        config: ReviewersConfig<'a>,
+        config: ReviewersConfig,
         shuffle_teams: TeamMembersShuffler,
     ) -> Self {
         Self {
             api,
-            team_name,
+            team_name: team_name.to_string(),
             pull_request_id,
             repository_id,
             config,
             shuffle_teams,
         }
     }
+}
 
-    async fn add_required_reviwers<F>(
-        &self,
-        reviwers: &mut Vec<Identifier>,
-        author_id: &Identifier,
-        can_be_added: F,
-    ) -> Result<()>
-    where
-        F: Fn(&TeamMember) -> bool,
-    {
-        let (team_members, team) = self.find_author_dev_team_members(author_id).await?;
-
-        let required_reviewers_team = team.and_then(|team| team.required_reviewers_team.clone());
-        let required_reviewers = if let Some(team_name) = required_reviewers_team {
-            tracing::info!("Required reviewers team is {team_name}.");
-            self.api.team_members(Identifier(team_name)).await?
-        } else {
-            tracing::info!("Author's team does not have requied reviwers.");
-            vec![]
-        };
+/// Candidates for the required reviewers with the teams they were selected from.
+pub(super) struct RequiredReviewerCandidates {
+    pub candidates: Vec<TeamMember>,
+    /// Names and members, except the excluded ones, of the author's dev team and its
+    /// required reviewers team.
+    pub teams: Vec<(String, Vec<TeamMember>)>,
+}
+
+/// Pull request whose required reviewers are selected and the state of the selection.
+pub(super) struct RequiredReviewersSelection<'a> {
+    pub repository_id: &'a str,
+    pub pull_request: &'a PullRequest,
+    pub seed: u64,
+    pub rotation: Option<&'a RotationState>,
+    pub author_affinity: Option<AuthorAffinity>,
+}
+
+/// Candidates for the required reviewers of the author's pull request: members of
+/// the author's dev team interleaved with members of its required reviewers team.
+/// Each team is in the order of the rotation if it is given, otherwise shuffled,
+/// and then ordered by the author affinity if it is configured.
+pub(super) async fn required_reviewer_candidates<Api, F>(
+    api: &Api,
+    config: &ReviewersConfig,
+    shuffle_teams: TeamMembersShuffler,
+    selection: RequiredReviewersSelection<'_>,
+    can_be_added: F,
+) -> Result<RequiredReviewerCandidates>
+where
+    Api: AzureTeamService + AzurePullRequestsService + AzurePullRequestChangesService,
+    F: Fn(&TeamMember) -> bool,
+{
+    let author_id = &selection.pull_request.created_by.id;
+    let (team_members, team) = find_author_dev_team_members(api, config, author_id).await?;
+
+    // Excluded members do not take turns in the rotations.
+    let included = |members: &[TeamMember]| {
+        members
+            .iter()
+            .filter(|member| !config.is_excluded(member))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let mut teams = Vec::new();
+    if let Some(team) = team {
+        teams.push((team.name.clone(), included(&team_members)));
+    }
+    let required_reviewers_team = team.and_then(|team| team.required_reviewers_team.clone());
+    let required_reviewers = if let Some(team_name) = required_reviewers_team {
+        tracing::info!("Required reviewers team is {team_name}.");
+        let members = api.team_members(Identifier(team_name.clone())).await?;
+        teams.push((team_name, included(&members)));
+        members
+    } else {
+        tracing::info!("Author's team does not have requied reviwers.");
+        vec![]
+    };
 
-        let (team_members, required_reviewers) =
-            (self.shuffle_teams)(team_members, required_reviewers);
+    let (team_members, required_reviewers) = match (selection.rotation, team) {
+        (Some(rotation), Some(team)) => {
+            let required_reviewers = match &team.required_reviewers_team {
+                Some(team_name) => rotation.order(team_name, required_reviewers),
+                None => required_reviewers,
+            };
+            (rotation.order(&team.name, team_members), required_reviewers)
+        }
+        _ => shuffle_teams(team_members, required_reviewers, selection.seed),
+    };
 
-        let team_members_ids = team_members
+    let team_members_ids = team_members
+        .iter()
+        .map(|m| m.id.clone())
+        .collect::<HashSet<_>>();
+
+    let can_be_added = |member: &TeamMember| can_be_added(member) && !config.is_excluded(member);
+    let mut team_members = team_members
+        .into_iter()
+        .filter(|member| can_be_added(member))
+        .collect::<Vec<_>>();
+    let mut required_reviewers = required_reviewers
+        .into_iter()
+        .filter(|member| can_be_added(member) && !team_members_ids.contains(&member.id))
+        .collect::<Vec<_>>();
+
+    if let Some(affinity) = selection.author_affinity {
+        let candidates = team_members
             .iter()
-            .map(|m| m.id.clone())
-            .collect::<HashSet<_>>();
+            .chain(&required_reviewers)
+            .cloned()
+            .collect::<Vec<_>>();
+        let order = affinity_order(api, &selection, &candidates, affinity).await?;
+        // Both teams keep their places in the interleaving, so the required
+        // reviewers team is not crowded out by the dev team experts.
+        team_members.sort_by_key(|member| order(member));
+        required_reviewers.sort_by_key(|member| order(member));
+    }
 
-        team_members
-            .into_iter()
-            .filter(|member| can_be_added(member))
-            .interleave(
-                required_reviewers.into_iter().filter(|member| {
-                    can_be_added(member) && !team_members_ids.contains(&member.id)
-                }),
-            )
-            .for_each(|reviwer| reviwers.push(reviwer.id));
+    let candidates = team_members
+        .into_iter()
+        .interleave(required_reviewers)
+        .collect();
 
-        Ok(())
+    Ok(RequiredReviewerCandidates { candidates, teams })
+}
+
+/// Maximum number of the completed pull requests scored for the author affinity.
+const MAX_AFFINITY_HISTORY: usize = 100;
+/// Maximum number of the concurrent requests of the author affinity scoring.
+const AFFINITY_REQUESTS_LIMIT: usize = 8;
+
+/// Sort key of the candidates by their affinity scores, keeping the round-robin
+/// order of equally scored candidates. Candidates at the pending reviews cap go
+/// after the others.
+async fn affinity_order<Api>(
+    api: &Api,
+    selection: &RequiredReviewersSelection<'_>,
+    candidates: &[TeamMember],
+    affinity: AuthorAffinity,
+) -> Result<impl Fn(&TeamMember) -> (bool, Reverse<usize>)>
+where
+    Api: AzurePullRequestsService + AzurePullRequestChangesService,
+{
+    let scores = affinity_scores(api, selection, candidates, affinity).await?;
+    tracing::info!("Author affinity scores: {scores:?}");
+    let scored = candidates
+        .iter()
+        .filter(|member| scores.contains_key(&member.id))
+        .cloned()
+        .collect::<Vec<_>>();
+    let loads = pending_reviews(api, selection.repository_id, &scored).await?;
+    Ok(move |member: &TeamMember| {
+        let is_capped = loads
+            .get(&member.id)
+            .is_some_and(|load| *load >= affinity.max_pending_reviews);
+        let score = scores.get(&member.id).copied().unwrap_or_default();
+        (is_capped, Reverse(score))
+    })
+}
+
+/// Scores of the candidates in the last completed pull requests: a point for
+/// each vote on a pull request of the author and for each pull request of the
+/// candidate which changed the files of the author's pull request.
+async fn affinity_scores<Api>(
+    api: &Api,
+    selection: &RequiredReviewersSelection<'_>,
+    candidates: &[TeamMember],
+    affinity: AuthorAffinity,
+) -> Result<HashMap<Identifier, usize>>
+where
+    Api: AzurePullRequestChangesService,
+{
+    let repository_id = selection.repository_id;
+    let pull_request = selection.pull_request;
+    let author_id = &pull_request.created_by.id;
+    if affinity.history > MAX_AFFINITY_HISTORY {
+        tracing::warn!(
+            "Author affinity history {} is limited to {MAX_AFFINITY_HISTORY} pull requests.",
+            affinity.history
+        );
+    }
+    let history = api
+        .obtain_completed_pull_requests(repository_id, affinity.history.min(MAX_AFFINITY_HISTORY));
+    let files = api.obtain_changed_files(repository_id, pull_request.id);
+    let (history, files) = futures::future::try_join(history, files).await?;
+    let files = files.into_iter().collect::<HashSet<_>>();
+    let candidate_ids = candidates.iter().map(|m| &m.id).collect::<HashSet<_>>();
+
+    let mut scores = HashMap::<Identifier, usize>::new();
+    for request in history.iter().filter(|r| &r.created_by.id == author_id) {
+        request
+            .reviewers
+            .iter()
+            .filter(|r| r.vote != Vote::NoVote && candidate_ids.contains(&r.id))
+            .for_each(|r| *scores.entry(r.id.clone()).or_default() += 1);
     }
 
-    async fn find_author_dev_team_members(
+    // Only the pull requests of the candidates are compared with the changed files.
+    let candidates_requests = history
+        .iter()
+        .filter(|r| candidate_ids.contains(&r.created_by.id))
+        .collect::<Vec<_>>();
+    let request_ids = candidates_requests.iter().map(|r| r.id).collect::<Vec<_>>();
+    let changed_files = futures::stream::iter(request_ids)
+        .map(|request_id| api.obtain_changed_files(repository_id, request_id))
+        .buffered(AFFINITY_REQUESTS_LIMIT)
+        .try_collect::<Vec<_>>()
+        .await?;
+    for (request, changed_files) in candidates_requests.into_iter().zip(changed_files) {
+        if changed_files.iter().any(|file| files.contains(file)) {
+            *scores.entry(request.created_by.id.clone()).or_default() += 1;
+        }
+    }
+    Ok(scores)
+}
+
+/// Numbers of the active pull requests waiting for the vote of the members.
+async fn pending_reviews<Api: AzurePullRequestsService>(
+    api: &Api,
+    repository_id: &str,
+    members: &[TeamMember],
+) -> Result<HashMap<Identifier, usize>> {
+    let loads = members.iter().map(|member| {
+        let criteria = PullRequestSearchCriteria::Reviewer(member.id.clone());
+        api.obtain_pull_requests(repository_id, criteria, |r| {
+            r.id == member.id && r.vote == Vote::NoVote
+        })
+        .map_ok(|requests| (member.id.clone(), requests.len()))
+    });
+    let loads = futures::future::try_join_all(loads).await?;
+    Ok(loads.into_iter().collect())
+}
+
+async fn find_author_dev_team_members<'a, Api: AzureTeamService>(
+    api: &Api,
+    config: &'a ReviewersConfig,
+    author_id: &Identifier,
+) -> Result<(Vec<TeamMember>, Option<&'a AzureTeam>)> {
+    for team in &config.teams {
+        let members = api.team_members(Identifier(team.name.clone())).await?;
+        if members.iter().any(|member| &member.id == author_id) {
+            tracing::info!("PR author found in {} team.", team.name);
+            return Ok((members, Some(team)));
+        }
+    }
+    tracing::warn!("Pull request author is not added to any of the dev groups.");
+    Ok((Vec::new(), None))
+}
+
+impl<Api> AddReviewersServiceImpl<Api>
+where
+    Api: AzureTeamService,
+    Api: AzurePullRequestService,
+    Api: AzurePullRequestsService,
+    Api: AzurePullRequestChangesService,
+{
+    /// Orders the available members by the optional reviewers strategy. Members
+    /// the strategy does not tell apart are in random order.
+    async fn order_optional_reviewers(
         &self,
+        members: Vec<TeamMember>,
         author_id: &Identifier,
-    ) -> Result<(Vec<TeamMember>, Option<&AzureTeam>)> {
-        for team in self.config.teams {
-            let members = self.api.team_members(Identifier(team.name.clone())).await?;
-            if members.iter().any(|member| &member.id == author_id) {
-                tracing::info!("PR author found in {} team.", team.name);
-                return Ok((members, Some(team)));
+        seed: u64,
+    ) -> Result<Vec<TeamMember>> {
+        let (mut members, _) = (self.shuffle_teams)(members, Vec::new(), seed);
+        match self.config.optional_reviewers_strategy {
+            OptionalReviewersStrategy::Random => {}
+            OptionalReviewersStrategy::LeastLoaded => {
+                let loads = pending_reviews(&self.api, &self.repository_id, &members).await?;
+                members.sort_by_key(|member| loads.get(&member.id).copied().unwrap_or_default());
+            }
+            OptionalReviewersStrategy::RecentCollaborators => {
+                let authored = self.api.obtain_pull_requests(
+                    &self.repository_id,
+                    PullRequestSearchCriteria::Creator(author_id.clone()),
+                    |_| true,
+                );
+                let reviewed = self.api.obtain_pull_requests(
+                    &self.repository_id,
+                    PullRequestSearchCriteria::Reviewer(author_id.clone()),
+                    |_| true,
+                );
+                let (authored, reviewed) = futures::future::try_join(authored, reviewed).await?;
+                members.sort_by_key(|member| {
+                    let reviews_author = authored
+                        .iter()
+                        .filter(|r| r.reviewers.iter().any(|v| v.id == member.id))
+                        .count();
+                    let reviewed_by_author = reviewed
+                        .iter()
+                        .filter(|r| r.created_by.id == member.id)
+                        .count();
+                    Reverse(reviews_author + reviewed_by_author)
+                });
             }
         }
-        tracing::warn!("Pull request author is not added to any of the dev groups.");
-        Ok((Vec::new(), None))
+        Ok(members)
     }
 }
 
 #[async_trait]
-impl<'a, Api> AddReviewersService for AddReviewersServiceImpl<'a, Api>
+impl<Api> AddReviewersService for AddReviewersServiceImpl<Api>
 where
     Api: AzureTeamService + Sync + Send,
     Api: AzurePullRequestService + Sync + Send,
+    Api: AzurePullRequestsService + Sync + Send,
+    Api: AzurePullRequestChangesService + Sync + Send,
 {
     async fn add_reviewers<F>(&self, is_on_vacation: F) -> Result<()>
     where
-        F: Fn(&str) -> bool + Send + Sync,
+        F: Fn(&TeamMember) -> bool + Send + Sync,
     {
         let all_members = self
             .api
@@ -177,7 +564,15 @@ where
             return Ok(());
         }
 
-        tracing::info!("Received pull request: {pull_request:?}");
+        tracing::info!(
+            "Received pull request {} with {} reviewers.",
+            pull_request.id,
+            pull_request.reviewers.len()
+        );
+        tracing::debug!("Received pull request: {pull_request:?}");
+
+        let seed = self.config.seed(&self.repository_id, &pull_request);
+        tracing::info!("Reviewers are shuffled with seed {seed}.");
 
         let author_id = &pull_request.created_by.id;
         let existing_reviewers = pull_request
@@ -198,32 +593,72 @@ where
             .required_reviewers_count
             .saturating_sub(required_reviwers_count);
 
+        let rotation_state_file = self.config.rotation_state_file();
+        // Held until the advanced rotation is saved.
+        let _rotation_lock = rotation_state_file.map(RotationState::lock).transpose()?;
+        let mut rotation = rotation_state_file.map(RotationState::load).transpose()?;
         if required_reviwers_left > 0 {
-            self.add_required_reviwers(&mut new_reviewers, author_id, |member| {
-                let id = &member.id;
-                author_id != id
-                    && !existing_reviewers.contains(id)
-                    && !is_on_vacation(member.name.as_str())
-            })
+            let selection = RequiredReviewersSelection {
+                repository_id: &self.repository_id,
+                pull_request: &pull_request,
+                seed,
+                rotation: rotation.as_ref(),
+                author_affinity: self.config.author_affinity(),
+            };
+            let RequiredReviewerCandidates { candidates, teams } = required_reviewer_candidates(
+                &self.api,
+                &self.config,
+                self.shuffle_teams,
+                selection,
+                |member| {
+                    let id = &member.id;
+                    author_id != id && !existing_reviewers.contains(id) && !is_on_vacation(member)
+                },
+            )
             .await?;
+            if let Some(rotation) = &mut rotation {
+                let assigned = candidates
+                    .iter()
+                    .take(required_reviwers_left)
+                    .map(|member| member.id.clone())
+                    .collect::<HashSet<_>>();
+                for (team_name, members) in &teams {
+                    rotation.advance(team_name, members, &assigned);
+                }
+            }
+            new_reviewers.extend(candidates.into_iter().map(|member| member.id));
         }
 
+        if self.config.optional_reviewers_count.is_some() {
+            // Other candidates compete with the whole team for the optional places.
+            new_reviewers.truncate(required_reviwers_left);
+        }
         let new_reviewers_set = new_reviewers.iter().cloned().collect::<HashSet<_>>();
 
         let (on_vacation, not_on_vacation): (Vec<_>, Vec<_>) = all_members
             .into_iter()
-            .partition(|member| is_on_vacation(&member.name));
-
-        not_on_vacation
-            .into_iter()
-            .chain(on_vacation.into_iter())
             .filter(|member| {
                 let id = &member.id;
                 author_id != id
                     && !new_reviewers_set.contains(id)
                     && !existing_reviewers.contains(id)
+                    && !self.config.is_excluded(member)
             })
-            .for_each(|member| new_reviewers.push(member.id));
+            .partition(|member| is_on_vacation(member));
+
+        match self.config.optional_reviewers_count {
+            // Unavailable members are not added when the optional reviewers are limited.
+            Some(count) => self
+                .order_optional_reviewers(not_on_vacation, author_id, seed)
+                .await?
+                .into_iter()
+                .take(count)
+                .for_each(|member| new_reviewers.push(member.id)),
+            None => not_on_vacation
+                .into_iter()
+                .chain(on_vacation)
+                .for_each(|member| new_reviewers.push(member.id)),
+        }
 
         let new_reviewers = new_reviewers
             .into_iter()
@@ -239,34 +674,58 @@ where
             return Ok(());
         }
 
-        tracing::info!("New reviewers will be added: {new_reviewers:?}");
+        tracing::info!("New reviewers will be added with seed {seed}: {new_reviewers:?}");
+        let added = new_reviewers.len() as u64;
         self.api
             .add_reviewers_to_pull_request(
                 &self.repository_id,
                 &self.pull_request_id,
                 new_reviewers,
             )
-            .await
+            .await?;
+        metrics().reviewers_added.inc_by(added);
+        if let (Some(rotation), Some(path)) = (rotation, rotation_state_file) {
+            rotation.save(path)?;
+        }
+        Ok(())
     }
 }
 
-fn shuffle_teams_members(
+/// Shuffles the teams in the same order for the same seed. ChaCha keeps the order
+/// stable across the versions of `rand`.
+pub(super) fn shuffle_teams_members(
     mut first_team: Vec<TeamMember>,
     mut second_team: Vec<TeamMember>,
+    seed: u64,
 ) -> (Vec<TeamMember>, Vec<TeamMember>) {
-    let mut rng = rand::thread_rng();
+    // The order of the members returned by the API is not guaranteed.
+    first_team.sort_by(|a, b| a.id.0.cmp(&b.id.0));
+    second_team.sort_by(|a, b| a.id.0.cmp(&b.id.0));
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
     first_team.shuffle(&mut rng);
     second_team.shuffle(&mut rng);
     (first_team, second_team)
 }
 
+/// FNV-1a hash of the pull request, which is stable across runs and Rust versions
+/// unlike the `Hash` of the standard library.
+pub(super) fn pull_request_seed(repository_id: &str, pull_request_id: &str) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+    format!("{repository_id}/{pull_request_id}")
+        .bytes()
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        })
+}
+
 #[cfg(test)]
 mod test {
     use crate::azure::api::{PullRequestAuthor, PullRequestReviewer};
 
     use super::super::api::{
-        AzurePullRequestService, AzureTeamService, NewPullRequestReviewer, PullRequest, Team,
-        TeamMember, Vote,
+        AzurePullRequestService, AzureTeamService, NewPullRequestReviewer, PullRequest,
+        PullRequestRepository, PullRequestSearchCriteria, Team, TeamMember, Vote,
     };
     use super::*;
     use async_trait::async_trait;
@@ -277,7 +736,13 @@ mod test {
     use url::Url;
 
     mock! {
-        Api {}
+        Api {
+            fn search_pull_requests(
+                &self,
+                reviewer: Option<Identifier>,
+                creator: Option<Identifier>,
+            ) -> Vec<PullRequest>;
+        }
 
         #[async_trait]
         impl AzureTeamService for Api {
@@ -300,6 +765,48 @@ mod test {
                 reviewers: Vec<NewPullRequestReviewer>,
             ) -> Result<()>;
         }
+
+        #[async_trait]
+        impl AzurePullRequestChangesService for Api {
+            async fn obtain_completed_pull_requests(
+                &self,
+                repository_id: &str,
+                count: usize,
+            ) -> Result<Vec<PullRequest>>;
+
+            async fn obtain_changed_files(
+                &self,
+                repository_id: &str,
+                pull_request_id: usize,
+            ) -> Result<Vec<String>>;
+        }
+    }
+
+    /// Searches go through a method without the filter, which mockall can not match.
+    #[async_trait]
+    impl AzurePullRequestsService for MockApi {
+        async fn obtain_pull_requests<F>(
+            &self,
+            _repository_id: &str,
+            search_creteria: PullRequestSearchCriteria,
+            filter: F,
+        ) -> Result<Vec<PullRequest>>
+        where
+            F: Fn(&PullRequestReviewer) -> bool,
+            F: Send,
+        {
+            let requests = match search_creteria {
+                PullRequestSearchCriteria::Reviewer(id) => {
+                    self.search_pull_requests(Some(id), None)
+                }
+                PullRequestSearchCriteria::Creator(id) => self.search_pull_requests(None, Some(id)),
+            };
+            let requests = requests
+                .into_iter()
+                .filter(|r| r.reviewers.iter().any(&filter))
+                .collect();
+            Ok(requests)
+        }
     }
 
     struct Stubs;
@@ -329,11 +836,8 @@ mod test {
                 },
             ]
         }
-        fn config(required_reviewers_count: usize, teams: &[AzureTeam]) -> ReviewersConfig<'_> {
-            ReviewersConfig {
-                required_reviewers_count,
-                teams,
-            }
+        fn config(required_reviewers_count: usize, teams: &[AzureTeam]) -> ReviewersConfig {
+            ReviewersConfig::new(required_reviewers_count, teams.to_vec())
         }
     }
 
@@ -375,7 +879,6 @@ mod test {
             let mut api = MockApi::new();
             let all_team_members = self
                 .all_team_members
-                .into_iter()
                 .map(Identifier::from)
                 .map(TeamMember::new)
                 .collect::<Vec<TeamMember>>();
@@ -491,12 +994,12 @@ mod test {
             })
             .chain(
                 std::iter::once(9)
-                    .chain((1..=6).into_iter())
+                    .chain(1..=6)
                     .map(NewPullRequestReviewer::from),
             );
 
-        run_test(MockApiBuilder::new(vec![], expected_reviewers), |name| {
-            name.parse::<usize>().unwrap() <= 6
+        run_test(MockApiBuilder::new(vec![], expected_reviewers), |member| {
+            member.name.parse::<usize>().unwrap() <= 6
         })
         .await
     }
@@ -504,20 +1007,263 @@ mod test {
     #[tokio::test]
     async fn new_reviewers_unique() -> Result<()> {
         let expected_reviewers = [1, 4]
+            .into_iter()
+            .map(|id| NewPullRequestReviewer {
+                id: Identifier::from(id),
+                is_required: true,
+            })
+            .chain((2..4).chain(5..10).map(NewPullRequestReviewer::from));
+
+        let builder = MockApiBuilder::new(vec![], expected_reviewers).team_members(0..4);
+        run_test(builder, |_| false).await
+    }
+
+    #[tokio::test]
+    async fn optional_reviewers_limited_without_excluded() -> Result<()> {
+        let expected_reviewers = [1, 3]
+            .into_iter()
+            .map(|id| NewPullRequestReviewer {
+                id: Identifier::from(id),
+                is_required: true,
+            })
+            .chain([2, 6, 7].into_iter().map(NewPullRequestReviewer::from));
+        let config = Stubs::config(2, &Stubs::teams())
+            .with_optional_reviewers(3, OptionalReviewersStrategy::Random)
+            .with_excluded_reviewers(vec!["4".to_string()]);
+
+        let api = MockApiBuilder::new(vec![], expected_reviewers).build();
+        run_test_with_config(api, config, |member| member.name == "5").await
+    }
+
+    #[tokio::test]
+    async fn least_loaded_optional_reviewers_added() -> Result<()> {
+        let expected_reviewers = [1, 3]
+            .into_iter()
+            .map(|id| NewPullRequestReviewer {
+                id: Identifier::from(id),
+                is_required: true,
+            })
+            .chain([5, 6].into_iter().map(NewPullRequestReviewer::from));
+        let config = Stubs::config(2, &Stubs::teams())
+            .with_optional_reviewers(2, OptionalReviewersStrategy::LeastLoaded);
+
+        let mut api = MockApiBuilder::new(vec![], expected_reviewers).build();
+        api.expect_search_pull_requests().returning(|reviewer, _| {
+            let id = reviewer.unwrap().0.parse::<usize>().unwrap();
+            let waiting = match id {
+                2 => 2,
+                4 => 1,
+                _ => 0,
+            };
+            // Pull requests the reviewer approved are not waiting for them.
+            let mut approved = PullRequestReviewer::from(id);
+            approved.vote = Vote::Approved;
+            std::iter::repeat_with(|| PullRequest::new(vec![PullRequestReviewer::from(id)]))
+                .take(waiting)
+                .chain(std::iter::once(PullRequest::new(vec![approved])))
+                .collect()
+        });
+        run_test_with_config(api, config, |_| false).await
+    }
+
+    #[tokio::test]
+    async fn recent_collaborators_added_as_optional_reviewers() -> Result<()> {
+        let expected_reviewers = [1, 3]
+            .into_iter()
+            .map(|id| NewPullRequestReviewer {
+                id: Identifier::from(id),
+                is_required: true,
+            })
+            .chain([8, 7].into_iter().map(NewPullRequestReviewer::from));
+        let config = Stubs::config(2, &Stubs::teams())
+            .with_optional_reviewers(2, OptionalReviewersStrategy::RecentCollaborators);
+
+        let mut api = MockApiBuilder::new(vec![], expected_reviewers).build();
+        api.expect_search_pull_requests()
+            .with(eq(None), eq(Some(Identifier::from(0))))
+            .returning(|_, _| {
+                vec![
+                    PullRequest::new([7, 8].map(PullRequestReviewer::from).into()),
+                    PullRequest::new(vec![PullRequestReviewer::from(8)]),
+                ]
+            });
+        api.expect_search_pull_requests()
+            .with(eq(Some(Identifier::from(0))), eq(None))
+            .returning(|_, _| {
+                let mut request = PullRequest::new(vec![PullRequestReviewer::from(0)]);
+                request.created_by.id = Identifier::from(9);
+                vec![request]
+            });
+        run_test_with_config(api, config, |_| false).await
+    }
+
+    #[tokio::test]
+    async fn required_reviewers_selected_by_author_affinity() -> Result<()> {
+        // Member 1 scores as much as member 4, but the required reviewers team
+        // keeps its place in the interleaving.
+        let expected_reviewers = [2, 4]
             .into_iter()
             .map(|id| NewPullRequestReviewer {
                 id: Identifier::from(id),
                 is_required: true,
             })
             .chain(
-                (2..4)
+                [1, 3, 5, 6, 7, 8, 9]
                     .into_iter()
-                    .chain((5..10).into_iter())
                     .map(NewPullRequestReviewer::from),
             );
+        let config = Stubs::config(2, &Stubs::teams()).with_author_affinity(AuthorAffinity {
+            history: 20,
+            max_pending_reviews: 3,
+        });
+
+        let mut api = MockApiBuilder::new(vec![], expected_reviewers).build();
+        expect_affinity_history(&mut api);
+        api.expect_search_pull_requests()
+            .returning(|_, _| Vec::new());
+        run_test_with_config(api, config, |_| false).await
+    }
 
-        let builder = MockApiBuilder::new(vec![], expected_reviewers).team_members(0..4);
-        run_test(builder, |_| false).await
+    #[tokio::test]
+    async fn author_affinity_limited_by_pending_reviews() -> Result<()> {
+        let expected_reviewers = [2, 3]
+            .into_iter()
+            .map(|id| NewPullRequestReviewer {
+                id: Identifier::from(id),
+                is_required: true,
+            })
+            .chain(
+                [1, 4, 5, 6, 7, 8, 9]
+                    .into_iter()
+                    .map(NewPullRequestReviewer::from),
+            );
+        let config = Stubs::config(2, &Stubs::teams()).with_author_affinity(AuthorAffinity {
+            history: 20,
+            max_pending_reviews: 3,
+        });
+
+        let mut api = MockApiBuilder::new(vec![], expected_reviewers).build();
+        expect_affinity_history(&mut api);
+        api.expect_search_pull_requests().returning(|reviewer, _| {
+            let id = reviewer.unwrap().0.parse::<usize>().unwrap();
+            let waiting = if id == 4 { 3 } else { 2 };
+            std::iter::repeat_with(|| PullRequest::new(vec![PullRequestReviewer::from(id)]))
+                .take(waiting)
+                .collect()
+        });
+        run_test_with_config(api, config, |_| false).await
+    }
+
+    /// Member 2 approved two pull requests of the author and member 1 one of them,
+    /// member 4 changed the same file.
+    fn expect_affinity_history(api: &mut MockApi) {
+        api.expect_obtain_completed_pull_requests()
+            .with(eq(Stubs::repository_id()), eq(20))
+            .times(1)
+            .returning(|_, _| {
+                let approved_by = |id: usize| {
+                    let mut reviewer = PullRequestReviewer::from(id);
+                    reviewer.vote = Vote::ApprovedWithSuggestions;
+                    reviewer
+                };
+                let mut requests = [
+                    PullRequest::new(vec![approved_by(1), approved_by(2)]),
+                    PullRequest::new(vec![]),
+                    PullRequest::new(vec![approved_by(2)]),
+                    PullRequest::new(vec![]),
+                ];
+                for (id, (request, author)) in requests.iter_mut().zip([0, 4, 0, 3]).enumerate() {
+                    request.id = id + 10;
+                    request.created_by.id = Identifier::from(author);
+                }
+                Ok(requests.into())
+            });
+        api.expect_obtain_changed_files()
+            .returning(|_, pull_request_id| {
+                let file = match pull_request_id {
+                    0 | 11 => "/src/lib.rs",
+                    _ => "/src/main.rs",
+                };
+                Ok(vec![file.to_string()])
+            });
+    }
+
+    #[test]
+    fn shuffle_reproducible_for_pull_request() {
+        let members = || {
+            (0..20)
+                .map(Identifier::from)
+                .map(TeamMember::new)
+                .collect_vec()
+        };
+        let shuffle = |seed, reversed: bool| {
+            let mut team = members();
+            if reversed {
+                team.reverse();
+            }
+            let (first, second) = shuffle_teams_members(team, members(), seed);
+            first
+                .into_iter()
+                .chain(second)
+                .map(|member| member.name)
+                .collect_vec()
+        };
+        let config = Stubs::config(2, &Stubs::teams());
+        let mut pull_request = PullRequest::new(vec![]);
+        pull_request.id = 1;
+        let seed = config.seed("repository", &pull_request);
+        let mut other_pull_request = PullRequest::new(vec![]);
+        other_pull_request.id = 2;
+        let other_seed = config.seed("repository", &other_pull_request);
+
+        assert_eq!(seed, pull_request_seed("repository", "1"));
+        assert_ne!(seed, other_seed);
+        assert_eq!(shuffle(seed, false), shuffle(seed, false));
+        assert_eq!(shuffle(seed, false), shuffle(seed, true));
+        assert_ne!(shuffle(seed, false), shuffle(other_seed, false));
+        assert_eq!(
+            config
+                .clone()
+                .with_seed(7)
+                .seed("repository", &pull_request),
+            7
+        );
+
+        pull_request.repository = Some(PullRequestRepository {
+            id: "3A1C-GUID".to_string(),
+        });
+        assert_eq!(
+            config.seed("Repository-Name", &pull_request),
+            pull_request_seed("3a1c-guid", "1")
+        );
+    }
+
+    #[tokio::test]
+    async fn required_reviewers_rotated_across_runs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config = Stubs::config(2, &Stubs::teams())
+            .with_rotation_state_file(dir.path().join("rotation.json"));
+        let expected = |required: [usize; 2], optional: &[usize]| {
+            required
+                .into_iter()
+                .map(|id| NewPullRequestReviewer {
+                    id: Identifier::from(id),
+                    is_required: true,
+                })
+                .chain(optional.iter().copied().map(NewPullRequestReviewer::from))
+                .collect_vec()
+                .into_iter()
+        };
+
+        let api = MockApiBuilder::new(vec![], expected([1, 3], &[2, 4, 5, 6, 7, 8, 9])).build();
+        run_test_with_config(api, config.clone(), |_| false).await?;
+
+        // Member 2 is on vacation when it is their turn.
+        let api = MockApiBuilder::new(vec![], expected([1, 4], &[3, 5, 6, 7, 8, 9, 2])).build();
+        run_test_with_config(api, config.clone(), |member| member.name == "2").await?;
+
+        let api = MockApiBuilder::new(vec![], expected([2, 3], &[1, 4, 5, 6, 7, 8, 9])).build();
+        run_test_with_config(api, config, |_| false).await
     }
 
     async fn run_test<OnVacation>(
@@ -525,15 +1271,26 @@ mod test {
         is_on_vacation: OnVacation,
     ) -> Result<()>
     where
-        OnVacation: Fn(&str) -> bool + Send + Sync,
+        OnVacation: Fn(&TeamMember) -> bool + Send + Sync,
+    {
+        let config = Stubs::config(2, &Stubs::teams());
+        run_test_with_config(api_builder.build(), config, is_on_vacation).await
+    }
+
+    async fn run_test_with_config<OnVacation>(
+        api: MockApi,
+        config: ReviewersConfig,
+        is_on_vacation: OnVacation,
+    ) -> Result<()>
+    where
+        OnVacation: Fn(&TeamMember) -> bool + Send + Sync,
     {
-        let developer_teams = Stubs::teams();
         let service = AddReviewersServiceImpl::new_with_shuffler(
-            api_builder.build(),
+            api,
             Stubs::team_name(),
             Stubs::pull_request_id().to_string(),
             Stubs::repository_id().to_string(),
-            Stubs::config(2, &developer_teams),
+            config,
             fake_shuffle_teams,
         );
         let result = service.add_reviewers(is_on_vacation).await;
@@ -549,6 +1306,7 @@ mod test {
                 id,
                 name,
                 is_container: false,
+                mail_address: None,
             }
         }
     }
@@ -576,6 +1334,8 @@ mod test {
                 is_required: false,
                 vote: Vote::NoVote,
                 has_declined: false,
+                is_container: false,
+                votes: vec![],
             }
         }
     }
@@ -588,6 +1348,8 @@ mod test {
                 is_required: value.1,
                 vote: Vote::NoVote,
                 has_declined: false,
+                is_container: false,
+                votes: vec![],
             }
         }
     }
@@ -605,6 +1367,7 @@ mod test {
                 creation_date: DateTime::default(),
                 reviewers,
                 status: PullRequestStatus::Active,
+                repository: None,
             }
         }
     }
@@ -612,6 +1375,7 @@ mod test {
     fn fake_shuffle_teams(
         first_team: Vec<TeamMember>,
         second_team: Vec<TeamMember>,
+        _seed: u64,
     ) -> (Vec<TeamMember>, Vec<TeamMember>) {
         (first_team, second_team)
     }
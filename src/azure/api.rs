@@ -5,9 +5,10 @@ use futures::TryFutureExt;
 use reqwest::RequestBuilder;
 use reqwest::{header::AUTHORIZATION, Client};
 use serde::de::DeserializeOwned;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_repr::Deserialize_repr;
-use std::fmt::{Display, Formatter};
+use std::collections::HashMap;
+use tracing::instrument;
 use url::Url;
 
 #[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug, Hash)]
@@ -20,14 +21,20 @@ pub struct PullRequestAuthor {
     pub name: String,
 }
 
-#[derive(Deserialize_repr, Debug, PartialEq)]
+#[derive(Deserialize_repr, Debug, PartialEq, Clone, Copy, Default)]
 #[repr(i32)]
+#[allow(clippy::enum_variant_names)]
 pub enum Vote {
     Rejected = -10,
     WaitingForAuthor = -5,
+    #[default]
     NoVote = 0,
     ApprovedWithSuggestions = 5,
     Approved = 10,
+    /// Vote added to Azure DevOps after this version. The reviewer voted, so the
+    /// pull request is not waiting for them.
+    #[serde(other)]
+    Unknown = i32::MIN,
 }
 
 #[derive(Deserialize, Debug)]
@@ -38,8 +45,24 @@ pub struct PullRequestReviewer {
     pub name: String,
     #[serde(default)]
     pub is_required: bool,
+    #[serde(default)]
     pub vote: Vote,
+    /// Missing for group reviewers.
+    #[serde(default)]
     pub has_declined: bool,
+    /// Group reviewers, e.g. the teams required by a branch policy.
+    #[serde(default)]
+    pub is_container: bool,
+    /// Votes of the reviewer in the order they were cast. Filled from the pull
+    /// request threads only when the vote history is requested.
+    #[serde(skip)]
+    pub votes: Vec<VoteUpdate>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct VoteUpdate {
+    pub vote: Vote,
+    pub date: DateTime<Utc>,
 }
 
 #[derive(Deserialize, Debug, PartialEq)]
@@ -50,6 +73,9 @@ pub enum PullRequestStatus {
     NotSet,
     All,
     Completed,
+    /// Status added to Azure DevOps after this version.
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(Deserialize, Debug)]
@@ -61,8 +87,17 @@ pub struct PullRequest {
     pub url: Url,
     pub created_by: PullRequestAuthor,
     pub creation_date: DateTime<Utc>,
+    #[serde(default, deserialize_with = "skip_invalid_items")]
     pub reviewers: Vec<PullRequestReviewer>,
     pub status: PullRequestStatus,
+    #[serde(default)]
+    pub repository: Option<PullRequestRepository>,
+}
+
+/// Repository of a pull request, identified by the GUID whatever name the caller used.
+#[derive(Deserialize, Debug)]
+pub struct PullRequestRepository {
+    pub id: String,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -73,6 +108,9 @@ pub struct TeamMember {
     pub name: String,
     #[serde(default)]
     pub is_container: bool,
+    /// Mail address of the identity. Azure DevOps Server may use domain logins instead.
+    #[serde(default, rename = "uniqueName")]
+    pub mail_address: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -80,6 +118,16 @@ struct TeamMemberContainer {
     identity: TeamMember,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Repository {
+    pub name: String,
+    #[serde(default)]
+    pub is_disabled: bool,
+    #[serde(default)]
+    pub is_in_maintenance: bool,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Team {
     pub name: String,
@@ -97,15 +145,99 @@ pub struct NewPullRequestReviewer {
     pub is_required: bool,
 }
 
+/// Pull request thread. Azure DevOps records vote changes in system threads
+/// with the `VoteUpdate` type.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Thread {
+    published_date: DateTime<Utc>,
+    #[serde(default)]
+    properties: HashMap<String, ThreadProperty>,
+    #[serde(default)]
+    identities: HashMap<String, PullRequestAuthor>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ThreadProperty {
+    #[serde(rename = "$value")]
+    value: serde_json::Value,
+}
+
+/// Iteration of a pull request, i.e. a push of its source branch.
+#[derive(Deserialize, Debug)]
+struct Iteration {
+    id: u32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct IterationChanges {
+    #[serde(default, deserialize_with = "skip_invalid_items")]
+    change_entries: Vec<ChangeEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChangeEntry {
+    item: ChangeItem,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChangeItem {
+    /// Missing for some changes, e.g. of submodules.
+    #[serde(default)]
+    path: Option<String>,
+}
+
 #[derive(Deserialize, Debug)]
 struct ListResponse<T> {
     value: T,
 }
 
-pub struct AzureApi<'a> {
-    token: &'a str,
-    base_url: &'a Url,
-    project: &'a str,
+/// Items of a list. Items which can not be deserialized are skipped with a
+/// warning, so one unexpected item does not fail the whole list.
+#[derive(Debug)]
+struct TolerantList<T> {
+    items: Vec<T>,
+    skipped: usize,
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for TolerantList<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let values = Vec::<serde_json::Value>::deserialize(deserializer)?;
+        let mut list = TolerantList {
+            items: Vec::with_capacity(values.len()),
+            skipped: 0,
+        };
+        for value in values {
+            match serde_json::from_value::<T>(value) {
+                Ok(item) => list.items.push(item),
+                Err(e) => {
+                    let type_name = std::any::type_name::<T>().rsplit("::").next();
+                    tracing::warn!("Skipped invalid {}: {e}.", type_name.unwrap_or("item"));
+                    list.skipped += 1;
+                }
+            }
+        }
+        Ok(list)
+    }
+}
+
+fn skip_invalid_items<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    TolerantList::deserialize(deserializer).map(|list| list.items)
+}
+
+#[derive(Clone)]
+pub struct AzureApi {
+    token: String,
+    base_url: Url,
+    project: String,
     client: Client,
 }
 
@@ -123,19 +255,15 @@ impl ApiVersion {
     }
 }
 
-#[derive(Debug)]
-struct ResponseError {
-    response: String,
-}
-
-impl Display for ResponseError {
-    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.response)
+/// Converts the "not found" response error to `None`.
+pub fn found<T>(result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if crate::Error::find(&e).is_some_and(crate::Error::is_not_found) => Ok(None),
+        Err(e) => Err(e),
     }
 }
 
-impl std::error::Error for ResponseError {}
-
 #[cfg_attr(test, mockall::automock)]
 #[async_trait]
 pub trait AzureTeamService {
@@ -172,24 +300,101 @@ pub trait AzurePullRequestService {
     ) -> Result<()>;
 }
 
-impl<'a> AzureApi<'a> {
-    pub fn new(token: &'a str, base_url: &'a Url, project: &'a str) -> Self {
+/// Changes of the reviewers of an assigned pull request.
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait AzurePullRequestReviewersService {
+    /// Active pull requests of the repository.
+    async fn obtain_active_pull_requests(&self, repository_id: &str) -> Result<Vec<PullRequest>>;
+
+    /// Adds the reviewer or changes whether the reviewer is required.
+    async fn update_reviewer(
+        &self,
+        repository_id: &str,
+        pull_request_id: &str,
+        reviewer: NewPullRequestReviewer,
+    ) -> Result<()>;
+
+    async fn remove_reviewer(
+        &self,
+        repository_id: &str,
+        pull_request_id: &str,
+        reviewer_id: &Identifier,
+    ) -> Result<()>;
+}
+
+#[async_trait]
+pub trait AzureRepositoryService {
+    async fn repository_exists(&self, repository_id: &str) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait AzurePullRequestHistoryService {
+    /// Active and completed pull requests created since the date, newest first.
+    async fn obtain_pull_requests_since(
+        &self,
+        repository_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<PullRequest>>;
+
+    /// Vote updates of the pull request reviewers in the order they were cast.
+    async fn obtain_vote_updates(
+        &self,
+        repository_id: &str,
+        pull_request_id: usize,
+    ) -> Result<Vec<(Identifier, VoteUpdate)>>;
+}
+
+/// Completed pull requests and the files they changed.
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait AzurePullRequestChangesService {
+    /// Last completed pull requests of the repository, newest first.
+    async fn obtain_completed_pull_requests(
+        &self,
+        repository_id: &str,
+        count: usize,
+    ) -> Result<Vec<PullRequest>>;
+
+    /// Paths of the files changed by the pull request.
+    async fn obtain_changed_files(
+        &self,
+        repository_id: &str,
+        pull_request_id: usize,
+    ) -> Result<Vec<String>>;
+}
+
+#[async_trait]
+pub trait AzureRepositoriesService {
+    async fn obtain_repositories(&self, project: &str) -> Result<Vec<Repository>>;
+}
+
+impl AzureApi {
+    pub fn new(token: &str, base_url: &Url, project: &str) -> Self {
         Self {
-            token,
-            base_url,
-            project,
-            client: Client::new(),
+            token: token.to_string(),
+            base_url: base_url.clone(),
+            project: project.to_string(),
+            client: crate::http::client(),
         }
     }
 
+    /// Splits the `<project>/<repository>` identifier of a repository from another
+    /// project. Other identifiers belong to the configured project.
+    fn split_repository_id<'r>(&'r self, repository_id: &'r str) -> (&'r str, &'r str) {
+        repository_id
+            .split_once('/')
+            .unwrap_or((&self.project, repository_id))
+    }
+
     fn make_pull_requests_url(
         &self,
         repository_id: &str,
         search_creteria: PullRequestSearchCriteria,
     ) -> Result<Url> {
+        let (project, repository) = self.split_repository_id(repository_id);
         let mut url = self.base_url.join(&format!(
-            "{}/_apis/git/repositories/{}/pullrequests",
-            self.project, repository_id
+            "{project}/_apis/git/repositories/{repository}/pullrequests"
         ))?;
         let queries = [search_creteria.query(), ("searchCriteria.status", "active")];
         url.query_pairs_mut().extend_pairs(queries);
@@ -202,7 +407,7 @@ impl<'a> AzureApi<'a> {
         api_version: ApiVersion,
     ) -> Result<T> {
         let response = self.send_get_request(url, api_version).await?;
-        response.json::<T>().await.map_err(color_eyre::Report::new)
+        crate::error::json("azure", response).await
     }
 
     async fn obtain_list<T: DeserializeOwned>(
@@ -210,9 +415,18 @@ impl<'a> AzureApi<'a> {
         url: Url,
         api_version: ApiVersion,
     ) -> Result<Vec<T>> {
+        let list = self.obtain_tolerant_list(url, api_version).await?;
+        Ok(list.items)
+    }
+
+    async fn obtain_tolerant_list<T: DeserializeOwned>(
+        &self,
+        url: Url,
+        api_version: ApiVersion,
+    ) -> Result<TolerantList<T>> {
         let response = self.send_get_request(url, api_version).await?;
-        let response = response.json::<ListResponse<Vec<T>>>().await;
-        response.map(|v| v.value).map_err(color_eyre::Report::new)
+        let response = crate::error::json::<ListResponse<TolerantList<T>>>("azure", response);
+        response.await.map(|v| v.value)
     }
 
     async fn send_get_request(
@@ -243,6 +457,34 @@ impl<'a> AzureApi<'a> {
         .await
     }
 
+    async fn send_put_request<Body>(
+        &self,
+        url: Url,
+        api_version: ApiVersion,
+        body: Body,
+    ) -> Result<reqwest::Response>
+    where
+        Body: Serialize,
+    {
+        self.send_request(url, api_version, |client, url| {
+            tracing::debug!("Executing PUT request with url: {url}.");
+            client.put(url).json(&body)
+        })
+        .await
+    }
+
+    async fn send_delete_request(
+        &self,
+        url: Url,
+        api_version: ApiVersion,
+    ) -> Result<reqwest::Response> {
+        self.send_request(url, api_version, |client, url| {
+            tracing::debug!("Executing DELETE request with url: {url}.");
+            client.delete(url)
+        })
+        .await
+    }
+
     async fn send_request<F>(
         &self,
         mut url: Url,
@@ -259,23 +501,14 @@ impl<'a> AzureApi<'a> {
             .header(AUTHORIZATION, format!("Bearer {}", self.token))
             .build()?;
 
-        let response = self
-            .client
-            .execute(request)
-            .await
-            .map_err(color_eyre::Report::from)?;
-
-        if !response.status().is_success() {
-            let response = response.text().await?;
-            Err(color_eyre::Report::from(ResponseError { response }))
-        } else {
-            Ok(response)
-        }
+        let response = crate::http::execute(&self.client, "azure", request).await?;
+        crate::error::error_for_status("azure", response).await
     }
 }
 
 #[async_trait]
-impl<'a> AzureTeamService for AzureApi<'a> {
+impl AzureTeamService for AzureApi {
+    #[instrument(skip_all, fields(team = %team_id.0))]
     async fn team_members(&self, team_id: Identifier) -> Result<Vec<TeamMember>> {
         tracing::info!("Requesting team {} members.", team_id.0);
         let url = self.base_url.join(&format!(
@@ -291,6 +524,7 @@ impl<'a> AzureTeamService for AzureApi<'a> {
             })
     }
 
+    #[instrument(skip_all)]
     async fn get_teams(&self) -> Result<Vec<Team>> {
         tracing::info!("Requesting teams in project {}.", self.project);
         let url = self
@@ -301,21 +535,23 @@ impl<'a> AzureTeamService for AzureApi<'a> {
 }
 
 #[async_trait]
-impl<'a> AzurePullRequestService for AzureApi<'a> {
+impl AzurePullRequestService for AzureApi {
+    #[instrument(skip(self))]
     async fn obtain_pull_request(
         &self,
         repository_id: &str,
         request_id: &str,
     ) -> Result<PullRequest> {
         tracing::info!("Requesting pull request {request_id} in repository {repository_id}.");
+        let (project, repository) = self.split_repository_id(repository_id);
         let url = self.base_url.join(&format!(
-            "{}/_apis/git/repositories/{}/pullrequests/{}",
-            self.project, repository_id, request_id
+            "{project}/_apis/git/repositories/{repository}/pullrequests/{request_id}"
         ))?;
         self.obtain_single_item::<PullRequest>(url, ApiVersion::Six)
             .await
     }
 
+    #[instrument(skip(self, reviewers), fields(reviewers = reviewers.len()))]
     async fn add_reviewers_to_pull_request(
         &self,
         repository_id: &str,
@@ -323,9 +559,9 @@ impl<'a> AzurePullRequestService for AzureApi<'a> {
         reviewers: Vec<NewPullRequestReviewer>,
     ) -> Result<()> {
         tracing::info!("Creating reviewers for {pull_request_id} in repository {repository_id}. Reviewers: {reviewers:?}");
+        let (project, repository) = self.split_repository_id(repository_id);
         let url = self.base_url.join(&format!(
-            "{}/_apis/git/repositories/{}/pullrequests/{}/reviewers",
-            self.project, repository_id, pull_request_id
+            "{project}/_apis/git/repositories/{repository}/pullrequests/{pull_request_id}/reviewers"
         ))?;
         self.send_post_request(url, ApiVersion::Six, reviewers)
             .map_ok(|_| ())
@@ -334,7 +570,92 @@ impl<'a> AzurePullRequestService for AzureApi<'a> {
 }
 
 #[async_trait]
-impl<'a> AzurePullRequestsService for AzureApi<'a> {
+impl AzurePullRequestReviewersService for AzureApi {
+    #[instrument(skip(self))]
+    async fn obtain_active_pull_requests(&self, repository_id: &str) -> Result<Vec<PullRequest>> {
+        tracing::info!("Requesting active pull requests in repository {repository_id}.");
+        let (project, repository) = self.split_repository_id(repository_id);
+        let mut url = self.base_url.join(&format!(
+            "{project}/_apis/git/repositories/{repository}/pullrequests"
+        ))?;
+        url.query_pairs_mut()
+            .append_pair("searchCriteria.status", "active");
+        self.obtain_list::<PullRequest>(url, ApiVersion::Six).await
+    }
+
+    #[instrument(skip(self))]
+    async fn update_reviewer(
+        &self,
+        repository_id: &str,
+        pull_request_id: &str,
+        reviewer: NewPullRequestReviewer,
+    ) -> Result<()> {
+        tracing::info!(
+            "Updating reviewer of {pull_request_id} in repository {repository_id}: {reviewer:?}"
+        );
+        let (project, repository) = self.split_repository_id(repository_id);
+        let url = self.base_url.join(&format!(
+            "{project}/_apis/git/repositories/{repository}/pullrequests/{pull_request_id}/reviewers/{}",
+            reviewer.id.0
+        ))?;
+        self.send_put_request(url, ApiVersion::Six, reviewer)
+            .map_ok(|_| ())
+            .await
+    }
+
+    #[instrument(skip(self))]
+    async fn remove_reviewer(
+        &self,
+        repository_id: &str,
+        pull_request_id: &str,
+        reviewer_id: &Identifier,
+    ) -> Result<()> {
+        tracing::info!(
+            "Removing reviewer {} from {pull_request_id} in repository {repository_id}.",
+            reviewer_id.0
+        );
+        let (project, repository) = self.split_repository_id(repository_id);
+        let url = self.base_url.join(&format!(
+            "{project}/_apis/git/repositories/{repository}/pullrequests/{pull_request_id}/reviewers/{}",
+            reviewer_id.0
+        ))?;
+        self.send_delete_request(url, ApiVersion::Six)
+            .map_ok(|_| ())
+            .await
+    }
+}
+
+#[async_trait]
+impl AzureRepositoryService for AzureApi {
+    #[instrument(skip(self))]
+    async fn repository_exists(&self, repository_id: &str) -> Result<bool> {
+        tracing::info!("Requesting repository {repository_id}.");
+        let (project, repository) = self.split_repository_id(repository_id);
+        let url = self
+            .base_url
+            .join(&format!("{project}/_apis/git/repositories/{repository}"))?;
+        let repository = self
+            .obtain_single_item::<serde_json::Value>(url, ApiVersion::Six)
+            .await;
+        found(repository).map(|v| v.is_some())
+    }
+}
+
+#[async_trait]
+impl AzureRepositoriesService for AzureApi {
+    #[instrument(skip(self))]
+    async fn obtain_repositories(&self, project: &str) -> Result<Vec<Repository>> {
+        tracing::info!("Requesting repositories in project {project}.");
+        let url = self
+            .base_url
+            .join(&format!("{project}/_apis/git/repositories"))?;
+        self.obtain_list::<Repository>(url, ApiVersion::Six).await
+    }
+}
+
+#[async_trait]
+impl AzurePullRequestsService for AzureApi {
+    #[instrument(skip(self, search_creteria, filter))]
     async fn obtain_pull_requests<F>(
         &self,
         repository_id: &str,
@@ -353,10 +674,9 @@ impl<'a> AzurePullRequestsService for AzureApi<'a> {
             .into_iter()
             .filter(|v| v.reviewers.iter().any(&filter))
             .map(|mut request| {
-                let request_path = format!(
-                    "{}/_git/{}/pullrequest/{}",
-                    self.project, repository_id, request.id
-                );
+                let (project, repository) = self.split_repository_id(repository_id);
+                let request_path =
+                    format!("{project}/_git/{repository}/pullrequest/{}", request.id);
                 request.url = self
                     .base_url
                     .join(&request_path)
@@ -368,6 +688,153 @@ impl<'a> AzurePullRequestsService for AzureApi<'a> {
     }
 }
 
+/// Page size of the pull request history requests.
+const HISTORY_PAGE_SIZE: usize = 100;
+
+#[async_trait]
+impl AzurePullRequestHistoryService for AzureApi {
+    #[instrument(skip(self))]
+    async fn obtain_pull_requests_since(
+        &self,
+        repository_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<PullRequest>> {
+        tracing::info!(
+            "Requesting pull requests created since {since} in repository {repository_id}."
+        );
+        let (project, repository) = self.split_repository_id(repository_id);
+        let mut pull_requests = Vec::new();
+        for skip in (0..).step_by(HISTORY_PAGE_SIZE) {
+            let mut url = self.base_url.join(&format!(
+                "{project}/_apis/git/repositories/{repository}/pullrequests"
+            ))?;
+            url.query_pairs_mut()
+                .append_pair("searchCriteria.status", "all")
+                .append_pair("$top", &HISTORY_PAGE_SIZE.to_string())
+                .append_pair("$skip", &skip.to_string());
+            let page = self
+                .obtain_tolerant_list::<PullRequest>(url, ApiVersion::Six)
+                .await?;
+            let is_last_page = page.items.len() + page.skipped < HISTORY_PAGE_SIZE
+                || page.items.iter().any(|r| r.creation_date < since);
+            pull_requests.extend(
+                page.items.into_iter().filter(|r| {
+                    r.creation_date >= since && r.status != PullRequestStatus::Abandoned
+                }),
+            );
+            if is_last_page {
+                break;
+            }
+        }
+        for request in &mut pull_requests {
+            let request_path = format!("{project}/_git/{repository}/pullrequest/{}", request.id);
+            request.url = self.base_url.join(&request_path)?;
+        }
+        Ok(pull_requests)
+    }
+
+    #[instrument(skip(self))]
+    async fn obtain_vote_updates(
+        &self,
+        repository_id: &str,
+        pull_request_id: usize,
+    ) -> Result<Vec<(Identifier, VoteUpdate)>> {
+        tracing::debug!(
+            "Requesting threads of pull request {pull_request_id} in repository {repository_id}."
+        );
+        let (project, repository) = self.split_repository_id(repository_id);
+        let url = self.base_url.join(&format!(
+            "{project}/_apis/git/repositories/{repository}/pullrequests/{pull_request_id}/threads"
+        ))?;
+        let threads = self.obtain_list::<Thread>(url, ApiVersion::Six).await?;
+        let mut updates = threads
+            .into_iter()
+            .filter_map(Thread::into_vote_update)
+            .collect::<Vec<_>>();
+        updates.sort_by_key(|(_, update)| update.date);
+        Ok(updates)
+    }
+}
+
+#[async_trait]
+impl AzurePullRequestChangesService for AzureApi {
+    #[instrument(skip(self))]
+    async fn obtain_completed_pull_requests(
+        &self,
+        repository_id: &str,
+        count: usize,
+    ) -> Result<Vec<PullRequest>> {
+        tracing::info!("Requesting {count} completed pull requests in repository {repository_id}.");
+        let (project, repository) = self.split_repository_id(repository_id);
+        let mut url = self.base_url.join(&format!(
+            "{project}/_apis/git/repositories/{repository}/pullrequests"
+        ))?;
+        url.query_pairs_mut()
+            .append_pair("searchCriteria.status", "completed")
+            .append_pair("$top", &count.to_string());
+        self.obtain_list::<PullRequest>(url, ApiVersion::Six).await
+    }
+
+    #[instrument(skip(self))]
+    async fn obtain_changed_files(
+        &self,
+        repository_id: &str,
+        pull_request_id: usize,
+    ) -> Result<Vec<String>> {
+        tracing::debug!(
+            "Requesting changes of pull request {pull_request_id} in repository {repository_id}."
+        );
+        let (project, repository) = self.split_repository_id(repository_id);
+        let iterations_path = format!(
+            "{project}/_apis/git/repositories/{repository}/pullrequests/{pull_request_id}/iterations"
+        );
+        let url = self.base_url.join(&iterations_path)?;
+        let iterations = self.obtain_list::<Iteration>(url, ApiVersion::Six).await?;
+        // Changes of the last iteration are compared with the target branch.
+        let Some(iteration) = iterations.iter().map(|i| i.id).max() else {
+            return Ok(vec![]);
+        };
+        let mut url = self
+            .base_url
+            .join(&format!("{iterations_path}/{iteration}/changes"))?;
+        url.query_pairs_mut().append_pair("$compareTo", "0");
+        let changes = self
+            .obtain_single_item::<IterationChanges>(url, ApiVersion::Six)
+            .await?;
+        let files = changes
+            .change_entries
+            .into_iter()
+            .filter_map(|entry| entry.item.path)
+            .collect();
+        Ok(files)
+    }
+}
+
+impl Thread {
+    fn property(&self, name: &str) -> Option<String> {
+        match &self.properties.get(name)?.value {
+            serde_json::Value::String(value) => Some(value.clone()),
+            serde_json::Value::Number(value) => Some(value.to_string()),
+            _ => None,
+        }
+    }
+
+    fn into_vote_update(mut self) -> Option<(Identifier, VoteUpdate)> {
+        if self.property("CodeReviewThreadType")? != "VoteUpdate" {
+            return None;
+        }
+        let vote = self.property("CodeReviewVoteResult")?.parse::<i32>().ok()?;
+        let vote = serde_json::from_value::<Vote>(vote.into()).ok()?;
+        let voter = self.property("CodeReviewVotedByIdentity")?;
+        let voter = self.identities.remove(&voter)?;
+        let update = VoteUpdate {
+            vote,
+            date: self.published_date,
+        };
+        Some((voter.id, update))
+    }
+}
+
 impl PullRequestSearchCriteria {
     fn query(&self) -> (&str, &str) {
         match self {
@@ -376,3 +843,151 @@ impl PullRequestSearchCriteria {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn unexpected_values_deserialized() -> Result<()> {
+        let request = serde_json::from_value::<PullRequest>(json!({
+            "pullRequestId": 1,
+            "title": "Title",
+            "url": "https://dev.azure.com/pr/1",
+            "createdBy": { "id": "author", "displayName": "Author" },
+            "creationDate": "2023-07-01T10:00:00Z",
+            "status": "draftReview",
+            "reviewers": [
+                { "id": "alice", "displayName": "Alice", "vote": 7, "hasDeclined": false },
+                { "id": "group", "displayName": "[Project]\\Reviewers", "isContainer": true },
+            ],
+        }))?;
+
+        assert_eq!(request.status, PullRequestStatus::Unknown);
+        assert_eq!(request.reviewers[0].vote, Vote::Unknown);
+        assert_eq!(request.reviewers[1].vote, Vote::NoVote);
+        assert!(!request.reviewers[1].has_declined);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_items_skipped() -> Result<()> {
+        let server = MockServer::start().await;
+        let pull_request = |id: usize, title: serde_json::Value, reviewer_id: serde_json::Value| {
+            json!({
+                "pullRequestId": id,
+                "title": title,
+                "url": format!("https://dev.azure.com/pr/{id}"),
+                "createdBy": { "id": "author", "displayName": "Author" },
+                "creationDate": "2023-07-01T10:00:00Z",
+                "status": "active",
+                "reviewers": [
+                    { "id": "alice", "displayName": "Alice", "vote": 0 },
+                    { "id": reviewer_id, "displayName": "Broken", "vote": 0 },
+                ],
+            })
+        };
+        Mock::given(method("GET"))
+            .and(path("/project/_apis/git/repositories/repo/pullrequests"))
+            .and(query_param("searchCriteria.reviewerId", "alice"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    pull_request(1, json!("First"), json!("bob")),
+                    pull_request(2, json!(null), json!("bob")),
+                    pull_request(3, json!("Third"), json!(["not", "an", "id"])),
+                ],
+            })))
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&format!("{}/", server.uri()))?;
+        let api = AzureApi::new("token", &base_url, "project");
+        let reviewer = PullRequestSearchCriteria::Reviewer(Identifier("alice".to_string()));
+        let requests = api.obtain_pull_requests("repo", reviewer, |_| true).await?;
+
+        let requests = requests
+            .iter()
+            .map(|r| (r.id, r.reviewers.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(requests, [(1, 2), (3, 1)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn vote_updates_read_from_threads() -> Result<()> {
+        let server = MockServer::start().await;
+        let vote_thread = |date: &str, vote: &str| {
+            json!({
+                "publishedDate": date,
+                "properties": {
+                    "CodeReviewThreadType": { "$type": "System.String", "$value": "VoteUpdate" },
+                    "CodeReviewVoteResult": { "$type": "System.String", "$value": vote },
+                    "CodeReviewVotedByIdentity": { "$type": "System.String", "$value": "1" },
+                },
+                "identities": { "1": { "id": "alice", "displayName": "Alice" } },
+            })
+        };
+        Mock::given(method("GET"))
+            .and(path(
+                "/project/_apis/git/repositories/repo/pullrequests/7/threads",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    vote_thread("2023-07-01T12:00:00Z", "10"),
+                    { "publishedDate": "2023-07-01T10:30:00Z", "comments": [] },
+                    vote_thread("2023-07-01T11:00:00Z", "-5"),
+                ],
+            })))
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&format!("{}/", server.uri()))?;
+        let api = AzureApi::new("token", &base_url, "project");
+        let updates = api.obtain_vote_updates("repo", 7).await?;
+
+        let votes = updates
+            .iter()
+            .map(|(id, update)| (id.0.as_str(), update.vote))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            votes,
+            [("alice", Vote::WaitingForAuthor), ("alice", Vote::Approved)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn changed_files_read_from_last_iteration() -> Result<()> {
+        let server = MockServer::start().await;
+        let iterations_path = "/project/_apis/git/repositories/repo/pullrequests/7/iterations";
+        Mock::given(method("GET"))
+            .and(path(iterations_path))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [{ "id": 1 }, { "id": 3 }, { "id": 2 }],
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("{iterations_path}/3/changes")))
+            .and(query_param("$compareTo", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "changeEntries": [
+                    { "changeType": "edit", "item": { "path": "/src/lib.rs" } },
+                    { "changeType": "add", "item": {} },
+                    { "changeType": "delete", "item": { "path": "/README.md" } },
+                ],
+            })))
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&format!("{}/", server.uri()))?;
+        let api = AzureApi::new("token", &base_url, "project");
+        let files = api.obtain_changed_files("repo", 7).await?;
+
+        assert_eq!(files, ["/src/lib.rs", "/README.md"]);
+        Ok(())
+    }
+}
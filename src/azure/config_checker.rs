@@ -0,0 +1,279 @@
+use super::api::{found, AzureRepositoryService, AzureTeamService, Identifier, TeamMember};
+use crate::diagnostics::Diagnostics;
+use crate::notifier::UserDirectory;
+use crate::Error;
+use async_trait::async_trait;
+use color_eyre::Report;
+use itertools::Itertools;
+use reqwest::StatusCode;
+use std::collections::HashSet;
+
+#[async_trait]
+pub trait ConfigChecker {
+    /// Checks that the configured teams and repositories exist and that members
+    /// of the teams can be found in the user directory.
+    async fn check(&self, directory: Option<&UserDirectory>) -> Diagnostics;
+}
+
+pub struct AzureConfigChecker<'a, Api>
+where
+    Api: AzureTeamService,
+    Api: AzureRepositoryService,
+{
+    api: Api,
+    team_name: &'a str,
+    reviewers_teams: Vec<&'a str>,
+    repositories: Vec<String>,
+}
+
+impl<'a, Api> AzureConfigChecker<'a, Api>
+where
+    Api: AzureTeamService,
+    Api: AzureRepositoryService,
+{
+    pub fn new(
+        api: Api,
+        team_name: &'a str,
+        reviewers_teams: Vec<&'a str>,
+        repositories: Vec<String>,
+    ) -> Self {
+        Self {
+            api,
+            team_name,
+            reviewers_teams,
+            repositories,
+        }
+    }
+
+    async fn check_team(
+        &self,
+        team_name: &str,
+        known_teams: &HashSet<String>,
+        diagnostics: &mut Diagnostics,
+    ) -> Option<Vec<TeamMember>> {
+        let members = self
+            .api
+            .team_members(Identifier(team_name.to_string()))
+            .await;
+        match found(members) {
+            Ok(Some(members)) if known_teams.contains(team_name) || !members.is_empty() => {
+                diagnostics.ok(format!(
+                    "Team \"{team_name}\" has {} members.",
+                    members.len()
+                ));
+                Some(members)
+            }
+            Ok(_) => {
+                diagnostics.error(format!("Team \"{team_name}\" was not found."));
+                None
+            }
+            Err(e) => {
+                let action = format!("obtain members of team \"{team_name}\"");
+                diagnostics.error(describe_error(&action, &e));
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<'a, Api> ConfigChecker for AzureConfigChecker<'a, Api>
+where
+    Api: AzureTeamService + Send + Sync,
+    Api: AzureRepositoryService + Send + Sync,
+{
+    async fn check(&self, directory: Option<&UserDirectory>) -> Diagnostics {
+        let mut diagnostics = Diagnostics::default();
+        let known_teams = match self.api.get_teams().await {
+            Ok(teams) => teams.into_iter().map(|team| team.name).collect(),
+            Err(e) => {
+                diagnostics.error(describe_error("list teams", &e));
+                return diagnostics;
+            }
+        };
+
+        let team_names = std::iter::once(self.team_name)
+            .chain(self.reviewers_teams.iter().copied())
+            .unique()
+            .collect::<Vec<_>>();
+        for team_name in team_names {
+            let Some(members) = self
+                .check_team(team_name, &known_teams, &mut diagnostics)
+                .await
+            else {
+                continue;
+            };
+            let Some(directory) = directory else {
+                continue;
+            };
+            let unmatched = members
+                .iter()
+                .filter(|m| !directory.contains(&m.name, m.mail_address.as_deref()))
+                .map(|m| m.name.as_str())
+                .join(", ");
+            if !unmatched.is_empty() {
+                diagnostics.warning(format!(
+                    "Members of team \"{team_name}\" not found in the user directory: {unmatched}."
+                ));
+            }
+        }
+
+        for repository_id in &self.repositories {
+            match self.api.repository_exists(repository_id).await {
+                Ok(true) => diagnostics.ok(format!("Repository \"{repository_id}\" exists.")),
+                Ok(false) => {
+                    diagnostics.error(format!("Repository \"{repository_id}\" was not found."))
+                }
+                Err(e) => {
+                    let action = format!("obtain repository \"{repository_id}\"");
+                    diagnostics.error(describe_error(&action, &e));
+                }
+            }
+        }
+        if !self.reviewers_teams.is_empty() {
+            // Nothing is written while checking, so only the read scopes are known.
+            diagnostics.warning(
+                "Write access of the token to the pull requests was not verified, adding reviewers needs it.",
+            );
+        }
+        diagnostics
+    }
+}
+
+fn describe_error(action: &str, error: &Report) -> String {
+    let status = match Error::find(error) {
+        Some(Error::Auth { status, .. }) => StatusCode::from_u16(*status).ok(),
+        _ => None,
+    };
+    match status {
+        Some(StatusCode::UNAUTHORIZED) => {
+            format!(
+                "Unable to {action}: the token is invalid, expired or lacks the required scope."
+            )
+        }
+        Some(StatusCode::FORBIDDEN) => {
+            format!("Unable to {action}: the token lacks the required scope or permission.")
+        }
+        _ => format!("Unable to {action}: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::api::Team;
+    use super::*;
+    use crate::diagnostics::Severity;
+    use crate::notifier::DirectoryUser;
+    use color_eyre::Result;
+    use mockall::mock;
+    use mockall::predicate::eq;
+
+    mock! {
+        Api {}
+
+        #[async_trait]
+        impl AzureTeamService for Api {
+            async fn team_members(&self, team_id: Identifier) -> Result<Vec<TeamMember>>;
+            async fn get_teams(&self) -> Result<Vec<Team>>;
+        }
+
+        #[async_trait]
+        impl AzureRepositoryService for Api {
+            async fn repository_exists(&self, repository_id: &str) -> Result<bool>;
+        }
+    }
+
+    fn member(name: &str) -> TeamMember {
+        TeamMember {
+            id: Identifier(name.to_string()),
+            name: name.to_string(),
+            is_container: false,
+            mail_address: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_teams_repositories_and_users_reported() {
+        let mut api = MockApi::new();
+        api.expect_get_teams().returning(|| {
+            Ok(vec![Team {
+                name: "Developers".to_string(),
+            }])
+        });
+        api.expect_team_members()
+            .with(eq(Identifier("Developers".to_string())))
+            .returning(|_| Ok(vec![member("Alice"), member("Bob")]));
+        api.expect_team_members()
+            .with(eq(Identifier("Reviewers".to_string())))
+            .returning(|_| {
+                let message = "Not found".to_string();
+                let service = "azure".to_string();
+                Err(Error::NotFound { service, message }.into())
+            });
+        api.expect_repository_exists()
+            .with(eq("app"))
+            .returning(|_| Ok(true));
+        api.expect_repository_exists()
+            .with(eq("typo"))
+            .returning(|_| Ok(false));
+
+        let checker = AzureConfigChecker::new(
+            api,
+            "Developers",
+            vec!["Developers", "Reviewers"],
+            vec!["app".to_string(), "typo".to_string()],
+        );
+        let directory = UserDirectory::Listed(vec![DirectoryUser {
+            id: "U1".to_string(),
+            name: "Alice".to_string(),
+            email: None,
+            is_available: true,
+        }]);
+        let diagnostics = checker.check(Some(&directory)).await;
+
+        let findings = diagnostics
+            .findings()
+            .iter()
+            .map(|f| (f.severity, f.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            findings,
+            [
+                (Severity::Ok, "Team \"Developers\" has 2 members."),
+                (
+                    Severity::Warning,
+                    "Members of team \"Developers\" not found in the user directory: Bob."
+                ),
+                (Severity::Error, "Team \"Reviewers\" was not found."),
+                (Severity::Ok, "Repository \"app\" exists."),
+                (Severity::Error, "Repository \"typo\" was not found."),
+                (
+                    Severity::Warning,
+                    "Write access of the token to the pull requests was not verified, adding reviewers needs it."
+                ),
+            ]
+        );
+        assert_eq!(diagnostics.errors_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn unauthorized_token_reported() {
+        let mut api = MockApi::new();
+        api.expect_get_teams().returning(|| {
+            Err(Error::Auth {
+                service: "azure".to_string(),
+                status: StatusCode::UNAUTHORIZED.as_u16(),
+                message: String::new(),
+            }
+            .into())
+        });
+
+        let checker = AzureConfigChecker::new(api, "Developers", vec![], vec![]);
+        let diagnostics = checker.check(None).await;
+
+        assert_eq!(diagnostics.errors_count(), 1);
+        assert!(diagnostics.findings()[0]
+            .message
+            .starts_with("Unable to list teams: the token is invalid"));
+    }
+}
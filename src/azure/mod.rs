@@ -1,36 +1,55 @@
-pub use self::pull_requests_provider::ReviewerRequestsProvider;
-use self::{
-    add_reviewers_service::AddReviewersServiceImpl, api::AzureApi,
+pub use self::pull_requests_provider::{
+    FormattedReviewerRequests, MemberError, MessageFormat, RepoRequests, ReviewerRequests,
+    ReviewerRequestsProvider, TeamRequests,
+};
+pub use self::{
+    add_reviewers_service::AddReviewersServiceImpl,
     pull_requests_provider::AzureReviewerRequestsProvider,
 };
-pub use add_reviewers_service::{AddReviewersService, AzureTeam, ReviewersConfig};
+pub use add_reviewers_service::{
+    AddReviewersService, AuthorAffinity, AzureTeam, OptionalReviewersStrategy, ReviewersConfig,
+};
+use api::AzureApi;
+pub use config_checker::{AzureConfigChecker, ConfigChecker};
+pub use rebalance_reviewers_service::{
+    AbsentReviewers, RebalanceReviewersService, RebalanceReviewersServiceImpl,
+};
+pub use repository_discovery::RepositoryDiscovery;
+pub use review_stats::{
+    AzureReviewStatsProvider, DurationStats, ReviewStats, ReviewStatsProvider, Stats, StatsPeriod,
+};
 
 use url::Url;
 
 mod add_reviewers_service;
-mod api;
+pub mod api;
+mod config_checker;
 mod pull_requests_provider;
+mod rebalance_reviewers_service;
+mod repository_discovery;
+mod review_stats;
+mod rotation;
 
-pub fn make_pull_requests_provider<'a>(
-    token: &'a str,
-    base_url: &'a Url,
-    project: &'a str,
-    team_name: &'a str,
+pub fn make_pull_requests_provider(
+    token: &str,
+    base_url: &Url,
+    project: &str,
+    team_name: &str,
     repositories: Vec<String>,
-) -> impl ReviewerRequestsProvider + 'a {
+) -> impl ReviewerRequestsProvider {
     let api = AzureApi::new(token, base_url, project);
     AzureReviewerRequestsProvider::new(api, team_name, repositories)
 }
 
-pub fn make_add_reviewers_service<'a>(
-    token: &'a str,
-    base_url: &'a Url,
-    project: &'a str,
-    team_name: &'a str,
+pub fn make_add_reviewers_service(
+    token: &str,
+    base_url: &Url,
+    project: &str,
+    team_name: &str,
     pull_request_id: String,
     repository_id: String,
-    reviewers_config: ReviewersConfig<'a>,
-) -> impl AddReviewersService + 'a {
+    reviewers_config: ReviewersConfig,
+) -> impl AddReviewersService {
     let api = AzureApi::new(token, base_url, project);
     AddReviewersServiceImpl::new(
         api,
@@ -40,3 +59,55 @@ pub fn make_add_reviewers_service<'a>(
         reviewers_config,
     )
 }
+
+pub fn make_rebalance_reviewers_service(
+    token: &str,
+    base_url: &Url,
+    project: &str,
+    team_name: &str,
+    repositories: Vec<String>,
+    reviewers_config: ReviewersConfig,
+    absent_reviewers: AbsentReviewers,
+) -> impl RebalanceReviewersService {
+    let api = AzureApi::new(token, base_url, project);
+    RebalanceReviewersServiceImpl::new(
+        api,
+        team_name,
+        repositories,
+        reviewers_config,
+        absent_reviewers,
+    )
+}
+
+pub fn make_config_checker<'a>(
+    token: &'a str,
+    base_url: &'a Url,
+    project: &'a str,
+    team_name: &'a str,
+    reviewers_teams: Vec<&'a str>,
+    repositories: Vec<String>,
+) -> impl ConfigChecker + 'a {
+    let api = AzureApi::new(token, base_url, project);
+    AzureConfigChecker::new(api, team_name, reviewers_teams, repositories)
+}
+
+pub fn make_review_stats_provider(
+    token: &str,
+    base_url: &Url,
+    project: &str,
+    team_name: &str,
+    repositories: Vec<String>,
+) -> impl ReviewStatsProvider {
+    let api = AzureApi::new(token, base_url, project);
+    AzureReviewStatsProvider::new(api, team_name, repositories)
+}
+
+pub async fn discover_repositories(
+    token: &str,
+    base_url: &Url,
+    project: &str,
+    discovery: &RepositoryDiscovery,
+) -> color_eyre::Result<Vec<String>> {
+    let api = AzureApi::new(token, base_url, project);
+    discovery.discover(&api, project).await
+}
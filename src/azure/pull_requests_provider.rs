@@ -1,64 +1,88 @@
 use super::api::{
     AzurePullRequestsService, AzureTeamService, Identifier, PullRequest, PullRequestReviewer,
-    PullRequestSearchCriteria, Vote,
+    PullRequestSearchCriteria, TeamMember, Vote,
 };
 use async_trait::async_trait;
 use chrono::{Duration, Utc};
 use color_eyre::Result;
 use futures::TryFutureExt;
 use std::fmt::{Display, Formatter};
+use tracing::Instrument;
 use url::Url;
 
-struct RepoRequests {
+#[derive(Debug)]
+pub struct RepoRequests {
     repo_id: String,
     pull_requests: Vec<PullRequest>,
 }
 
+#[derive(Debug)]
 pub struct ReviewerRequests {
     pub reviewer_name: String,
+    pub reviewer_email: Option<String>,
     waiting_for_review: Vec<RepoRequests>,
     waiting_by_reviewers: Vec<RepoRequests>,
 }
 
+/// Pull requests of the team members. Members whose pull requests could not be
+/// obtained are listed in `failures`.
+#[derive(Debug)]
+pub struct TeamRequests {
+    pub requests: Vec<ReviewerRequests>,
+    pub failures: Vec<MemberError>,
+}
+
+#[derive(Debug)]
+pub struct MemberError {
+    pub member: String,
+    pub error: crate::Error,
+}
+
 #[async_trait]
 pub trait ReviewerRequestsProvider {
-    async fn pull_requests<F>(&self, include_user: F) -> Result<Vec<ReviewerRequests>>
+    async fn pull_requests<F>(&self, include_user: F) -> Result<TeamRequests>
     where
-        F: Fn(&str) -> bool + Send + Sync;
+        F: Fn(&TeamMember) -> bool + Send + Sync;
 }
 
-pub struct AzureReviewerRequestsProvider<'a, Service>
+pub struct AzureReviewerRequestsProvider<Service>
 where
     Service: AzureTeamService,
     Service: AzurePullRequestsService,
 {
     api: Service,
-    team_name: &'a str,
+    team_name: String,
     repositories: Vec<String>,
 }
 
 #[async_trait]
-impl<'a, Service> ReviewerRequestsProvider for AzureReviewerRequestsProvider<'a, Service>
+impl<Service> ReviewerRequestsProvider for AzureReviewerRequestsProvider<Service>
 where
     Service: AzureTeamService + Send + Sync,
     Service: AzurePullRequestsService + Send + Sync,
 {
-    async fn pull_requests<F>(&self, include_user: F) -> Result<Vec<ReviewerRequests>>
+    async fn pull_requests<F>(&self, include_user: F) -> Result<TeamRequests>
     where
-        F: Fn(&str) -> bool + Send + Sync,
+        F: Fn(&TeamMember) -> bool + Send + Sync,
     {
         let teams = self.api.get_teams().await?;
         let dev_team = teams.into_iter().find(|v| v.name == self.team_name);
         let Some(dev_team) = dev_team else {
             tracing::info!("Team was not found.");
-            return Ok(vec![]);
+            return Ok(TeamRequests {
+                requests: vec![],
+                failures: vec![],
+            });
         };
         let members = self.api.team_members(Identifier(dev_team.name)).await?;
         let requests_iter = members
             .into_iter()
-            .filter(|member| include_user(&member.name))
+            .filter(|member| include_user(member))
             .map(|member| {
+                let span = tracing::info_span!("reviewer", name = %member.name);
+                let failed_member = member.name.clone();
                 let reviewer_name = member.name;
+                let reviewer_email = member.mail_address.filter(|v| v.contains('@'));
                 let requests = self.repositories.iter().map(|repo_id| {
                     let member_id = member.id.clone();
                     tracing::info!(
@@ -72,7 +96,7 @@ where
                             move |r| r.should_be_shown_to_reviewer(&member_id),
                         )
                         .map_ok(move |mut pull_requests| {
-                            pull_requests.sort_by(|a, b| a.creation_date.cmp(&b.creation_date));
+                            pull_requests.sort_by_key(|r| r.creation_date);
                             RepoRequests {
                                 repo_id: repo_id.clone(),
                                 pull_requests,
@@ -92,41 +116,52 @@ where
                             |r| r.should_be_shown_to_creator(),
                         )
                         .map_ok(move |mut pull_requests| {
-                            pull_requests.sort_by(|a, b| a.creation_date.cmp(&b.creation_date));
+                            pull_requests.sort_by_key(|r| r.creation_date);
                             RepoRequests {
                                 repo_id: repo_id.clone(),
                                 pull_requests,
                             }
                         })
                 });
-                futures::future::try_join_all(requests).and_then(|waiting_for_review| {
-                    futures::future::try_join_all(waiting_by_reviewers).map_ok(
-                        |waiting_by_reviewers| ReviewerRequests {
-                            reviewer_name,
-                            waiting_for_review: waiting_for_review
-                                .into_iter()
-                                .filter(|r| !r.pull_requests.is_empty())
-                                .collect(),
-                            waiting_by_reviewers: waiting_by_reviewers
-                                .into_iter()
-                                .filter(|r| !r.pull_requests.is_empty())
-                                .collect(),
-                        },
-                    )
-                })
+                let requests = futures::future::try_join_all(requests)
+                    .and_then(|waiting_for_review| {
+                        futures::future::try_join_all(waiting_by_reviewers).map_ok(
+                            |waiting_by_reviewers| ReviewerRequests {
+                                reviewer_name,
+                                reviewer_email,
+                                waiting_for_review: waiting_for_review
+                                    .into_iter()
+                                    .filter(|r| !r.pull_requests.is_empty())
+                                    .collect(),
+                                waiting_by_reviewers: waiting_by_reviewers
+                                    .into_iter()
+                                    .filter(|r| !r.pull_requests.is_empty())
+                                    .collect(),
+                            },
+                        )
+                    })
+                    .instrument(span);
+                (failed_member, requests)
             });
-        let mut results = Vec::<ReviewerRequests>::new();
-        for member_request in requests_iter {
+        let mut results = TeamRequests {
+            requests: vec![],
+            failures: vec![],
+        };
+        for (member, member_request) in requests_iter {
             let result = member_request.await;
             match result {
                 Ok(r) if !r.waiting_for_review.is_empty() || !r.waiting_by_reviewers.is_empty() => {
-                    results.push(r);
+                    results.requests.push(r);
                 }
                 Ok(r) => {
                     tracing::info!("There're no requests for {:?}", r.reviewer_name);
                 }
                 Err(error) => {
-                    tracing::error!("Failed to obtain Pull Request list with error: {error:?}");
+                    tracing::error!(
+                        "Failed to obtain Pull Request list of {member} with error: {error:?}"
+                    );
+                    let error = error.into();
+                    results.failures.push(MemberError { member, error });
                 }
             }
         }
@@ -134,38 +169,110 @@ where
     }
 }
 
-impl<'a, Service> AzureReviewerRequestsProvider<'a, Service>
+impl<Service> AzureReviewerRequestsProvider<Service>
 where
     Service: AzureTeamService,
     Service: AzurePullRequestsService,
 {
-    pub fn new(api: Service, team_name: &'a str, repositories: Vec<String>) -> Self {
+    pub fn new(api: Service, team_name: &str, repositories: Vec<String>) -> Self {
         Self {
             api,
-            team_name,
+            team_name: team_name.to_string(),
             repositories,
         }
     }
 }
 
+/// Markup used to render reviewer requests in a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageFormat {
+    SlackMarkdown,
+    Html,
+    Markdown,
+    PlainText,
+}
+
+pub struct FormattedReviewerRequests<'a> {
+    requests: &'a ReviewerRequests,
+    format: MessageFormat,
+}
+
+impl ReviewerRequests {
+    #[cfg(test)]
+    pub fn empty(reviewer_name: String) -> Self {
+        Self {
+            reviewer_name,
+            reviewer_email: None,
+            waiting_for_review: vec![],
+            waiting_by_reviewers: vec![],
+        }
+    }
+
+    #[cfg(test)]
+    pub fn new(
+        reviewer_name: String,
+        waiting_for_review: Vec<RepoRequests>,
+        waiting_by_reviewers: Vec<RepoRequests>,
+    ) -> Self {
+        Self {
+            reviewer_name,
+            reviewer_email: None,
+            waiting_for_review,
+            waiting_by_reviewers,
+        }
+    }
+
+    /// Pull requests waiting for the reviewer's review, grouped by repository.
+    pub fn waiting_for_review(&self) -> &[RepoRequests] {
+        &self.waiting_for_review
+    }
+
+    /// Reviewer's own pull requests where reviewers wait for the author, grouped by repository.
+    pub fn waiting_by_reviewers(&self) -> &[RepoRequests] {
+        &self.waiting_by_reviewers
+    }
+
+    /// Whether no pull requests wait for the reviewer or for the reviewer's authors.
+    pub fn is_empty(&self) -> bool {
+        self.waiting_for_review.is_empty() && self.waiting_by_reviewers.is_empty()
+    }
+
+    pub fn formatted(&self, format: MessageFormat) -> FormattedReviewerRequests<'_> {
+        FormattedReviewerRequests {
+            requests: self,
+            format,
+        }
+    }
+}
+
 impl Display for ReviewerRequests {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Hey!")?;
+        self.formatted(MessageFormat::SlackMarkdown).fmt(f)
+    }
+}
+
+impl<'a> Display for FormattedReviewerRequests<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let format = self.format;
+        write!(f, "Hey!")?;
+        format.write_line_break(f)?;
         write!(f, "Just a friendly reminder that there are ")?;
-        if !self.waiting_for_review.is_empty() {
-            writeln!(f, "Pull Requests waiting for your review:")?;
-            writeln!(f)?;
-            for repository in &self.waiting_for_review {
-                repository.format_for_reviewer(f)?;
-                writeln!(f)?;
+        if !self.requests.waiting_for_review.is_empty() {
+            write!(f, "Pull Requests waiting for your review:")?;
+            format.write_line_break(f)?;
+            format.write_line_break(f)?;
+            for repository in &self.requests.waiting_for_review {
+                repository.format_for_reviewer(f, format)?;
+                format.write_line_break(f)?;
             }
         }
-        if !self.waiting_by_reviewers.is_empty() {
-            writeln!(f, "Pull Requests where reviewers are waiting for you:")?;
-            writeln!(f)?;
-            for repository in &self.waiting_by_reviewers {
-                repository.format_for_creator(f)?;
-                writeln!(f)?;
+        if !self.requests.waiting_by_reviewers.is_empty() {
+            write!(f, "Pull Requests where reviewers are waiting for you:")?;
+            format.write_line_break(f)?;
+            format.write_line_break(f)?;
+            for repository in &self.requests.waiting_by_reviewers {
+                repository.format_for_creator(f, format)?;
+                format.write_line_break(f)?;
             }
         }
         Ok(())
@@ -173,31 +280,55 @@ impl Display for ReviewerRequests {
 }
 
 impl RepoRequests {
-    fn format_for_reviewer(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    #[cfg(test)]
+    pub fn new(repo_id: String, pull_requests: Vec<PullRequest>) -> Self {
+        Self {
+            repo_id,
+            pull_requests,
+        }
+    }
+
+    pub fn repo_id(&self) -> &str {
+        &self.repo_id
+    }
+
+    pub fn pull_requests(&self) -> &[PullRequest] {
+        &self.pull_requests
+    }
+
+    fn format_for_reviewer(
+        &self,
+        f: &mut Formatter<'_>,
+        format: MessageFormat,
+    ) -> std::fmt::Result {
         if self.pull_requests.is_empty() {
             return Ok(());
         }
-        writeln!(f, "{}", self.repo_id)?;
+        format.write_text(f, &self.repo_id)?;
+        format.write_line_break(f)?;
         let date_now = Utc::now();
         for pull_request in &self.pull_requests {
             write!(f, "- ")?;
-            write_link(f, &pull_request.url, pull_request.title.as_str());
-            write!(f, ". Author: {}.", pull_request.created_by.name)?;
+            format.write_link(f, &pull_request.url, pull_request.title.as_str())?;
+            write!(f, ". Author: ")?;
+            format.write_text(f, &pull_request.created_by.name)?;
+            write!(f, ".")?;
             write_formatted_duration(date_now - pull_request.creation_date, f);
-            writeln!(f)?;
+            format.write_line_break(f)?;
         }
         Ok(())
     }
 
-    fn format_for_creator(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn format_for_creator(&self, f: &mut Formatter<'_>, format: MessageFormat) -> std::fmt::Result {
         assert!(!self.pull_requests.is_empty());
-        writeln!(f, "{}", self.repo_id)?;
+        format.write_text(f, &self.repo_id)?;
+        format.write_line_break(f)?;
         let date_now = Utc::now();
         for pull_request in &self.pull_requests {
             write!(f, "- ")?;
-            write_link(f, &pull_request.url, pull_request.title.as_str());
+            format.write_link(f, &pull_request.url, pull_request.title.as_str())?;
             write_formatted_duration(date_now - pull_request.creation_date, f);
-            writeln!(f)?;
+            format.write_line_break(f)?;
             write!(f, "Waiting: ")?;
             let waiting_reviewers = pull_request
                 .reviewers
@@ -207,16 +338,42 @@ impl RepoRequests {
                 if index != 0 {
                     write!(f, ", ")?;
                 }
-                write!(f, "{}", name)?;
+                format.write_text(f, name)?;
             }
-            writeln!(f)?;
+            format.write_line_break(f)?;
         }
         Ok(())
     }
 }
 
-fn write_link(f: &mut Formatter<'_>, url: &Url, message: &str) {
-    write!(f, "<{url}|{}>", html_escape::encode_text(message)).unwrap();
+impl MessageFormat {
+    fn write_link(self, f: &mut Formatter<'_>, url: &Url, message: &str) -> std::fmt::Result {
+        match self {
+            Self::SlackMarkdown => write!(f, "<{url}|{}>", html_escape::encode_text(message)),
+            Self::Html => write!(
+                f,
+                "<a href=\"{}\">{}</a>",
+                html_escape::encode_double_quoted_attribute(url.as_str()),
+                html_escape::encode_text(message)
+            ),
+            Self::Markdown => write!(f, "[{}]({url})", message.replace(']', "\\]")),
+            Self::PlainText => write!(f, "{message} ({url})"),
+        }
+    }
+
+    fn write_text(self, f: &mut Formatter<'_>, text: &str) -> std::fmt::Result {
+        match self {
+            Self::SlackMarkdown | Self::Markdown | Self::PlainText => write!(f, "{text}"),
+            Self::Html => write!(f, "{}", html_escape::encode_text(text)),
+        }
+    }
+
+    fn write_line_break(self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SlackMarkdown | Self::Markdown | Self::PlainText => writeln!(f),
+            Self::Html => writeln!(f, "<br>"),
+        }
+    }
 }
 
 fn write_formatted_duration(duration: Duration, f: &mut Formatter<'_>) {
@@ -243,7 +400,9 @@ impl PullRequestReviewer {
         }
         match &self.vote {
             Vote::NoVote | Vote::WaitingForAuthor => true,
-            Vote::Rejected | Vote::Approved | Vote::ApprovedWithSuggestions => false,
+            Vote::Rejected | Vote::Approved | Vote::ApprovedWithSuggestions | Vote::Unknown => {
+                false
+            }
         }
     }
 
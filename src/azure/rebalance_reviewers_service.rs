@@ -0,0 +1,507 @@
+use super::add_reviewers_service::{
+    required_reviewer_candidates, shuffle_teams_members, RequiredReviewerCandidates,
+    RequiredReviewersSelection, ReviewersConfig, TeamMembersShuffler,
+};
+use super::api::{
+    AzurePullRequestChangesService, AzurePullRequestReviewersService, AzurePullRequestService,
+    AzurePullRequestsService, AzureTeamService, Identifier, NewPullRequestReviewer, PullRequest,
+    TeamMember, Vote,
+};
+use super::rotation::RotationState;
+use crate::metrics::metrics;
+use async_trait::async_trait;
+use color_eyre::{Report, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// What happens to the required reviewers who are replaced.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AbsentReviewers {
+    /// They stay required, the replacements are added next to them.
+    #[default]
+    Keep,
+    /// They stay on the pull request as optional reviewers.
+    Demote,
+    /// They are removed from the pull request.
+    Remove,
+}
+
+#[async_trait]
+pub trait RebalanceReviewersService {
+    /// Replaces the required reviewers of the active pull requests who are members of
+    /// the configured teams, have not voted and are unavailable now.
+    async fn rebalance_reviewers<F>(&self, is_unavailable: F) -> Result<()>
+    where
+        F: Fn(&TeamMember) -> bool + Send + Sync;
+}
+
+pub struct RebalanceReviewersServiceImpl<Api> {
+    api: Api,
+    team_name: String,
+    repositories: Vec<String>,
+    config: ReviewersConfig,
+    absent_reviewers: AbsentReviewers,
+    shuffle_teams: TeamMembersShuffler,
+}
+
+impl<Api> RebalanceReviewersServiceImpl<Api>
+where
+    Api: AzureTeamService + AzurePullRequestService + AzurePullRequestReviewersService,
+    Api: AzurePullRequestsService + AzurePullRequestChangesService,
+{
+    pub fn new(
+        api: Api,
+        team_name: &str,
+        repositories: Vec<String>,
+        config: ReviewersConfig,
+        absent_reviewers: AbsentReviewers,
+    ) -> Self {
+        Self {
+            api,
+            team_name: team_name.to_string(),
+            repositories,
+            config,
+            absent_reviewers,
+            shuffle_teams: shuffle_teams_members,
+        }
+    }
+
+    async fn rebalance_pull_request<F>(
+        &self,
+        repository_id: &str,
+        pull_request: &PullRequest,
+        members: &HashMap<Identifier, TeamMember>,
+        is_unavailable: &F,
+        rotation: Option<&mut RotationState>,
+    ) -> Result<()>
+    where
+        F: Fn(&TeamMember) -> bool + Send + Sync,
+    {
+        let (absent, present): (Vec<_>, Vec<_>) = pull_request
+            .reviewers
+            .iter()
+            .filter(|r| r.is_required)
+            .partition(|r| {
+                // Group reviewers and reviewers outside of the teams, e.g. required by a
+                // branch policy, are left as they are.
+                r.vote == Vote::NoVote
+                    && !r.is_container
+                    && members.get(&r.id).is_some_and(is_unavailable)
+            });
+        if absent.is_empty() {
+            return Ok(());
+        }
+        let pull_request_id = pull_request.id.to_string();
+        let absent_names = absent.iter().map(|r| r.name.as_str()).collect::<Vec<_>>();
+        tracing::info!(
+            "Pull request {pull_request_id} has unavailable required reviewers: {}.",
+            absent_names.join(", ")
+        );
+
+        let replacements_count = absent.len().min(
+            self.config
+                .required_reviewers_count()
+                .saturating_sub(present.len()),
+        );
+        let author_id = &pull_request.created_by.id;
+        let required = absent
+            .iter()
+            .chain(&present)
+            .map(|r| &r.id)
+            .collect::<HashSet<_>>();
+        let declined = pull_request
+            .reviewers
+            .iter()
+            .filter(|r| r.has_declined)
+            .map(|r| &r.id)
+            .collect::<HashSet<_>>();
+        let seed = self.config.seed(repository_id, pull_request);
+        tracing::info!(
+            "Reviewers of pull request {pull_request_id} are shuffled with seed {seed}."
+        );
+        let selection = RequiredReviewersSelection {
+            repository_id,
+            pull_request,
+            seed,
+            rotation: rotation.as_deref(),
+            author_affinity: self.config.author_affinity(),
+        };
+        let RequiredReviewerCandidates { candidates, teams } = required_reviewer_candidates(
+            &self.api,
+            &self.config,
+            self.shuffle_teams,
+            selection,
+            |member| {
+                let id = &member.id;
+                author_id != id
+                    && !required.contains(id)
+                    && !declined.contains(id)
+                    && !is_unavailable(member)
+            },
+        )
+        .await?;
+        if candidates.len() < replacements_count {
+            tracing::warn!(
+                "Only {} of {replacements_count} replacements are available for pull request {pull_request_id}.",
+                candidates.len()
+            );
+        }
+
+        let existing = pull_request
+            .reviewers
+            .iter()
+            .map(|r| &r.id)
+            .collect::<HashSet<_>>();
+        let replacements = candidates
+            .into_iter()
+            .take(replacements_count)
+            .map(|member| member.id)
+            .collect::<Vec<_>>();
+        let assigned = replacements.iter().cloned().collect::<HashSet<_>>();
+        let replaced_count = replacements.len();
+        let (promoted, added): (Vec<_>, Vec<_>) = replacements
+            .into_iter()
+            .map(|id| NewPullRequestReviewer {
+                id,
+                is_required: true,
+            })
+            .partition(|reviewer| existing.contains(&reviewer.id));
+        // Optional reviewers already on the pull request keep their votes.
+        for reviewer in promoted {
+            tracing::info!("Making reviewer {} required.", reviewer.id.0);
+            self.api
+                .update_reviewer(repository_id, &pull_request_id, reviewer)
+                .await?;
+        }
+        if !added.is_empty() {
+            let added_count = added.len() as u64;
+            self.api
+                .add_reviewers_to_pull_request(repository_id, &pull_request_id, added)
+                .await?;
+            metrics().reviewers_added.inc_by(added_count);
+        }
+        if let Some(rotation) = rotation {
+            for (team_name, members) in &teams {
+                rotation.advance(team_name, members, &assigned);
+            }
+        }
+
+        // Absent reviewers without a replacement stay required.
+        for reviewer in absent.into_iter().take(replaced_count) {
+            match self.absent_reviewers {
+                AbsentReviewers::Keep => {}
+                AbsentReviewers::Demote => {
+                    let reviewer = NewPullRequestReviewer {
+                        id: reviewer.id.clone(),
+                        is_required: false,
+                    };
+                    self.api
+                        .update_reviewer(repository_id, &pull_request_id, reviewer)
+                        .await?
+                }
+                AbsentReviewers::Remove => {
+                    self.api
+                        .remove_reviewer(repository_id, &pull_request_id, &reviewer.id)
+                        .await?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<Api> RebalanceReviewersService for RebalanceReviewersServiceImpl<Api>
+where
+    Api: AzureTeamService + Sync + Send,
+    Api: AzurePullRequestService + Sync + Send,
+    Api: AzurePullRequestReviewersService + Sync + Send,
+    Api: AzurePullRequestsService + Sync + Send,
+    Api: AzurePullRequestChangesService + Sync + Send,
+{
+    async fn rebalance_reviewers<F>(&self, is_unavailable: F) -> Result<()>
+    where
+        F: Fn(&TeamMember) -> bool + Send + Sync,
+    {
+        let mut members = HashMap::new();
+        let team_names = std::iter::once(self.team_name.as_str()).chain(self.config.team_names());
+        for team_name in team_names {
+            let team_members = self
+                .api
+                .team_members(Identifier(team_name.to_string()))
+                .await?;
+            for member in team_members.into_iter().filter(|m| !m.is_container) {
+                members.entry(member.id.clone()).or_insert(member);
+            }
+        }
+        let rotation_state_file = self.config.rotation_state_file();
+        // Held until the advanced rotation is saved.
+        let _rotation_lock = rotation_state_file.map(RotationState::lock).transpose()?;
+        let mut rotation = rotation_state_file.map(RotationState::load).transpose()?;
+        let mut failed = 0;
+        for repository_id in &self.repositories {
+            let pull_requests = self.api.obtain_active_pull_requests(repository_id).await?;
+            tracing::info!(
+                "Rebalancing reviewers of {} pull requests in repository {repository_id}.",
+                pull_requests.len()
+            );
+            for pull_request in &pull_requests {
+                let result = self
+                    .rebalance_pull_request(
+                        repository_id,
+                        pull_request,
+                        &members,
+                        &is_unavailable,
+                        rotation.as_mut(),
+                    )
+                    .await;
+                if let Err(e) = result {
+                    tracing::error!(
+                        "Rebalancing reviewers of pull request {} failed: {e:?}",
+                        pull_request.id
+                    );
+                    failed += 1;
+                }
+            }
+        }
+        if let (Some(rotation), Some(path)) = (rotation, rotation_state_file) {
+            rotation.save(path)?;
+        }
+        match failed {
+            0 => Ok(()),
+            count => Err(Report::msg(format!(
+                "Rebalancing reviewers failed for {count} pull requests."
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::add_reviewers_service::AzureTeam;
+    use super::super::api::{
+        PullRequestAuthor, PullRequestReviewer, PullRequestSearchCriteria, PullRequestStatus, Team,
+    };
+    use super::*;
+    use chrono::DateTime;
+    use mockall::mock;
+    use mockall::predicate::eq;
+    use url::Url;
+
+    mock! {
+        Api {}
+
+        #[async_trait]
+        impl AzureTeamService for Api {
+            async fn team_members(&self, team_id: Identifier) -> Result<Vec<TeamMember>>;
+            async fn get_teams(&self) -> Result<Vec<Team>>;
+        }
+
+        #[async_trait]
+        impl AzurePullRequestService for Api {
+            async fn obtain_pull_request(
+                &self,
+                repository_id: &str,
+                pull_request_id: &str,
+            ) -> Result<PullRequest>;
+
+            async fn add_reviewers_to_pull_request(
+                &self,
+                repository_id: &str,
+                request_id: &str,
+                reviewers: Vec<NewPullRequestReviewer>,
+            ) -> Result<()>;
+        }
+
+        #[async_trait]
+        impl AzurePullRequestReviewersService for Api {
+            async fn obtain_active_pull_requests(
+                &self,
+                repository_id: &str,
+            ) -> Result<Vec<PullRequest>>;
+
+            async fn update_reviewer(
+                &self,
+                repository_id: &str,
+                pull_request_id: &str,
+                reviewer: NewPullRequestReviewer,
+            ) -> Result<()>;
+
+            async fn remove_reviewer(
+                &self,
+                repository_id: &str,
+                pull_request_id: &str,
+                reviewer_id: &Identifier,
+            ) -> Result<()>;
+        }
+
+        #[async_trait]
+        impl AzurePullRequestChangesService for Api {
+            async fn obtain_completed_pull_requests(
+                &self,
+                repository_id: &str,
+                count: usize,
+            ) -> Result<Vec<PullRequest>>;
+
+            async fn obtain_changed_files(
+                &self,
+                repository_id: &str,
+                pull_request_id: usize,
+            ) -> Result<Vec<String>>;
+        }
+    }
+
+    /// Pending reviews are not searched without the author affinity.
+    #[async_trait]
+    impl AzurePullRequestsService for MockApi {
+        async fn obtain_pull_requests<F>(
+            &self,
+            _repository_id: &str,
+            _search_creteria: PullRequestSearchCriteria,
+            _filter: F,
+        ) -> Result<Vec<PullRequest>>
+        where
+            F: Fn(&PullRequestReviewer) -> bool,
+            F: Send,
+        {
+            Ok(vec![])
+        }
+    }
+
+    fn member(id: usize) -> TeamMember {
+        TeamMember {
+            id: Identifier(id.to_string()),
+            name: id.to_string(),
+            is_container: false,
+            mail_address: None,
+        }
+    }
+
+    fn reviewer(id: usize, is_required: bool, vote: Vote) -> PullRequestReviewer {
+        PullRequestReviewer {
+            id: Identifier(id.to_string()),
+            name: id.to_string(),
+            is_required,
+            vote,
+            has_declined: false,
+            is_container: false,
+            votes: vec![],
+        }
+    }
+
+    fn required(id: usize, is_required: bool) -> NewPullRequestReviewer {
+        NewPullRequestReviewer {
+            id: Identifier(id.to_string()),
+            is_required,
+        }
+    }
+
+    /// Team of members 0 to 4 with an active pull request of member 0.
+    fn api(reviewers: Vec<PullRequestReviewer>) -> MockApi {
+        let mut api = MockApi::new();
+        api.expect_team_members()
+            .returning(|_| Ok((0..5).map(member).collect()));
+        api.expect_obtain_active_pull_requests()
+            .with(eq("repo"))
+            .return_once(move |_| {
+                Ok(vec![PullRequest {
+                    id: 7,
+                    title: "Title".to_string(),
+                    url: Url::parse("http://some.co").unwrap(),
+                    created_by: PullRequestAuthor {
+                        id: Identifier("0".to_string()),
+                        name: "0".to_string(),
+                    },
+                    creation_date: DateTime::default(),
+                    reviewers,
+                    status: PullRequestStatus::Active,
+                    repository: None,
+                }])
+            });
+        api
+    }
+
+    fn service(api: MockApi, absent_reviewers: AbsentReviewers) -> impl RebalanceReviewersService {
+        let teams = vec![AzureTeam::new("Team".to_string(), None)];
+        RebalanceReviewersServiceImpl {
+            api,
+            team_name: "Team".to_string(),
+            repositories: vec!["repo".to_string()],
+            config: ReviewersConfig::new(2, teams),
+            absent_reviewers,
+            shuffle_teams: |first, second, _| (first, second),
+        }
+    }
+
+    #[tokio::test]
+    async fn optional_reviewer_promoted_and_absent_reviewer_demoted() -> Result<()> {
+        let reviewers = vec![
+            reviewer(1, true, Vote::NoVote),
+            reviewer(2, true, Vote::Approved),
+            reviewer(3, false, Vote::NoVote),
+        ];
+        let mut api = api(reviewers);
+        api.expect_update_reviewer()
+            .with(eq("repo"), eq("7"), eq(required(3, true)))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        api.expect_update_reviewer()
+            .with(eq("repo"), eq("7"), eq(required(1, false)))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        // Member 2 voted, so only member 1 is replaced.
+        let is_unavailable = |member: &TeamMember| ["1", "2"].contains(&member.name.as_str());
+        service(api, AbsentReviewers::Demote)
+            .rebalance_reviewers(is_unavailable)
+            .await
+    }
+
+    #[tokio::test]
+    async fn replacement_added_and_absent_reviewers_removed() -> Result<()> {
+        let reviewers = vec![
+            reviewer(1, true, Vote::NoVote),
+            reviewer(3, true, Vote::NoVote),
+        ];
+        let mut api = api(reviewers);
+        api.expect_add_reviewers_to_pull_request()
+            .with(eq("repo"), eq("7"), eq(vec![required(4, true)]))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        api.expect_remove_reviewer()
+            .with(eq("repo"), eq("7"), eq(Identifier("1".to_string())))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        // Member 2 is unavailable too, so member 4 replaces member 1.
+        let is_unavailable = |member: &TeamMember| ["1", "2"].contains(&member.name.as_str());
+        service(api, AbsentReviewers::Remove)
+            .rebalance_reviewers(is_unavailable)
+            .await
+    }
+
+    #[tokio::test]
+    async fn reviewers_outside_teams_and_groups_kept() -> Result<()> {
+        let mut group = reviewer(1, true, Vote::NoVote);
+        group.is_container = true;
+        // Member 9 is not in the teams, e.g. required by a branch policy.
+        let api = api(vec![group, reviewer(9, true, Vote::NoVote)]);
+
+        let is_unavailable = |_: &TeamMember| true;
+        service(api, AbsentReviewers::Remove)
+            .rebalance_reviewers(is_unavailable)
+            .await
+    }
+
+    #[tokio::test]
+    async fn absent_reviewers_without_candidates_kept() -> Result<()> {
+        let api = api(vec![reviewer(1, true, Vote::NoVote)]);
+
+        // No member can replace member 1, so nothing is updated or removed.
+        let is_unavailable = |_: &TeamMember| true;
+        service(api, AbsentReviewers::Remove)
+            .rebalance_reviewers(is_unavailable)
+            .await
+    }
+}
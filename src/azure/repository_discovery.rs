@@ -0,0 +1,154 @@
+use super::api::{AzureRepositoriesService, Repository};
+use color_eyre::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+
+/// Discovery of repositories in one or more projects.
+///
+/// Patterns are matched against both the repository name and the
+/// `<project>/<repository>` path. Repositories of other projects than the
+/// configured one are identified by the path.
+#[derive(Deserialize, Debug)]
+pub struct RepositoryDiscovery {
+    #[serde(default)]
+    projects: Vec<String>,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+impl RepositoryDiscovery {
+    pub async fn discover<Api>(&self, api: &Api, default_project: &str) -> Result<Vec<String>>
+    where
+        Api: AzureRepositoriesService + Sync,
+    {
+        let include = build_glob_set(&self.include)?;
+        let exclude = build_glob_set(&self.exclude)?;
+        let projects = match self.projects.as_slice() {
+            [] => vec![default_project.to_string()],
+            projects => projects.to_vec(),
+        };
+
+        let mut repositories = Vec::new();
+        for project in &projects {
+            for repository in api.obtain_repositories(project).await? {
+                let path = format!("{project}/{}", repository.name);
+                if !is_active(&repository) {
+                    tracing::info!("Repository {path} is disabled and skipped.");
+                    continue;
+                }
+                let is_match =
+                    |set: &GlobSet| set.is_match(&repository.name) || set.is_match(&path);
+                if (!self.include.is_empty() && !is_match(&include)) || is_match(&exclude) {
+                    continue;
+                }
+                let id = if project == default_project {
+                    repository.name
+                } else {
+                    path
+                };
+                if !repositories.contains(&id) {
+                    repositories.push(id);
+                }
+            }
+        }
+        tracing::info!("Discovered repositories: {repositories:?}");
+        Ok(repositories)
+    }
+}
+
+fn is_active(repository: &Repository) -> bool {
+    !repository.is_disabled && !repository.is_in_maintenance
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_trait::async_trait;
+    use mockall::mock;
+    use mockall::predicate::eq;
+
+    mock! {
+        Api {}
+
+        #[async_trait]
+        impl AzureRepositoriesService for Api {
+            async fn obtain_repositories(&self, project: &str) -> Result<Vec<Repository>>;
+        }
+    }
+
+    fn repository(name: &str, is_disabled: bool) -> Repository {
+        Repository {
+            name: name.to_string(),
+            is_disabled,
+            is_in_maintenance: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn repositories_filtered_by_patterns() -> Result<()> {
+        let mut api = MockApi::new();
+        api.expect_obtain_repositories()
+            .with(eq("Mobile"))
+            .returning(|_| {
+                Ok(vec![
+                    repository("mobile-ios", false),
+                    repository("mobile-android", false),
+                    repository("mobile-legacy", true),
+                    repository("mobile-archive", false),
+                    repository("docs", false),
+                ])
+            });
+        api.expect_obtain_repositories()
+            .with(eq("Shared"))
+            .returning(|_| {
+                Ok(vec![
+                    repository("mobile-kit", false),
+                    repository("ci", false),
+                ])
+            });
+
+        let discovery = RepositoryDiscovery {
+            projects: vec!["Mobile".to_string(), "Shared".to_string()],
+            include: vec!["mobile-*".to_string(), "Shared/ci".to_string()],
+            exclude: vec!["*-archive".to_string()],
+        };
+        let repositories = discovery.discover(&api, "Mobile").await?;
+
+        assert_eq!(
+            repositories,
+            [
+                "mobile-ios",
+                "mobile-android",
+                "Shared/mobile-kit",
+                "Shared/ci"
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn configured_project_used_by_default() -> Result<()> {
+        let mut api = MockApi::new();
+        api.expect_obtain_repositories()
+            .with(eq("Mobile"))
+            .returning(|_| Ok(vec![repository("app", false)]));
+
+        let discovery = RepositoryDiscovery {
+            projects: vec![],
+            include: vec![],
+            exclude: vec![],
+        };
+        assert_eq!(discovery.discover(&api, "Mobile").await?, ["app"]);
+        Ok(())
+    }
+}
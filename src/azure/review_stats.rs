@@ -0,0 +1,394 @@
+use super::api::{
+    AzurePullRequestHistoryService, AzurePullRequestReviewersService, AzureTeamService, Identifier,
+    PullRequest, PullRequestReviewer, TeamMember, Vote,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use futures::{StreamExt, TryStreamExt};
+use serde::Serialize;
+
+/// Maximum number of the concurrent vote history requests.
+const VOTE_UPDATES_LIMIT: usize = 8;
+
+/// Creation dates of the pull requests included in the statistics.
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct StatsPeriod {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReviewStats {
+    pub team: Stats,
+    pub reviewers: Vec<Stats>,
+}
+
+/// Review statistics of a reviewer or a team. Durations are measured from the
+/// creation of the pull request.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub name: String,
+    /// Pull requests the reviewer was asked to review.
+    pub pull_requests: usize,
+    pub time_to_first_vote: DurationStats,
+    pub time_to_approval: DurationStats,
+    /// Pull requests active now and still waiting for the vote, whenever they
+    /// were created.
+    pub queue_size: usize,
+    pub declined: usize,
+    /// Times the author was asked for changes with the `WaitingForAuthor` vote.
+    pub waiting_for_author_round_trips: usize,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct DurationStats {
+    pub count: usize,
+    pub median_minutes: Option<i64>,
+    pub average_minutes: Option<i64>,
+}
+
+#[async_trait]
+pub trait ReviewStatsProvider {
+    async fn review_stats(&self, period: StatsPeriod) -> Result<ReviewStats>;
+}
+
+pub struct AzureReviewStatsProvider<Service>
+where
+    Service: AzureTeamService,
+    Service: AzurePullRequestHistoryService,
+    Service: AzurePullRequestReviewersService,
+{
+    api: Service,
+    team_name: String,
+    repositories: Vec<String>,
+}
+
+#[async_trait]
+impl<Service> ReviewStatsProvider for AzureReviewStatsProvider<Service>
+where
+    Service: AzureTeamService + Send + Sync,
+    Service: AzurePullRequestHistoryService + Send + Sync,
+    Service: AzurePullRequestReviewersService + Send + Sync,
+{
+    async fn review_stats(&self, period: StatsPeriod) -> Result<ReviewStats> {
+        let members = self
+            .api
+            .team_members(Identifier(self.team_name.to_string()))
+            .await?;
+        let mut pull_requests = Vec::new();
+        let mut active_pull_requests = Vec::new();
+        for repository_id in &self.repositories {
+            let requests = self
+                .api
+                .obtain_pull_requests_since(repository_id, period.since)
+                .await?
+                .into_iter()
+                .filter(|request| {
+                    request.creation_date < period.until
+                        && request.reviewers.iter().any(|r| is_member(&members, r))
+                });
+            let requests = futures::stream::iter(requests)
+                .map(|request| self.with_vote_updates(repository_id, request))
+                .buffered(VOTE_UPDATES_LIMIT)
+                .try_collect::<Vec<_>>()
+                .await?;
+            pull_requests.extend(requests);
+            active_pull_requests.extend(self.api.obtain_active_pull_requests(repository_id).await?);
+        }
+        tracing::info!(
+            "Collected {} pull requests for team {}.",
+            pull_requests.len(),
+            self.team_name
+        );
+        Ok(ReviewStats::new(
+            &self.team_name,
+            &members,
+            &pull_requests,
+            &active_pull_requests,
+        ))
+    }
+}
+
+impl<Service> AzureReviewStatsProvider<Service>
+where
+    Service: AzureTeamService,
+    Service: AzurePullRequestHistoryService,
+    Service: AzurePullRequestReviewersService,
+{
+    pub fn new(api: Service, team_name: &str, repositories: Vec<String>) -> Self {
+        Self {
+            api,
+            team_name: team_name.to_string(),
+            repositories,
+        }
+    }
+
+    async fn with_vote_updates(
+        &self,
+        repository_id: &str,
+        mut request: PullRequest,
+    ) -> Result<PullRequest> {
+        let updates = self
+            .api
+            .obtain_vote_updates(repository_id, request.id)
+            .await?;
+        for (voter, update) in updates {
+            let reviewer = request.reviewers.iter_mut().find(|r| r.id == voter);
+            if let Some(reviewer) = reviewer {
+                reviewer.votes.push(update);
+            }
+        }
+        Ok(request)
+    }
+}
+
+fn is_member(members: &[TeamMember], reviewer: &PullRequestReviewer) -> bool {
+    members.iter().any(|m| m.id == reviewer.id)
+}
+
+fn reviewers<F>(request: &PullRequest, is_reviewer: F) -> Vec<&PullRequestReviewer>
+where
+    F: Fn(&PullRequestReviewer) -> bool,
+{
+    request
+        .reviewers
+        .iter()
+        .filter(|r| is_reviewer(r))
+        .collect()
+}
+
+/// Pull requests still waiting for the vote of any of the reviewers.
+fn queue_size<F>(active_pull_requests: &[PullRequest], is_reviewer: F) -> usize
+where
+    F: Fn(&PullRequestReviewer) -> bool,
+{
+    active_pull_requests
+        .iter()
+        .filter(|request| {
+            request
+                .reviewers
+                .iter()
+                .any(|r| is_reviewer(r) && r.vote == Vote::NoVote && !r.has_declined)
+        })
+        .count()
+}
+
+impl ReviewStats {
+    /// Statistics of the pull requests created in the period, with the queues of the
+    /// pull requests active now.
+    pub fn new(
+        team_name: &str,
+        members: &[TeamMember],
+        pull_requests: &[PullRequest],
+        active_pull_requests: &[PullRequest],
+    ) -> Self {
+        let is_team_reviewer = |r: &PullRequestReviewer| is_member(members, r);
+        let team_reviews = pull_requests
+            .iter()
+            .map(|request| (request, reviewers(request, is_team_reviewer)));
+        let mut team = Stats::new(team_name.to_string(), team_reviews);
+        team.queue_size = queue_size(active_pull_requests, is_team_reviewer);
+        let reviewers = members
+            .iter()
+            .map(|member| {
+                let is_member = |r: &PullRequestReviewer| r.id == member.id;
+                let reviews = pull_requests
+                    .iter()
+                    .map(|request| (request, reviewers(request, is_member)));
+                let mut stats = Stats::new(member.name.clone(), reviews);
+                stats.queue_size = queue_size(active_pull_requests, is_member);
+                stats
+            })
+            .filter(|stats| stats.pull_requests > 0 || stats.queue_size > 0)
+            .collect();
+        Self { team, reviewers }
+    }
+}
+
+impl Stats {
+    /// Statistics of the pull requests reviewed by the reviewers, without the queue.
+    /// A pull request is voted when the first of the reviewers votes and approved
+    /// when the first of them approves.
+    fn new<'a>(
+        name: String,
+        reviews: impl Iterator<Item = (&'a PullRequest, Vec<&'a PullRequestReviewer>)>,
+    ) -> Self {
+        let mut stats = Stats {
+            name,
+            ..Default::default()
+        };
+        let mut first_votes = Vec::new();
+        let mut approvals = Vec::new();
+        for (request, reviewers) in reviews {
+            if reviewers.is_empty() {
+                continue;
+            }
+            stats.pull_requests += 1;
+            let updates = reviewers.iter().flat_map(|r| &r.votes);
+            let first_vote = updates
+                .clone()
+                .filter(|update| update.vote != Vote::NoVote)
+                .map(|update| update.date)
+                .min();
+            let approval = updates
+                .clone()
+                .filter(|update| update.vote.is_approval())
+                .map(|update| update.date)
+                .min();
+            let minutes = |date: DateTime<Utc>| (date - request.creation_date).num_minutes();
+            first_votes.extend(first_vote.map(minutes));
+            approvals.extend(approval.map(minutes));
+            stats.declined += reviewers.iter().filter(|r| r.has_declined).count();
+            stats.waiting_for_author_round_trips += updates
+                .filter(|update| update.vote == Vote::WaitingForAuthor)
+                .count();
+        }
+        stats.time_to_first_vote = DurationStats::new(first_votes);
+        stats.time_to_approval = DurationStats::new(approvals);
+        stats
+    }
+}
+
+impl DurationStats {
+    fn new(mut minutes: Vec<i64>) -> Self {
+        minutes.sort_unstable();
+        let count = minutes.len();
+        Self {
+            count,
+            median_minutes: minutes.get(count.saturating_sub(1) / 2).copied(),
+            average_minutes: (count > 0).then(|| minutes.iter().sum::<i64>() / count as i64),
+        }
+    }
+}
+
+impl Vote {
+    fn is_approval(&self) -> bool {
+        matches!(self, Vote::Approved | Vote::ApprovedWithSuggestions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::azure::api::VoteUpdate;
+    use serde_json::json;
+
+    fn member(id: &str) -> TeamMember {
+        TeamMember {
+            id: Identifier(id.to_string()),
+            name: id.to_string(),
+            is_container: false,
+            mail_address: None,
+        }
+    }
+
+    fn pull_request(status: &str, reviewers: serde_json::Value) -> PullRequest {
+        serde_json::from_value(json!({
+            "pullRequestId": 1,
+            "title": "Title",
+            "url": "https://dev.azure.com/pr/1",
+            "createdBy": { "id": "author", "displayName": "Author" },
+            "creationDate": "2023-07-01T10:00:00Z",
+            "status": status,
+            "reviewers": reviewers,
+        }))
+        .unwrap()
+    }
+
+    fn votes(request: &mut PullRequest, id: &str, votes: &[(Vote, &str)]) {
+        let reviewer = request.reviewers.iter_mut().find(|r| r.id.0 == id).unwrap();
+        reviewer.votes = votes
+            .iter()
+            .map(|(vote, date)| VoteUpdate {
+                vote: *vote,
+                date: date.parse().unwrap(),
+            })
+            .collect();
+    }
+
+    #[test]
+    fn stats_computed_per_reviewer_and_team() {
+        let mut completed = pull_request(
+            "completed",
+            json!([
+                { "id": "alice", "displayName": "alice", "vote": 10, "hasDeclined": false },
+                { "id": "bob", "displayName": "bob", "vote": 0, "hasDeclined": true },
+                { "id": "other", "displayName": "other", "vote": 10, "hasDeclined": false },
+            ]),
+        );
+        votes(
+            &mut completed,
+            "alice",
+            &[
+                (Vote::WaitingForAuthor, "2023-07-01T11:00:00Z"),
+                (Vote::Approved, "2023-07-01T13:00:00Z"),
+            ],
+        );
+        votes(
+            &mut completed,
+            "other",
+            &[(Vote::Approved, "2023-07-01T10:30:00Z")],
+        );
+        let active_reviewers =
+            json!([{ "id": "alice", "displayName": "alice", "vote": 0, "hasDeclined": false }]);
+        let active = pull_request("active", active_reviewers.clone());
+        // Created before the period, so only counted in the queue.
+        let old_active = pull_request(
+            "active",
+            json!([{ "id": "carol", "displayName": "carol", "vote": 0, "hasDeclined": false }]),
+        );
+        let members = [member("alice"), member("bob"), member("carol")];
+
+        let active_pull_requests = [old_active, pull_request("active", active_reviewers)];
+        let stats = ReviewStats::new(
+            "Team",
+            &members,
+            &[completed, active],
+            &active_pull_requests,
+        );
+
+        assert_eq!(
+            stats.team,
+            Stats {
+                name: "Team".to_string(),
+                pull_requests: 2,
+                time_to_first_vote: DurationStats {
+                    count: 1,
+                    median_minutes: Some(60),
+                    average_minutes: Some(60),
+                },
+                time_to_approval: DurationStats {
+                    count: 1,
+                    median_minutes: Some(180),
+                    average_minutes: Some(180),
+                },
+                queue_size: 2,
+                declined: 1,
+                waiting_for_author_round_trips: 1,
+            }
+        );
+        let names = stats
+            .reviewers
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["alice", "bob", "carol"]);
+        assert_eq!(stats.reviewers[0].pull_requests, 2);
+        assert_eq!(stats.reviewers[0].queue_size, 1);
+        assert_eq!(stats.reviewers[1].declined, 1);
+        assert_eq!(stats.reviewers[2].pull_requests, 0);
+        assert_eq!(stats.reviewers[2].queue_size, 1);
+        assert_eq!(
+            stats.reviewers[1].time_to_first_vote,
+            DurationStats::default()
+        );
+    }
+
+    #[test]
+    fn median_of_durations() {
+        let stats = DurationStats::new(vec![30, 10, 20, 100]);
+        assert_eq!(stats.median_minutes, Some(20));
+        assert_eq!(stats.average_minutes, Some(40));
+    }
+}
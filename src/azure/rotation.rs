@@ -0,0 +1,214 @@
+use super::api::{Identifier, TeamMember};
+use color_eyre::eyre::WrapErr;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// Position of the round-robin rotation of a team.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct TeamRotation {
+    /// Last member whose turn passed.
+    last: Option<Identifier>,
+    /// Members who were skipped while unavailable and take their turn first.
+    #[serde(default)]
+    skipped: Vec<Identifier>,
+}
+
+/// Round-robin rotations of the teams, persisted between the runs in a state file.
+/// Members of a team take the turns in the order of their identifiers.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct RotationState {
+    #[serde(default)]
+    teams: BTreeMap<String, TeamRotation>,
+}
+
+/// Exclusive lock of a rotation state file, released when dropped.
+pub struct RotationLock {
+    _file: File,
+}
+
+impl RotationState {
+    /// Locks the state file until the returned lock is dropped, so the concurrent runs
+    /// load, advance and save the rotations one after another.
+    pub fn lock(path: &Path) -> Result<RotationLock> {
+        create_parent_dir(path)?;
+        let lock_path = path.with_extension("lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .wrap_err_with(|| format!("Unable to open rotation lock {}", lock_path.display()))?;
+        file.lock()
+            .wrap_err_with(|| format!("Unable to lock rotation state {}", path.display()))?;
+        Ok(RotationLock { _file: file })
+    }
+
+    /// Reads the state file. A missing file starts the rotations from the beginning.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            tracing::info!("Rotation state {} does not exist yet.", path.display());
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Unable to read rotation state {}", path.display()))?;
+        serde_json::from_str(&content)
+            .wrap_err_with(|| format!("Unable to parse rotation state {}", path.display()))
+    }
+
+    /// Writes the state file, replacing it at once so an interrupted run does not
+    /// leave it truncated.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let parent = create_parent_dir(path)?;
+        let mut temporary = tempfile::NamedTempFile::new_in(parent)?;
+        temporary.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        temporary
+            .persist(path)
+            .wrap_err_with(|| format!("Unable to write rotation state {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Members of the team in the order of their turns: the skipped members first,
+    /// then the others starting after the last member whose turn passed.
+    pub fn order(&self, team: &str, mut members: Vec<TeamMember>) -> Vec<TeamMember> {
+        members.sort_by(|a, b| a.id.0.cmp(&b.id.0));
+        let Some(rotation) = self.teams.get(team) else {
+            return members;
+        };
+        let start = rotation
+            .last
+            .as_ref()
+            .map_or(0, |last| members.partition_point(|m| m.id.0 <= last.0));
+        members.rotate_left(start);
+        let (mut skipped, mut others): (Vec<_>, Vec<_>) = members
+            .into_iter()
+            .partition(|m| rotation.skipped.contains(&m.id));
+        skipped.sort_by_key(|m| rotation.skipped.iter().position(|id| id == &m.id));
+        skipped.append(&mut others);
+        skipped
+    }
+
+    /// Moves the rotation of the team past the members assigned as reviewers.
+    /// Members passed on the way who were not assigned, because they were unavailable
+    /// or could not review the pull request, keep their turn for the next runs.
+    pub fn advance(&mut self, team: &str, members: &[TeamMember], assigned: &HashSet<Identifier>) {
+        let ordered = self.order(team, members.to_vec());
+        let mut assigned_left = ordered.iter().filter(|m| assigned.contains(&m.id)).count();
+        let rotation = self.teams.entry(team.to_string()).or_default();
+        rotation
+            .skipped
+            .retain(|id| members.iter().any(|m| &m.id == id));
+        for member in &ordered {
+            if assigned_left == 0 {
+                break;
+            }
+            let was_skipped = rotation.skipped.contains(&member.id);
+            if assigned.contains(&member.id) {
+                assigned_left -= 1;
+                rotation.skipped.retain(|id| id != &member.id);
+                if !was_skipped {
+                    rotation.last = Some(member.id.clone());
+                }
+            } else if !was_skipped {
+                tracing::info!("{} keeps the turn in team {team}.", member.name);
+                rotation.skipped.push(member.id.clone());
+            }
+        }
+    }
+}
+
+/// Creates the directory of the state file and returns it.
+fn create_parent_dir(path: &Path) -> Result<&Path> {
+    match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(parent) => {
+            std::fs::create_dir_all(parent)?;
+            Ok(parent)
+        }
+        None => Ok(Path::new(".")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn members(ids: &[&str]) -> Vec<TeamMember> {
+        ids.iter()
+            .map(|id| TeamMember {
+                id: Identifier(id.to_string()),
+                name: id.to_string(),
+                is_container: false,
+                mail_address: None,
+            })
+            .collect()
+    }
+
+    fn ids(members: &[TeamMember]) -> Vec<&str> {
+        members.iter().map(|m| m.id.0.as_str()).collect()
+    }
+
+    fn assigned(ids: &[&str]) -> HashSet<Identifier> {
+        ids.iter().map(|id| Identifier(id.to_string())).collect()
+    }
+
+    #[test]
+    fn rotation_continues_after_last_assigned() {
+        let team = members(&["d", "b", "a", "c"]);
+        let mut state = RotationState::default();
+        assert_eq!(
+            ids(&state.order("team", team.clone())),
+            ["a", "b", "c", "d"]
+        );
+
+        state.advance("team", &team, &assigned(&["a", "b"]));
+        assert_eq!(
+            ids(&state.order("team", team.clone())),
+            ["c", "d", "a", "b"]
+        );
+
+        state.advance("team", &team, &assigned(&["c", "d"]));
+        assert_eq!(ids(&state.order("team", team)), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn passed_member_keeps_turn() {
+        let team = members(&["a", "b", "c", "d"]);
+        let mut state = RotationState::default();
+
+        // The members before the assigned one were unavailable or the author.
+        state.advance("team", &team, &assigned(&["c"]));
+        assert_eq!(
+            ids(&state.order("team", team.clone())),
+            ["a", "b", "d", "c"]
+        );
+
+        // The skipped members keep their places in the front.
+        state.advance("team", &team, &assigned(&["d"]));
+        assert_eq!(
+            ids(&state.order("team", team.clone())),
+            ["a", "b", "c", "d"]
+        );
+
+        state.advance("team", &team, &assigned(&["a", "b", "c"]));
+        assert_eq!(ids(&state.order("team", team)), ["d", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn state_saved_and_loaded() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("state").join("rotation.json");
+        let _lock = RotationState::lock(&path)?;
+        assert_eq!(RotationState::load(&path)?, RotationState::default());
+
+        let team = members(&["a", "b"]);
+        let mut state = RotationState::default();
+        state.advance("team", &team, &assigned(&["b"]));
+        state.save(&path)?;
+
+        assert_eq!(RotationState::load(&path)?, state);
+        Ok(())
+    }
+}
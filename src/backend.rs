@@ -0,0 +1,67 @@
+use crate::azure::api::TeamMember;
+use crate::azure::{AddReviewersService, ConfigChecker, ReviewerRequestsProvider, TeamRequests};
+use crate::diagnostics::Diagnostics;
+use crate::notifier::UserDirectory;
+use async_trait::async_trait;
+use color_eyre::Result;
+
+/// Code hosting backend selected in the configuration.
+pub enum Backend<Azure, GitLab, Bitbucket> {
+    Azure(Azure),
+    GitLab(GitLab),
+    Bitbucket(Bitbucket),
+}
+
+#[async_trait]
+impl<Azure, GitLab, Bitbucket> ReviewerRequestsProvider for Backend<Azure, GitLab, Bitbucket>
+where
+    Azure: ReviewerRequestsProvider + Send + Sync,
+    GitLab: ReviewerRequestsProvider + Send + Sync,
+    Bitbucket: ReviewerRequestsProvider + Send + Sync,
+{
+    async fn pull_requests<F>(&self, include_user: F) -> Result<TeamRequests>
+    where
+        F: Fn(&TeamMember) -> bool + Send + Sync,
+    {
+        match self {
+            Self::Azure(provider) => provider.pull_requests(include_user).await,
+            Self::GitLab(provider) => provider.pull_requests(include_user).await,
+            Self::Bitbucket(provider) => provider.pull_requests(include_user).await,
+        }
+    }
+}
+
+#[async_trait]
+impl<Azure, GitLab, Bitbucket> AddReviewersService for Backend<Azure, GitLab, Bitbucket>
+where
+    Azure: AddReviewersService + Send + Sync,
+    GitLab: AddReviewersService + Send + Sync,
+    Bitbucket: AddReviewersService + Send + Sync,
+{
+    async fn add_reviewers<F>(&self, is_on_vacation: F) -> Result<()>
+    where
+        F: Fn(&TeamMember) -> bool + Send + Sync,
+    {
+        match self {
+            Self::Azure(service) => service.add_reviewers(is_on_vacation).await,
+            Self::GitLab(service) => service.add_reviewers(is_on_vacation).await,
+            Self::Bitbucket(service) => service.add_reviewers(is_on_vacation).await,
+        }
+    }
+}
+
+#[async_trait]
+impl<Azure, GitLab, Bitbucket> ConfigChecker for Backend<Azure, GitLab, Bitbucket>
+where
+    Azure: ConfigChecker + Send + Sync,
+    GitLab: ConfigChecker + Send + Sync,
+    Bitbucket: ConfigChecker + Send + Sync,
+{
+    async fn check(&self, directory: Option<&UserDirectory>) -> Diagnostics {
+        match self {
+            Self::Azure(checker) => checker.check(directory).await,
+            Self::GitLab(checker) => checker.check(directory).await,
+            Self::Bitbucket(checker) => checker.check(directory).await,
+        }
+    }
+}
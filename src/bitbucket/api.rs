@@ -0,0 +1,716 @@
+use crate::azure::api::{
+    found, AzurePullRequestChangesService, AzurePullRequestService, AzurePullRequestsService,
+    AzureRepositoryService, AzureTeamService, Identifier, NewPullRequestReviewer, PullRequest,
+    PullRequestAuthor, PullRequestRepository, PullRequestReviewer, PullRequestSearchCriteria,
+    PullRequestStatus, Team, TeamMember, Vote,
+};
+use crate::error::Error;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use futures::TryFutureExt;
+use itertools::Itertools;
+use reqwest::{header::AUTHORIZATION, Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+const PAGE_SIZE: usize = 100;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Page<T> {
+    values: Vec<T>,
+    #[serde(default)]
+    is_last_page: bool,
+    #[serde(default)]
+    next_page_start: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct User {
+    name: String,
+    #[serde(default, skip_serializing)]
+    display_name: String,
+    #[serde(default = "default_active", skip_serializing)]
+    active: bool,
+    #[serde(default, skip_serializing)]
+    email_address: Option<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum ParticipantStatus {
+    Unapproved,
+    NeedsWork,
+    Approved,
+}
+
+#[derive(Deserialize, Debug)]
+struct Participant {
+    user: User,
+    status: ParticipantStatus,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum BitbucketPullRequestState {
+    Open,
+    Merged,
+    Declined,
+}
+
+#[derive(Deserialize, Debug)]
+struct Link {
+    href: Url,
+}
+
+#[derive(Deserialize, Debug)]
+struct Links {
+    #[serde(rename = "self")]
+    self_links: Vec<Link>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BitbucketPullRequest {
+    id: usize,
+    version: u64,
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    state: BitbucketPullRequestState,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    created_date: DateTime<Utc>,
+    author: Participant,
+    #[serde(default)]
+    reviewers: Vec<Participant>,
+    links: Links,
+    #[serde(default)]
+    to_ref: Option<Ref>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Ref {
+    repository: Repository,
+}
+
+#[derive(Deserialize, Debug)]
+struct Repository {
+    id: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct Change {
+    path: ChangePath,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ChangePath {
+    to_string: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Group {
+    name: String,
+}
+
+#[derive(Serialize, Debug)]
+struct ReviewerUpdate {
+    user: User,
+}
+
+#[derive(Serialize, Debug)]
+struct UpdatePullRequest {
+    version: u64,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    reviewers: Vec<ReviewerUpdate>,
+}
+
+/// Bitbucket Server / Data Center REST API 1.0 client.
+///
+/// Implements the same services as `AzureApi`, so pull requests can be processed
+/// by the existing reviewer requests provider and add reviewers service.
+/// Repositories are identified as `<project key>/<repository slug>`, teams are
+/// Bitbucket groups. Listing groups and their members requires a token of a user
+/// with the admin permission.
+pub struct BitbucketApi<'a> {
+    token: &'a str,
+    base_url: &'a Url,
+    client: Client,
+}
+
+impl<'a> BitbucketApi<'a> {
+    pub fn new(token: &'a str, base_url: &'a Url) -> Self {
+        Self {
+            token,
+            base_url,
+            client: crate::http::client(),
+        }
+    }
+
+    fn make_url<'s>(&self, segments: impl IntoIterator<Item = &'s str>) -> Result<Url> {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| color_eyre::Report::msg("Bitbucket base URL can not be a base."))?
+            .pop_if_empty()
+            .extend(["rest", "api", "1.0"])
+            .extend(segments);
+        Ok(url)
+    }
+
+    fn make_pull_requests_url(&self, repository_id: &str) -> Result<Url> {
+        let Some((project, repository)) = repository_id.split_once('/') else {
+            return Err(color_eyre::Report::msg(format!(
+                "Bitbucket repository {repository_id} must be in the <PROJECT>/<repository> form."
+            )));
+        };
+        self.make_url(["projects", project, "repos", repository, "pull-requests"])
+    }
+
+    async fn obtain_single_item<T: DeserializeOwned>(&self, url: Url) -> Result<T> {
+        let response = self
+            .send_request(url, |client, url| {
+                tracing::debug!("Executing GET request with url: {url}.");
+                client.get(url)
+            })
+            .await?;
+        crate::error::json("bitbucket", response).await
+    }
+
+    async fn obtain_list<T: DeserializeOwned>(&self, url: Url) -> Result<Vec<T>> {
+        self.obtain_first_items(url, usize::MAX).await
+    }
+
+    /// Reads the pages until `count` items are received or the last page.
+    async fn obtain_first_items<T: DeserializeOwned>(
+        &self,
+        url: Url,
+        count: usize,
+    ) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut start = 0;
+        while items.len() < count {
+            let limit = (count - items.len()).min(PAGE_SIZE);
+            let mut page_url = url.clone();
+            page_url.query_pairs_mut().extend_pairs([
+                ("limit", limit.to_string().as_str()),
+                ("start", start.to_string().as_str()),
+            ]);
+            let page = self.obtain_single_item::<Page<T>>(page_url).await?;
+            items.extend(page.values);
+            match page.next_page_start {
+                Some(next_page_start) if !page.is_last_page => start = next_page_start,
+                _ => break,
+            }
+        }
+        items.truncate(count);
+        Ok(items)
+    }
+
+    async fn send_request<F>(
+        &self,
+        url: Url,
+        request_builder_factory: F,
+    ) -> Result<reqwest::Response>
+    where
+        F: FnOnce(&Client, Url) -> RequestBuilder,
+    {
+        let request = request_builder_factory(&self.client, url)
+            .header(AUTHORIZATION, format!("Bearer {}", self.token))
+            .build()?;
+
+        let response = crate::http::execute(&self.client, "bitbucket", request).await?;
+        crate::error::error_for_status("bitbucket", response).await
+    }
+
+    async fn obtain_bitbucket_pull_request(
+        &self,
+        repository_id: &str,
+        pull_request_id: &str,
+    ) -> Result<BitbucketPullRequest> {
+        let mut url = self.make_pull_requests_url(repository_id)?;
+        url.path_segments_mut()
+            .map_err(|_| color_eyre::Report::msg("Bitbucket base URL can not be a base."))?
+            .push(pull_request_id);
+        self.obtain_single_item::<BitbucketPullRequest>(url).await
+    }
+}
+
+#[async_trait]
+impl<'a> AzureTeamService for BitbucketApi<'a> {
+    async fn team_members(&self, team_id: Identifier) -> Result<Vec<TeamMember>> {
+        tracing::info!("Requesting group {} members.", team_id.0);
+        let mut url = self.make_url(["admin", "groups", "more-members"])?;
+        url.query_pairs_mut()
+            .extend_pairs([("context", team_id.0.as_str())]);
+        let members = self
+            .obtain_list::<User>(url)
+            .await?
+            .into_iter()
+            .filter(|user| user.active)
+            .map(|user| TeamMember {
+                id: Identifier(user.name),
+                name: user.display_name,
+                is_container: false,
+                mail_address: user.email_address,
+            })
+            .collect();
+        Ok(members)
+    }
+
+    async fn get_teams(&self) -> Result<Vec<Team>> {
+        tracing::info!("Requesting groups.");
+        let url = self.make_url(["admin", "groups"])?;
+        let teams = self
+            .obtain_list::<Group>(url)
+            .await?
+            .into_iter()
+            .map(|group| Team { name: group.name })
+            .collect();
+        Ok(teams)
+    }
+}
+
+#[async_trait]
+impl<'a> AzurePullRequestService for BitbucketApi<'a> {
+    async fn obtain_pull_request(
+        &self,
+        repository_id: &str,
+        pull_request_id: &str,
+    ) -> Result<PullRequest> {
+        tracing::info!("Requesting pull request {pull_request_id} in repository {repository_id}.");
+        self.obtain_bitbucket_pull_request(repository_id, pull_request_id)
+            .await
+            .and_then(PullRequest::try_from)
+    }
+
+    /// Bitbucket has no optional reviewers, so only required reviewers are assigned.
+    async fn add_reviewers_to_pull_request(
+        &self,
+        repository_id: &str,
+        request_id: &str,
+        reviewers: Vec<NewPullRequestReviewer>,
+    ) -> Result<()> {
+        let (required, optional): (Vec<_>, Vec<_>) =
+            reviewers.into_iter().partition(|r| r.is_required);
+        if !optional.is_empty() {
+            tracing::info!("Bitbucket does not support optional reviewers. Skipped: {optional:?}");
+        }
+        if required.is_empty() {
+            tracing::info!("No required reviewers to assign to pull request {request_id}.");
+            return Ok(());
+        }
+        let pull_request = self
+            .obtain_bitbucket_pull_request(repository_id, request_id)
+            .await?;
+        let mut reviewers = pull_request
+            .reviewers
+            .into_iter()
+            .map(|r| r.user)
+            .collect::<Vec<_>>();
+        for reviewer in required {
+            if !reviewers.iter().any(|r| r.name == reviewer.id.0) {
+                reviewers.push(User {
+                    name: reviewer.id.0,
+                    display_name: String::new(),
+                    active: true,
+                    email_address: None,
+                });
+            }
+        }
+        tracing::info!("Updating reviewers of pull request {request_id} in repository {repository_id}. Reviewers: {reviewers:?}");
+        let mut url = self.make_pull_requests_url(repository_id)?;
+        url.path_segments_mut()
+            .map_err(|_| color_eyre::Report::msg("Bitbucket base URL can not be a base."))?
+            .push(request_id);
+        let body = UpdatePullRequest {
+            version: pull_request.version,
+            title: pull_request.title,
+            description: pull_request.description,
+            reviewers: reviewers
+                .into_iter()
+                .map(|user| ReviewerUpdate { user })
+                .collect(),
+        };
+        self.send_request(url, |client, url| {
+            tracing::debug!("Executing PUT request with url: {url}.");
+            client.put(url).json(&body)
+        })
+        .map_ok(|_| ())
+        .await
+    }
+}
+
+#[async_trait]
+impl<'a> AzureRepositoryService for BitbucketApi<'a> {
+    async fn repository_exists(&self, repository_id: &str) -> Result<bool> {
+        tracing::info!("Requesting repository {repository_id}.");
+        let Some((project, repository)) = repository_id.split_once('/') else {
+            return Ok(false);
+        };
+        let url = self.make_url(["projects", project, "repos", repository])?;
+        let repository = self.obtain_single_item::<serde_json::Value>(url).await;
+        found(repository).map(|v| v.is_some())
+    }
+}
+
+#[async_trait]
+impl<'a> AzurePullRequestsService for BitbucketApi<'a> {
+    async fn obtain_pull_requests<F>(
+        &self,
+        repository_id: &str,
+        search_creteria: PullRequestSearchCriteria,
+        filter: F,
+    ) -> Result<Vec<PullRequest>>
+    where
+        F: Fn(&PullRequestReviewer) -> bool,
+        F: Send,
+    {
+        let mut url = self.make_pull_requests_url(repository_id)?;
+        let (role, username) = match &search_creteria {
+            PullRequestSearchCriteria::Reviewer(id) => ("REVIEWER", id.0.as_str()),
+            PullRequestSearchCriteria::Creator(id) => ("AUTHOR", id.0.as_str()),
+        };
+        url.query_pairs_mut().extend_pairs([
+            ("state", "OPEN"),
+            ("role.1", role),
+            ("username.1", username),
+        ]);
+        let requests = self
+            .obtain_list::<BitbucketPullRequest>(url)
+            .await?
+            .into_iter()
+            .map(PullRequest::try_from)
+            .filter_ok(|v| v.reviewers.iter().any(&filter))
+            .collect::<Result<_>>()?;
+        Ok(requests)
+    }
+}
+
+#[async_trait]
+impl<'a> AzurePullRequestChangesService for BitbucketApi<'a> {
+    async fn obtain_completed_pull_requests(
+        &self,
+        repository_id: &str,
+        count: usize,
+    ) -> Result<Vec<PullRequest>> {
+        tracing::info!("Requesting {count} merged pull requests in repository {repository_id}.");
+        let mut url = self.make_pull_requests_url(repository_id)?;
+        url.query_pairs_mut()
+            .extend_pairs([("state", "MERGED"), ("order", "NEWEST")]);
+        self.obtain_first_items::<BitbucketPullRequest>(url, count)
+            .await?
+            .into_iter()
+            .map(PullRequest::try_from)
+            .collect()
+    }
+
+    async fn obtain_changed_files(
+        &self,
+        repository_id: &str,
+        pull_request_id: usize,
+    ) -> Result<Vec<String>> {
+        let mut url = self.make_pull_requests_url(repository_id)?;
+        url.path_segments_mut()
+            .map_err(|_| color_eyre::Report::msg("Bitbucket base URL can not be a base."))?
+            .extend([pull_request_id.to_string().as_str(), "changes"]);
+        let changes = self.obtain_list::<Change>(url).await?;
+        Ok(changes
+            .into_iter()
+            .map(|change| change.path.to_string)
+            .collect())
+    }
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl From<ParticipantStatus> for Vote {
+    fn from(value: ParticipantStatus) -> Self {
+        match value {
+            ParticipantStatus::Unapproved => Vote::NoVote,
+            ParticipantStatus::NeedsWork => Vote::WaitingForAuthor,
+            ParticipantStatus::Approved => Vote::Approved,
+        }
+    }
+}
+
+impl TryFrom<BitbucketPullRequest> for PullRequest {
+    type Error = color_eyre::Report;
+
+    fn try_from(value: BitbucketPullRequest) -> Result<Self> {
+        let url = value
+            .links
+            .self_links
+            .into_iter()
+            .next()
+            .map(|link| link.href)
+            .ok_or_else(|| Error::Deserialize {
+                service: "bitbucket".to_string(),
+                path: "links.self".to_string(),
+                message: format!("pull request {} has no self link", value.id),
+            })?;
+        let status = match value.state {
+            BitbucketPullRequestState::Open => PullRequestStatus::Active,
+            BitbucketPullRequestState::Merged => PullRequestStatus::Completed,
+            BitbucketPullRequestState::Declined => PullRequestStatus::Abandoned,
+        };
+        let reviewers = value
+            .reviewers
+            .into_iter()
+            .map(|reviewer| PullRequestReviewer {
+                id: Identifier(reviewer.user.name),
+                name: reviewer.user.display_name,
+                is_required: true,
+                vote: reviewer.status.into(),
+                has_declined: false,
+                is_container: false,
+                votes: vec![],
+            })
+            .collect();
+        Ok(PullRequest {
+            id: value.id,
+            title: value.title,
+            url,
+            created_by: PullRequestAuthor {
+                id: Identifier(value.author.user.name),
+                name: value.author.user.display_name,
+            },
+            creation_date: value.created_date,
+            reviewers,
+            status,
+            repository: value.to_ref.map(|to_ref| PullRequestRepository {
+                id: to_ref.repository.id.to_string(),
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const TOKEN: &str = "fake_token";
+    const REPOSITORY: &str = "MOB/app";
+
+    fn participant(name: &str, status: &str) -> serde_json::Value {
+        json!({
+            "user": { "name": name, "displayName": name.to_uppercase(), "active": true },
+            "role": "REVIEWER",
+            "approved": status == "APPROVED",
+            "status": status,
+        })
+    }
+
+    fn pull_request(id: usize) -> serde_json::Value {
+        json!({
+            "id": id,
+            "version": 3,
+            "title": format!("Pull request {id}"),
+            "description": "Description",
+            "state": "OPEN",
+            "createdDate": 1688205600000_i64,
+            "author": participant("author", "UNAPPROVED"),
+            "reviewers": [participant("alice", "NEEDS_WORK"), participant("bob", "APPROVED")],
+            "links": { "self": [{ "href": format!("https://bitbucket.example.com/projects/MOB/repos/app/pull-requests/{id}") }] },
+        })
+    }
+
+    #[tokio::test]
+    async fn pull_requests_listed_by_participant_role() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/1.0/projects/MOB/repos/app/pull-requests"))
+            .and(query_param("state", "OPEN"))
+            .and(query_param("role.1", "AUTHOR"))
+            .and(query_param("username.1", "author"))
+            .and(query_param("start", "0"))
+            .and(header("authorization", "Bearer fake_token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "values": [pull_request(1)],
+                "isLastPage": false,
+                "nextPageStart": 1,
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/1.0/projects/MOB/repos/app/pull-requests"))
+            .and(query_param("start", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "values": [pull_request(2)],
+                "isLastPage": true,
+            })))
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&server.uri())?;
+        let api = BitbucketApi::new(TOKEN, &base_url);
+        let requests = api
+            .obtain_pull_requests(
+                REPOSITORY,
+                PullRequestSearchCriteria::Creator(Identifier("author".to_string())),
+                |r| r.vote == Vote::WaitingForAuthor,
+            )
+            .await?;
+
+        let ids = requests.iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2]);
+        let request = &requests[0];
+        assert_eq!(request.created_by.name, "AUTHOR");
+        assert_eq!(request.creation_date.timestamp(), 1688205600);
+        let votes = request
+            .reviewers
+            .iter()
+            .map(|r| (r.id.0.as_str(), &r.vote))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            votes,
+            vec![("alice", &Vote::WaitingForAuthor), ("bob", &Vote::Approved)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reviewers_assigned_through_pull_request_update() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/1.0/projects/MOB/repos/app/pull-requests/5"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(pull_request(5)))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/rest/api/1.0/projects/MOB/repos/app/pull-requests/5"))
+            .and(body_json(json!({
+                "version": 3,
+                "title": "Pull request 5",
+                "description": "Description",
+                "reviewers": [
+                    { "user": { "name": "alice" } },
+                    { "user": { "name": "bob" } },
+                    { "user": { "name": "carol" } },
+                ],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(pull_request(5)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&server.uri())?;
+        let api = BitbucketApi::new(TOKEN, &base_url);
+        let reviewers = vec![
+            NewPullRequestReviewer {
+                id: Identifier("carol".to_string()),
+                is_required: true,
+            },
+            NewPullRequestReviewer {
+                id: Identifier("bob".to_string()),
+                is_required: true,
+            },
+            NewPullRequestReviewer {
+                id: Identifier("dave".to_string()),
+                is_required: false,
+            },
+        ];
+        api.add_reviewers_to_pull_request(REPOSITORY, "5", reviewers)
+            .await
+    }
+
+    #[tokio::test]
+    async fn inactive_group_members_skipped() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/1.0/admin/groups/more-members"))
+            .and(query_param("context", "mobile-developers"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "values": [
+                    { "name": "alice", "displayName": "Alice", "active": true },
+                    { "name": "eve", "displayName": "Eve", "active": false },
+                ],
+                "isLastPage": true,
+            })))
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&server.uri())?;
+        let api = BitbucketApi::new(TOKEN, &base_url);
+        let members = api
+            .team_members(Identifier("mobile-developers".to_string()))
+            .await?;
+
+        let names = members.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Alice"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn merged_pull_requests_paged_up_to_count() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/1.0/projects/MOB/repos/app/pull-requests"))
+            .and(query_param("state", "MERGED"))
+            .and(query_param("limit", "3"))
+            .and(query_param("start", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "values": [pull_request(1), pull_request(2)],
+                "isLastPage": false,
+                "nextPageStart": 2,
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/1.0/projects/MOB/repos/app/pull-requests"))
+            .and(query_param("limit", "1"))
+            .and(query_param("start", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "values": [pull_request(3)],
+                "isLastPage": false,
+                "nextPageStart": 3,
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&server.uri())?;
+        let api = BitbucketApi::new(TOKEN, &base_url);
+        let requests = api.obtain_completed_pull_requests(REPOSITORY, 3).await?;
+
+        let ids = requests.iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2, 3]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pull_request_without_self_link_rejected() -> Result<()> {
+        let server = MockServer::start().await;
+        let mut pull_request = pull_request(5);
+        pull_request["links"] = json!({ "self": [] });
+        Mock::given(method("GET"))
+            .and(path("/rest/api/1.0/projects/MOB/repos/app/pull-requests/5"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(pull_request))
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&server.uri())?;
+        let api = BitbucketApi::new(TOKEN, &base_url);
+        let report = api
+            .obtain_pull_request(REPOSITORY, "5")
+            .await
+            .expect_err("pull request without a self link");
+
+        assert!(matches!(
+            Error::find(&report),
+            Some(Error::Deserialize { path, .. }) if path == "links.self"
+        ));
+        Ok(())
+    }
+}
@@ -1,4 +1,6 @@
-use clap::{Parser, Subcommand};
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -7,6 +9,24 @@ pub struct Cli {
     /// Provides a configuration file
     #[arg(short, long, value_name = "FILE")]
     pub config: PathBuf,
+    /// Runs the command for a single profile of the configuration file
+    #[arg(short, long, global = true)]
+    pub profile: Option<String>,
+    /// Format of the log output
+    #[arg(long, value_enum, global = true, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+    /// Exports traces to the OpenTelemetry collector with the OTLP/HTTP base URL,
+    /// e.g. http://localhost:4318
+    #[cfg(feature = "otlp")]
+    #[arg(long, global = true, value_name = "URL")]
+    pub otlp_endpoint: Option<String>,
+    /// Saves the responses of the services, with tokens and mail addresses
+    /// scrubbed, to the directory
+    #[arg(long, global = true, value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+    /// Serves the responses saved with --record instead of calling the services
+    #[arg(long, global = true, value_name = "DIR")]
+    pub replay: Option<PathBuf>,
     #[command(subcommand)]
     pub command: Command,
 }
@@ -21,10 +41,78 @@ pub enum Command {
         /// Pull request's id
         #[arg(long)]
         request_id: String,
+        /// Seed of the reviewers shuffling, derived from the repository and the pull request by default
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Replace unavailable required reviewers who have not voted on active pull requests
+    RebalanceReviewers {
+        /// List of repositories
+        repositories: Vec<String>,
     },
     /// Send reports with not reviewed pull requests to reviewers
     SendReports {
         /// List of repositories
         repositories: Vec<String>,
     },
+    /// Check the configuration and the access to the configured services
+    CheckConfig {
+        /// List of repositories to check
+        repositories: Vec<String>,
+    },
+    /// Write the pull requests waiting for reviewers without sending anything
+    Report {
+        /// Format of the report
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Json)]
+        format: ReportFormat,
+        /// Writes the report to the file instead of the standard output
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        /// List of repositories
+        repositories: Vec<String>,
+    },
+    /// Send reports periodically and serve Prometheus metrics on /metrics
+    Serve {
+        /// Address of the metrics endpoint
+        #[arg(long, default_value = "0.0.0.0:9090")]
+        listen: SocketAddr,
+        /// Minutes between the reports
+        #[arg(long, value_name = "MINUTES", default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+        /// List of repositories
+        repositories: Vec<String>,
+    },
+    /// Print review statistics of the pull requests created in the period
+    Stats {
+        /// First day of the period, 30 days before the last day by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        since: Option<NaiveDate>,
+        /// Last day of the period, today by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        until: Option<NaiveDate>,
+        /// Format of the statistics
+        #[arg(short, long, value_enum, default_value_t = StatsFormat::Table)]
+        format: StatsFormat,
+        /// List of repositories
+        repositories: Vec<String>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+    Markdown,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum StatsFormat {
+    Table,
+    Json,
 }
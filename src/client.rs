@@ -0,0 +1,338 @@
+//! Clients for embedding reviewporter in other tools.
+//!
+//! The clients are built in code instead of the TOML config. They own their
+//! settings and are `Send + Sync + 'static`, so they can be stored and shared
+//! between tasks.
+//!
+//! ```no_run
+//! use reviewporter::client::{AzureClient, SlackClient};
+//!
+//! # async fn run() -> Result<(), reviewporter::Error> {
+//! let azure = AzureClient::builder()
+//!     .base_url("https://dev.azure.com/organization/")
+//!     .token("azure-token")
+//!     .project("Project")
+//!     .team_name("Developers")
+//!     .build()?;
+//! let slack = SlackClient::builder()
+//!     .token("slack-token")
+//!     .team_id("T0123456")
+//!     .usergroup_id("S0123456")
+//!     .build()?;
+//!
+//! let directory = slack.directory().await?;
+//! let team_requests = azure
+//!     .pull_requests(vec!["backend".to_string()], |_| true)
+//!     .await?;
+//! for requests in &team_requests.requests {
+//!     let email = requests.reviewer_email.as_deref();
+//!     if let Some(id) = directory.available_user_id(&requests.reviewer_name, email) {
+//!         slack.send_report(id, requests).await?;
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use crate::azure::api::{AzureApi, AzureTeamService, Identifier, TeamMember};
+use crate::azure::{
+    AddReviewersService, AddReviewersServiceImpl, AuthorAffinity, AzureReviewerRequestsProvider,
+    AzureTeam, OptionalReviewersStrategy, ReviewerRequests, ReviewerRequestsProvider,
+    ReviewersConfig, TeamRequests,
+};
+use crate::notifier::{Notifier, UserDirectory};
+use crate::slack::SlackApi;
+use crate::template::MessageTemplate;
+use crate::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use url::Url;
+
+/// Azure DevOps team whose pull requests are reported and reviewed.
+#[derive(Clone)]
+pub struct AzureClient {
+    api: AzureApi,
+    team_name: String,
+    reviewers: Option<ReviewersConfig>,
+}
+
+#[derive(Default)]
+pub struct AzureClientBuilder {
+    base_url: Option<String>,
+    token: Option<String>,
+    project: Option<String>,
+    team_name: Option<String>,
+    reviewers: Option<ReviewersConfig>,
+    optional_reviewers: Option<(usize, OptionalReviewersStrategy)>,
+    excluded_reviewers: Vec<String>,
+    author_affinity: Option<AuthorAffinity>,
+    rotation_state_file: Option<PathBuf>,
+}
+
+impl AzureClient {
+    pub fn builder() -> AzureClientBuilder {
+        AzureClientBuilder::default()
+    }
+
+    pub async fn team_members(&self) -> Result<Vec<TeamMember>, Error> {
+        let team = Identifier(self.team_name.clone());
+        Ok(self.api.team_members(team).await?)
+    }
+
+    /// Pull requests of the team members in the repositories. Members for whom
+    /// `include_member` returns false are skipped.
+    pub async fn pull_requests<F>(
+        &self,
+        repositories: Vec<String>,
+        include_member: F,
+    ) -> Result<TeamRequests, Error>
+    where
+        F: Fn(&TeamMember) -> bool + Send + Sync,
+    {
+        let provider =
+            AzureReviewerRequestsProvider::new(self.api.clone(), &self.team_name, repositories);
+        Ok(provider.pull_requests(include_member).await?)
+    }
+
+    /// Adds the team members to the reviewers of the pull request, selecting the
+    /// required reviewers as configured with `AzureClientBuilder::reviewers`.
+    /// Members for whom `is_unavailable` returns true are added last as optional
+    /// reviewers.
+    pub async fn add_reviewers<F>(
+        &self,
+        repository_id: &str,
+        pull_request_id: &str,
+        is_unavailable: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn(&TeamMember) -> bool + Send + Sync,
+    {
+        let Some(reviewers) = self.reviewers.clone() else {
+            let error = "reviewers are not configured, see `AzureClientBuilder::reviewers`";
+            return Err(Error::Config(error.to_string()));
+        };
+        let service = AddReviewersServiceImpl::new(
+            self.api.clone(),
+            &self.team_name,
+            pull_request_id.to_string(),
+            repository_id.to_string(),
+            reviewers,
+        );
+        Ok(service.add_reviewers(is_unavailable).await?)
+    }
+}
+
+impl AzureClientBuilder {
+    /// Organization URL, e.g. `https://dev.azure.com/organization/`.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Personal access token.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn project(mut self, project: impl Into<String>) -> Self {
+        self.project = Some(project.into());
+        self
+    }
+
+    pub fn team_name(mut self, team_name: impl Into<String>) -> Self {
+        self.team_name = Some(team_name.into());
+        self
+    }
+
+    /// Number of required reviewers added to pull requests and the dev teams
+    /// with their required reviewers teams, as `(team, required_reviewers_team)`.
+    pub fn reviewers(
+        mut self,
+        required_reviewers_count: usize,
+        teams: impl IntoIterator<Item = (String, Option<String>)>,
+    ) -> Self {
+        let teams = teams
+            .into_iter()
+            .map(|(name, required_reviewers_team)| AzureTeam::new(name, required_reviewers_team))
+            .collect();
+        self.reviewers = Some(ReviewersConfig::new(required_reviewers_count, teams));
+        self
+    }
+
+    /// Limits the optional reviewers added from the team to `count` available
+    /// members selected by the strategy.
+    pub fn optional_reviewers(mut self, count: usize, strategy: OptionalReviewersStrategy) -> Self {
+        self.optional_reviewers = Some((count, strategy));
+        self
+    }
+
+    /// Names or mail addresses of the members who are never added as reviewers.
+    pub fn excluded_reviewers(mut self, names: impl IntoIterator<Item = String>) -> Self {
+        self.excluded_reviewers = names.into_iter().collect();
+        self
+    }
+
+    /// Selects the required reviewers by their affinity to the author's pull
+    /// request instead of the round-robin order.
+    pub fn author_affinity(mut self, author_affinity: AuthorAffinity) -> Self {
+        self.author_affinity = Some(author_affinity);
+        self
+    }
+
+    /// Takes the required reviewers in a round-robin rotation, which is kept in the
+    /// file between the calls, instead of shuffling them.
+    pub fn rotation_state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.rotation_state_file = Some(path.into());
+        self
+    }
+
+    pub fn build(self) -> Result<AzureClient, Error> {
+        let base_url = parse_url(required(self.base_url, "base_url")?)?;
+        let token = required(self.token, "token")?;
+        let project = required(self.project, "project")?;
+        Ok(AzureClient {
+            api: AzureApi::new(&token, &base_url, &project),
+            team_name: required(self.team_name, "team_name")?,
+            reviewers: self.reviewers.map(|reviewers| {
+                let mut reviewers = reviewers.with_excluded_reviewers(self.excluded_reviewers);
+                if let Some(author_affinity) = self.author_affinity {
+                    reviewers = reviewers.with_author_affinity(author_affinity);
+                }
+                if let Some(path) = self.rotation_state_file {
+                    reviewers = reviewers.with_rotation_state_file(path);
+                }
+                match self.optional_reviewers {
+                    Some((count, strategy)) => reviewers.with_optional_reviewers(count, strategy),
+                    None => reviewers,
+                }
+            }),
+        })
+    }
+}
+
+/// Slack workspace whose usergroup members receive the reports.
+#[derive(Clone)]
+pub struct SlackClient {
+    api: SlackApi,
+}
+
+#[derive(Default)]
+pub struct SlackClientBuilder {
+    token: Option<String>,
+    team_id: Option<String>,
+    usergroup_id: Option<String>,
+    base_url: Option<String>,
+    template: Option<String>,
+}
+
+impl SlackClient {
+    pub fn builder() -> SlackClientBuilder {
+        SlackClientBuilder::default()
+    }
+
+    /// Members of the usergroup with their availability.
+    pub async fn directory(&self) -> Result<UserDirectory, Error> {
+        Ok(self.api.obtain_directory().await?)
+    }
+
+    /// Sends the pull requests of the reviewer to the Slack user.
+    pub async fn send_report(
+        &self,
+        user_id: &str,
+        requests: &ReviewerRequests,
+    ) -> Result<(), Error> {
+        Ok(self.api.send_message(user_id, requests).await?)
+    }
+}
+
+impl SlackClientBuilder {
+    /// Bot token.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn team_id(mut self, team_id: impl Into<String>) -> Self {
+        self.team_id = Some(team_id.into());
+        self
+    }
+
+    pub fn usergroup_id(mut self, usergroup_id: impl Into<String>) -> Self {
+        self.usergroup_id = Some(usergroup_id.into());
+        self
+    }
+
+    /// Base URL of the Slack Web API, `https://slack.com/api/` by default.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Message template in the syntax described in the README.
+    pub fn template(mut self, template: impl Into<String>) -> Self {
+        self.template = Some(template.into());
+        self
+    }
+
+    pub fn build(self) -> Result<SlackClient, Error> {
+        let token = required(self.token, "token")?;
+        let team_id = required(self.team_id, "team_id")?;
+        let usergroup_id = required(self.usergroup_id, "usergroup_id")?;
+        let mut api = SlackApi::new(&token, &team_id, &usergroup_id);
+        if let Some(base_url) = self.base_url {
+            api = api.with_base_url(parse_url(base_url)?);
+        }
+        if let Some(template) = self.template {
+            let template = MessageTemplate::new(template)
+                .map_err(|e| Error::Config(format!("invalid template: {e}")))?;
+            api = api.with_template(Arc::new(template));
+        }
+        Ok(SlackClient { api })
+    }
+}
+
+fn required(value: Option<String>, name: &str) -> Result<String, Error> {
+    value.ok_or_else(|| Error::Config(format!("`{name}` is required")))
+}
+
+fn parse_url(url: String) -> Result<Url, Error> {
+    Url::parse(&url).map_err(|e| Error::Config(format!("invalid URL {url}: {e}")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clients_can_be_shared_between_tasks() {
+        fn assert_shareable<T: Send + Sync + 'static>() {}
+        assert_shareable::<AzureClient>();
+        assert_shareable::<SlackClient>();
+        assert_shareable::<TeamRequests>();
+        assert_shareable::<UserDirectory>();
+        assert_shareable::<Error>();
+    }
+
+    #[test]
+    fn missing_settings_rejected() {
+        let error = AzureClient::builder()
+            .base_url("https://dev.azure.com/organization/")
+            .token("token")
+            .team_name("Developers")
+            .build()
+            .err();
+        assert!(
+            matches!(&error, Some(Error::Config(message)) if message.contains("project")),
+            "{error:?}"
+        );
+
+        let error = SlackClient::builder()
+            .token("token")
+            .team_id("T1")
+            .usergroup_id("S1")
+            .template("{% if %}")
+            .build()
+            .err();
+        assert!(matches!(error, Some(Error::Config(_))), "{error:?}");
+    }
+}
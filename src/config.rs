@@ -1,79 +1,677 @@
 use super::azure::{self, ReviewerRequestsProvider};
 use crate::{
-    azure::{AddReviewersService, AzureTeam, ReviewersConfig},
+    azure::{
+        AbsentReviewers, AddReviewersService, AuthorAffinity, AzureTeam, ConfigChecker,
+        OptionalReviewersStrategy, RebalanceReviewersService, RepositoryDiscovery,
+        ReviewStatsProvider, ReviewersConfig,
+    },
+    backend::Backend,
+    bitbucket,
+    email::{SmtpNotifier, SmtpSecurity},
+    gitlab,
+    mattermost::MattermostApi,
+    notifier::Notifier,
+    secret::{self, Secret},
     slack::SlackApi,
+    teams::TeamsApi,
+    template::MessageTemplate,
+    webhook::WebhookNotifier,
 };
+use color_eyre::{eyre::WrapErr, Report, Result};
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const PROFILES_KEY: &str = "profiles";
+const DEFAULT_PROFILE: &str = "default";
 
 #[derive(Deserialize, Debug)]
 struct AzureConfig {
     base_url: url::Url,
-    token: String,
+    token: Secret,
     project: String,
     team_name: String,
     pull_request_reviewers: Option<PullRequestReviewersConfig>,
+    repository_discovery: Option<RepositoryDiscovery>,
+}
+
+/// Connection to a GitLab or Bitbucket server.
+#[derive(Deserialize, Debug)]
+struct HostingConfig {
+    base_url: url::Url,
+    token: Secret,
+    team_name: String,
+    pull_request_reviewers: Option<PullRequestReviewersConfig>,
 }
 
 #[derive(Deserialize, Debug)]
 struct SlackConfig {
-    token: String,
+    token: Secret,
     team_id: String,
     usergroup_id: String,
+    /// Base URL of the Slack Web API, replaced by a fake server in tests.
+    #[serde(default = "default_slack_base_url")]
+    base_url: url::Url,
+}
+
+#[derive(Deserialize, Debug)]
+struct TeamsConfig {
+    token: Secret,
+    group_id: String,
+    sender_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct MattermostConfig {
+    base_url: url::Url,
+    token: Secret,
+    team_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct WebhookConfig {
+    url: url::Url,
+    #[serde(default)]
+    headers: HashMap<String, Secret>,
+    secret: Option<Secret>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmailConfig {
+    host: String,
+    port: Option<u16>,
+    #[serde(default)]
+    security: SmtpSecurity,
+    username: Option<String>,
+    password: Option<Secret>,
+    from: String,
+    #[serde(default = "default_email_subject")]
+    subject: String,
+}
+
+/// Message template, either inline or in a file.
+#[derive(Deserialize, Debug, Default)]
+struct MessageConfig {
+    template: Option<String>,
+    template_file: Option<PathBuf>,
 }
 
 #[derive(Deserialize, Debug)]
 struct PullRequestReviewersConfig {
     required_reviewers_count: usize,
     teams: Vec<AzureTeam>,
+    /// What `rebalance-reviewers` does with the replaced reviewers.
+    #[serde(default)]
+    absent_reviewers: AbsentReviewers,
+    /// Limit of the optional reviewers added from the umbrella team.
+    optional_reviewers_count: Option<usize>,
+    #[serde(default)]
+    optional_reviewers_strategy: OptionalReviewersStrategy,
+    /// Names or mail addresses of the members who are never added as reviewers.
+    #[serde(default)]
+    excluded_reviewers: Vec<String>,
+    /// Selection of the required reviewers by their affinity to the author.
+    author_affinity: Option<AuthorAffinity>,
+    /// File of the round-robin rotation of the required reviewers.
+    rotation_state_file: Option<PathBuf>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
-    azure: AzureConfig,
-    slack: SlackConfig,
+    /// Repositories checked when none are given on the command line.
+    #[serde(default)]
+    repositories: Vec<String>,
+    azure: Option<AzureConfig>,
+    gitlab: Option<HostingConfig>,
+    bitbucket: Option<HostingConfig>,
+    slack: Option<SlackConfig>,
+    teams: Option<TeamsConfig>,
+    mattermost: Option<MattermostConfig>,
+    webhook: Option<WebhookConfig>,
+    email: Option<EmailConfig>,
+    #[serde(default)]
+    message: MessageConfig,
+    #[serde(skip)]
+    template: Arc<MessageTemplate>,
+}
+
+/// Named configuration of a team.
+///
+/// Profiles are declared in `[profiles.<name>]` tables. Every profile is the top
+/// level configuration with the profile's tables merged over it, so shared settings
+/// are written once. A file without profiles has the single `default` profile.
+#[derive(Debug)]
+pub struct Profile {
+    pub name: String,
+    pub config: Config,
+    /// Paths of the keys which are not part of the config schema.
+    pub unknown_keys: Vec<String>,
+}
+
+impl Profile {
+    /// Parses the profiles of the config. Returns all profiles or the selected one,
+    /// resolving secret references of the returned profiles only.
+    pub fn parse_all(content: &str, selected: Option<&str>) -> Result<Vec<Self>> {
+        let value = toml::from_str::<toml::Value>(content)?;
+        let toml::Value::Table(mut base) = value else {
+            return Err(Report::msg("Config must be a table."));
+        };
+        let profiles = match base.remove(PROFILES_KEY) {
+            None => {
+                toml::Table::from_iter([(DEFAULT_PROFILE.to_string(), toml::Table::new().into())])
+            }
+            Some(toml::Value::Table(profiles)) if !profiles.is_empty() => profiles,
+            Some(_) => {
+                return Err(Report::msg(format!(
+                    "[{PROFILES_KEY}] must contain at least one [{PROFILES_KEY}.<name>] table."
+                )))
+            }
+        };
+        if let Some(selected) = selected.filter(|v| !profiles.contains_key(*v)) {
+            return Err(Report::msg(format!("Profile {selected} was not found.")));
+        }
+
+        profiles
+            .into_iter()
+            .filter(|(name, _)| selected.is_none_or(|v| v == name))
+            .map(|(name, overrides)| {
+                let mut value = toml::Value::Table(base.clone());
+                merge(&mut value, overrides);
+                secret::resolve_secrets(&mut value)
+                    .wrap_err_with(|| format!("Invalid secret of profile {name}"))?;
+                let mut unknown_keys = Vec::new();
+                let mut config: Config = serde_ignored::deserialize(value, |path| {
+                    unknown_keys.push(path.to_string());
+                })
+                .wrap_err_with(|| format!("Invalid profile {name}"))?;
+                let template = config.message.template();
+                config.template = Arc::new(
+                    template
+                        .wrap_err_with(|| format!("Invalid message template of profile {name}"))?,
+                );
+                Ok(Self {
+                    name,
+                    config,
+                    unknown_keys,
+                })
+            })
+            .collect()
+    }
 }
 
 impl Config {
+    pub fn validate(&self) -> Result<()> {
+        let backends_count = [
+            self.azure.is_some(),
+            self.gitlab.is_some(),
+            self.bitbucket.is_some(),
+        ]
+        .into_iter()
+        .filter(|v| *v)
+        .count();
+        if backends_count != 1 {
+            return Err(Report::msg(
+                "Config must have exactly one of [azure], [gitlab] or [bitbucket] sections.",
+            ));
+        }
+        let notifiers_count = [
+            self.slack.is_some(),
+            self.teams.is_some(),
+            self.mattermost.is_some(),
+            self.webhook.is_some(),
+        ]
+        .into_iter()
+        .filter(|v| *v)
+        .count();
+        if notifiers_count != 1 {
+            return Err(Report::msg(
+                "Config must have exactly one of [slack], [teams], [mattermost] or [webhook] sections.",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Review statistics need the vote history of pull request threads, which only
+    /// Azure DevOps provides.
+    pub fn review_stats_provider(
+        &self,
+        repositories: Vec<String>,
+    ) -> Result<impl ReviewStatsProvider + '_> {
+        let config = self
+            .azure
+            .as_ref()
+            .ok_or_else(|| Report::msg("Review statistics are available for Azure DevOps only."))?;
+        Ok(azure::make_review_stats_provider(
+            config.token.expose(),
+            &config.base_url,
+            &config.project,
+            &config.team_name,
+            repositories,
+        ))
+    }
+
+    /// Reviewers are replaced through the reviewer endpoints of Azure DevOps.
+    pub fn rebalance_reviewers_service(
+        &self,
+        repositories: Vec<String>,
+    ) -> Result<impl RebalanceReviewersService + '_> {
+        let config = self.azure.as_ref().ok_or_else(|| {
+            Report::msg("Rebalancing reviewers is available for Azure DevOps only.")
+        })?;
+        let reviewers_config = config
+            .pull_request_reviewers
+            .as_ref()
+            .ok_or_else(|| Report::msg("Config must have [azure.pull_request_reviewers]."))?;
+        Ok(azure::make_rebalance_reviewers_service(
+            config.token.expose(),
+            &config.base_url,
+            &config.project,
+            &config.team_name,
+            repositories,
+            reviewers_config.reviewers_config(None),
+            reviewers_config.absent_reviewers,
+        ))
+    }
+
     pub fn pull_requests_provider(
         &self,
         repositories: Vec<String>,
     ) -> impl ReviewerRequestsProvider + '_ {
-        let config = &self.azure;
-        azure::make_pull_requests_provider(
-            &config.token,
+        if let Some(config) = &self.azure {
+            return Backend::Azure(azure::make_pull_requests_provider(
+                config.token.expose(),
+                &config.base_url,
+                &config.project,
+                &config.team_name,
+                repositories,
+            ));
+        }
+        if let Some(config) = &self.gitlab {
+            return Backend::GitLab(gitlab::make_pull_requests_provider(
+                config.token.expose(),
+                &config.base_url,
+                &config.team_name,
+                repositories,
+            ));
+        }
+        let config = self.bitbucket_config();
+        Backend::Bitbucket(bitbucket::make_pull_requests_provider(
+            config.token.expose(),
             &config.base_url,
-            &config.project,
             &config.team_name,
             repositories,
-        )
+        ))
     }
 
     pub fn add_reviewers_service(
         &self,
         pull_request_id: String,
         repository_id: String,
+        seed: Option<u64>,
     ) -> impl AddReviewersService + '_ {
-        let azure_config = &self.azure;
-        let reviewers_config = azure_config
+        if let Some(azure_config) = &self.azure {
+            let reviewers_config = azure_config
+                .pull_request_reviewers
+                .as_ref()
+                .expect("Config must have [azure.pull_request_reviewers].");
+            return Backend::Azure(azure::make_add_reviewers_service(
+                azure_config.token.expose(),
+                &azure_config.base_url,
+                &azure_config.project,
+                &azure_config.team_name,
+                pull_request_id,
+                repository_id,
+                reviewers_config.reviewers_config(seed),
+            ));
+        }
+        if let Some(gitlab_config) = &self.gitlab {
+            let reviewers_config = gitlab_config
+                .pull_request_reviewers
+                .as_ref()
+                .expect("Config must have [gitlab.pull_request_reviewers].");
+            return Backend::GitLab(gitlab::make_add_reviewers_service(
+                gitlab_config.token.expose(),
+                &gitlab_config.base_url,
+                &gitlab_config.team_name,
+                pull_request_id,
+                repository_id,
+                reviewers_config.reviewers_config(seed),
+            ));
+        }
+        let bitbucket_config = self.bitbucket_config();
+        let reviewers_config = bitbucket_config
             .pull_request_reviewers
             .as_ref()
-            .expect("Config must have [azure.pull_request_reviewers].");
-        azure::make_add_reviewers_service(
-            &azure_config.token,
-            &azure_config.base_url,
-            &azure_config.project,
-            &azure_config.team_name,
+            .expect("Config must have [bitbucket.pull_request_reviewers].");
+        Backend::Bitbucket(bitbucket::make_add_reviewers_service(
+            bitbucket_config.token.expose(),
+            &bitbucket_config.base_url,
+            &bitbucket_config.team_name,
             pull_request_id,
             repository_id,
-            ReviewersConfig::new(
-                reviewers_config.required_reviewers_count,
-                &reviewers_config.teams,
-            ),
+            reviewers_config.reviewers_config(seed),
+        ))
+    }
+
+    /// Repositories of the config unless others are provided.
+    pub fn repositories(&self, repositories: &[String]) -> Vec<String> {
+        if repositories.is_empty() {
+            self.repositories.clone()
+        } else {
+            repositories.to_vec()
+        }
+    }
+
+    /// Repositories found by the repository discovery, if it is configured.
+    pub async fn discover_repositories(&self) -> Result<Vec<String>> {
+        let Some(config) = &self.azure else {
+            return Ok(vec![]);
+        };
+        let Some(discovery) = &config.repository_discovery else {
+            return Ok(vec![]);
+        };
+        azure::discover_repositories(
+            config.token.expose(),
+            &config.base_url,
+            &config.project,
+            discovery,
+        )
+        .await
+    }
+
+    pub fn has_repository_discovery(&self) -> bool {
+        self.azure
+            .as_ref()
+            .is_some_and(|config| config.repository_discovery.is_some())
+    }
+
+    /// Identifies the repository on its code hosting, so a repository of several
+    /// profiles gets its reviewers only once.
+    pub fn repository_key(&self, repository: &str) -> String {
+        if let Some(config) = &self.azure {
+            let project = config.project.to_lowercase();
+            let repository = repository.to_lowercase();
+            return format!("azure/{}/{project}/{repository}", config.base_url);
+        }
+        if let Some(config) = &self.gitlab {
+            return format!("gitlab/{}/{repository}", config.base_url);
+        }
+        self.bitbucket
+            .as_ref()
+            .map(|config| format!("bitbucket/{}/{repository}", config.base_url))
+            .unwrap_or_else(|| repository.to_string())
+    }
+
+    pub fn has_pull_request_reviewers(&self) -> bool {
+        self.azure
+            .as_ref()
+            .map(|v| &v.pull_request_reviewers)
+            .or(self.gitlab.as_ref().map(|v| &v.pull_request_reviewers))
+            .or(self.bitbucket.as_ref().map(|v| &v.pull_request_reviewers))
+            .is_some_and(Option::is_some)
+    }
+
+    /// Identifies the user directory of the notifier, so profiles sharing
+    /// the directory obtain it only once.
+    pub fn directory_key(&self) -> String {
+        if let Some(config) = &self.slack {
+            return format!("slack/{}/{}", config.team_id, config.usergroup_id);
+        }
+        if let Some(config) = &self.teams {
+            return format!("teams/{}", config.group_id);
+        }
+        if let Some(config) = &self.mattermost {
+            return format!("mattermost/{}/{}", config.base_url, config.team_id);
+        }
+        self.webhook
+            .as_ref()
+            .map(|config| format!("webhook/{}", config.url))
+            .unwrap_or_default()
+    }
+
+    pub fn config_checker(&self, repositories: Vec<String>) -> impl ConfigChecker + '_ {
+        if let Some(config) = &self.azure {
+            return Backend::Azure(azure::make_config_checker(
+                config.token.expose(),
+                &config.base_url,
+                &config.project,
+                &config.team_name,
+                reviewers_teams(&config.pull_request_reviewers),
+                repositories,
+            ));
+        }
+        if let Some(config) = &self.gitlab {
+            return Backend::GitLab(gitlab::make_config_checker(
+                config.token.expose(),
+                &config.base_url,
+                &config.team_name,
+                reviewers_teams(&config.pull_request_reviewers),
+                repositories,
+            ));
+        }
+        let config = self.bitbucket_config();
+        Backend::Bitbucket(bitbucket::make_config_checker(
+            config.token.expose(),
+            &config.base_url,
+            &config.team_name,
+            reviewers_teams(&config.pull_request_reviewers),
+            repositories,
+        ))
+    }
+
+    pub fn notifier(&self) -> Result<Box<dyn Notifier + '_>> {
+        if let Some(config) = &self.mattermost {
+            return Ok(Box::new(
+                MattermostApi::new(config.token.expose(), &config.team_id, &config.base_url)
+                    .with_template(&self.template),
+            ));
+        }
+        if let Some(config) = &self.webhook {
+            return Ok(Box::new(
+                WebhookNotifier::new(
+                    &config.url,
+                    config
+                        .headers
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value.expose())),
+                    config.secret.as_ref().map(Secret::expose),
+                )?
+                .with_template(&self.template),
+            ));
+        }
+        if let Some(config) = &self.teams {
+            return Ok(Box::new(
+                TeamsApi::new(config.token.expose(), &config.group_id, &config.sender_id)
+                    .with_template(&self.template),
+            ));
+        }
+        let config = self
+            .slack
+            .as_ref()
+            .expect("Config must have a notifier section.");
+        Ok(Box::new(
+            SlackApi::new(config.token.expose(), &config.team_id, &config.usergroup_id)
+                .with_base_url(config.base_url.clone())
+                .with_template(self.template.clone()),
+        ))
+    }
+
+    /// Notifier for users who can not be found in the chat directory.
+    pub fn email_notifier(&self) -> Result<Option<SmtpNotifier<'_>>> {
+        let Some(config) = &self.email else {
+            return Ok(None);
+        };
+        let credentials = config
+            .username
+            .as_deref()
+            .zip(config.password.as_ref().map(Secret::expose));
+        SmtpNotifier::new(
+            &config.host,
+            config.port,
+            config.security,
+            credentials,
+            &config.from,
+            &config.subject,
         )
+        .map(|notifier| Some(notifier.with_template(&self.template)))
+    }
+
+    fn bitbucket_config(&self) -> &HostingConfig {
+        self.bitbucket
+            .as_ref()
+            .expect("Config must have [azure], [gitlab] or [bitbucket] section.")
+    }
+}
+
+impl MessageConfig {
+    fn template(&self) -> Result<MessageTemplate> {
+        match (&self.template, &self.template_file) {
+            (None, None) => Ok(MessageTemplate::BuiltIn),
+            (Some(template), None) => MessageTemplate::new(template.clone()),
+            (None, Some(path)) => MessageTemplate::new(std::fs::read_to_string(path)?),
+            (Some(_), Some(_)) => Err(Report::msg(
+                "Only one of `template` or `template_file` can be set.",
+            )),
+        }
+    }
+}
+
+impl PullRequestReviewersConfig {
+    fn reviewers_config(&self, seed: Option<u64>) -> ReviewersConfig {
+        let mut config = ReviewersConfig::new(self.required_reviewers_count, self.teams.clone())
+            .with_excluded_reviewers(self.excluded_reviewers.clone());
+        if let Some(author_affinity) = self.author_affinity {
+            config = config.with_author_affinity(author_affinity);
+        }
+        if let Some(seed) = seed {
+            config = config.with_seed(seed);
+        }
+        if let Some(path) = &self.rotation_state_file {
+            config = config.with_rotation_state_file(path.clone());
+        }
+        match self.optional_reviewers_count {
+            Some(count) => config.with_optional_reviewers(count, self.optional_reviewers_strategy),
+            None => config,
+        }
+    }
+}
+
+/// Merges the `overrides` tables into the `base` ones, replacing other values.
+fn merge(base: &mut toml::Value, overrides: toml::Value) {
+    match (base, overrides) {
+        (toml::Value::Table(base), toml::Value::Table(overrides)) => {
+            for key in overrides.keys() {
+                for source in secret::other_sources(key) {
+                    base.remove(&source);
+                }
+            }
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(base) => merge(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+fn reviewers_teams(config: &Option<PullRequestReviewersConfig>) -> Vec<&str> {
+    config
+        .iter()
+        .flat_map(|config| config.teams.iter().flat_map(AzureTeam::names))
+        .collect()
+}
+
+fn default_slack_base_url() -> url::Url {
+    url::Url::parse("https://slack.com/api/").expect("Failed to create Slack base URL")
+}
+
+fn default_email_subject() -> String {
+    "Pull requests waiting for you".to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        repositories = ["shared"]
+        [azure]
+        base_url = "https://dev.azure.com/org/"
+        token = "token"
+        project = "Mobile"
+        team_name = "Mobile"
+        [slack]
+        token = "token"
+        team_id = "T1"
+        usergroup_id = "S1"
+
+        [profiles.mobile]
+        [profiles.web]
+        repositories = ["web"]
+        unknown = 1
+        [profiles.web.azure]
+        project = "Web"
+        team_name = "Web"
+        [profiles.web.slack]
+        usergroup_id = "S2"
+    "#;
+
+    #[test]
+    fn profiles_override_top_level_settings() -> Result<()> {
+        let profiles = Profile::parse_all(CONFIG, None)?;
+        assert_eq!(profiles.len(), 2);
+
+        let mobile = &profiles[0];
+        assert_eq!(mobile.name, "mobile");
+        assert_eq!(mobile.config.repositories(&[]), ["shared"]);
+        assert!(mobile.unknown_keys.is_empty());
+
+        let web = &profiles[1];
+        let azure = web.config.azure.as_ref().unwrap();
+        assert_eq!(
+            (azure.project.as_str(), azure.team_name.as_str()),
+            ("Web", "Web")
+        );
+        assert_eq!(azure.token.expose(), "token");
+        assert_eq!(web.config.repositories(&[]), ["web"]);
+        assert_eq!(web.config.directory_key(), "slack/T1/S2");
+        assert_eq!(web.unknown_keys, ["unknown"]);
+        Ok(())
     }
 
-    pub fn slack_api(&self) -> SlackApi {
-        let config = &self.slack;
-        SlackApi::new(&config.token, &config.team_id, &config.usergroup_id)
+    #[test]
+    fn profile_selected_by_name() -> Result<()> {
+        let profiles = Profile::parse_all(CONFIG, Some("web"))?;
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].name, "web");
+        assert!(Profile::parse_all(CONFIG, Some("missing")).is_err());
+
+        let content = CONFIG.split("[profiles.mobile]").next().unwrap();
+        let profiles = Profile::parse_all(content, None)?;
+        assert_eq!(profiles[0].name, DEFAULT_PROFILE);
+        Ok(())
+    }
+
+    #[test]
+    fn secrets_resolved_for_selected_profiles() -> Result<()> {
+        let content = format!(
+            r#"{CONFIG}
+            [profiles.broken.azure]
+            token_command = "exit 1"
+            [profiles.mobile.slack]
+            token_command = "echo mobile-token"
+            "#
+        );
+        assert!(Profile::parse_all(&content, None).is_err());
+
+        let profiles = Profile::parse_all(&content, Some("mobile"))?;
+        let slack = profiles[0].config.slack.as_ref().unwrap();
+        assert_eq!(slack.token.expose(), "mobile-token");
+        Ok(())
     }
 }
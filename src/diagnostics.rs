@@ -0,0 +1,68 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Results of the configuration checks.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    findings: Vec<Finding>,
+}
+
+impl Diagnostics {
+    pub fn ok(&mut self, message: impl Into<String>) {
+        self.push(Severity::Ok, message.into());
+    }
+
+    pub fn warning(&mut self, message: impl Into<String>) {
+        self.push(Severity::Warning, message.into());
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.push(Severity::Error, message.into());
+    }
+
+    pub fn extend(&mut self, other: Diagnostics) {
+        self.findings.extend(other.findings);
+    }
+
+    #[cfg(test)]
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    pub fn errors_count(&self) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == Severity::Error)
+            .count()
+    }
+
+    fn push(&mut self, severity: Severity, message: String) {
+        self.findings.push(Finding { severity, message });
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        for finding in &self.findings {
+            let label = match finding.severity {
+                Severity::Ok => "ok",
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            };
+            writeln!(f, "[{label:^7}] {}", finding.message)?;
+        }
+        Ok(())
+    }
+}
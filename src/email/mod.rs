@@ -0,0 +1,180 @@
+use crate::azure::{MessageFormat, ReviewerRequests};
+use crate::notifier::{Notifier, UserDirectory};
+use crate::template::MessageTemplate;
+use async_trait::async_trait;
+use color_eyre::Result;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Deserialize;
+
+/// Connection security of the SMTP server.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Implicit TLS, usually on port 465.
+    Tls,
+    /// Plain connection upgraded with STARTTLS, usually on port 587.
+    #[default]
+    Starttls,
+    /// Unencrypted connection. Intended for local relays only.
+    None,
+}
+
+/// Sends reviewer requests by email.
+///
+/// SMTP has no user directory, so messages are addressed by the mail address of
+/// the reviewer's identity instead of a directory user id.
+pub struct SmtpNotifier<'a> {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    subject: &'a str,
+    template: &'a MessageTemplate,
+}
+
+impl<'a> SmtpNotifier<'a> {
+    pub fn new(
+        host: &str,
+        port: Option<u16>,
+        security: SmtpSecurity,
+        credentials: Option<(&str, &str)>,
+        from: &str,
+        subject: &'a str,
+    ) -> Result<Self> {
+        let mut builder = match security {
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            SmtpSecurity::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        if let Some(port) = port {
+            builder = builder.port(port);
+        }
+        if let Some((username, password)) = credentials {
+            builder =
+                builder.credentials(Credentials::new(username.to_string(), password.to_string()));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from: from.parse()?,
+            subject,
+            template: MessageTemplate::built_in(),
+        })
+    }
+
+    pub fn with_template(mut self, template: &'a MessageTemplate) -> Self {
+        self.template = template;
+        self
+    }
+}
+
+impl<'a> SmtpNotifier<'a> {
+    pub async fn test_connection(&self) -> Result<()> {
+        if self.transport.test_connection().await? {
+            Ok(())
+        } else {
+            Err(color_eyre::Report::msg(
+                "SMTP server rejected the connection.",
+            ))
+        }
+    }
+}
+
+#[async_trait]
+impl<'a> Notifier for SmtpNotifier<'a> {
+    async fn obtain_directory(&self) -> Result<UserDirectory> {
+        Ok(UserDirectory::Open)
+    }
+
+    async fn send_message(&self, user_id: &str, requests: &ReviewerRequests) -> Result<()> {
+        tracing::info!("Sending email to {user_id}.");
+        let to = Mailbox::new(Some(requests.reviewer_name.clone()), user_id.parse()?);
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(self.subject)
+            .multipart(MultiPart::alternative_plain_html(
+                self.template.render(requests, MessageFormat::PlainText)?,
+                self.template.render(requests, MessageFormat::Html)?,
+            ))?;
+        self.transport.send(message).await?;
+        tracing::info!("Email successfully sent to {user_id}.");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    /// Accepts a single SMTP session and returns the received message data.
+    async fn start_smtp_sink() -> (u16, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+            let mut data = String::new();
+            let mut is_reading_data = false;
+            while let Ok(Some(line)) = lines.next_line().await {
+                if is_reading_data {
+                    if line == "." {
+                        is_reading_data = false;
+                        writer.write_all(b"250 OK\r\n").await.unwrap();
+                    } else {
+                        data.push_str(&line);
+                        data.push('\n');
+                    }
+                    continue;
+                }
+                let command = line.to_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") {
+                    b"250-localhost\r\n250 8BITMIME\r\n"
+                } else if command.starts_with("DATA") {
+                    is_reading_data = true;
+                    b"354 End data with <CR><LF>.<CR><LF>\r\n"
+                } else if command.starts_with("QUIT") {
+                    writer.write_all(b"221 Bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 OK\r\n"
+                };
+                writer.write_all(reply).await.unwrap();
+            }
+            data
+        });
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn multipart_email_sent() -> Result<()> {
+        let (port, sink) = start_smtp_sink().await;
+        let notifier = SmtpNotifier::new(
+            "127.0.0.1",
+            Some(port),
+            SmtpSecurity::None,
+            None,
+            "Reviewporter <reviewporter@example.com>",
+            "Pull requests waiting for you",
+        )?;
+        let requests = ReviewerRequests::empty("Alice".to_string());
+        notifier
+            .send_message("alice@example.com", &requests)
+            .await?;
+
+        let data = sink.await?;
+        assert!(data.contains("From: Reviewporter <reviewporter@example.com>"));
+        assert!(data.contains("To: Alice <alice@example.com>"));
+        assert!(data.contains("Subject: Pull requests waiting for you"));
+        assert!(data.contains("Content-Type: multipart/alternative"));
+        assert!(data.contains("Content-Type: text/plain; charset=utf-8"));
+        assert!(data.contains("Content-Type: text/html; charset=utf-8"));
+        assert!(data.contains("Hey!<br>"));
+        Ok(())
+    }
+}
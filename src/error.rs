@@ -0,0 +1,326 @@
+//! Errors of the services and the library API.
+//!
+//! The services report failures as `color_eyre::Report`, which carries an `Error`
+//! when the failure is known, e.g. a rejected token or a missing repository. Callers
+//! find it with `Error::find` to branch on the kind of the failure, and the library
+//! API converts the reports back to `Error`.
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Slack error codes of invalid tokens and missing permissions.
+const SLACK_AUTH_CODES: [&str; 7] = [
+    "not_authed",
+    "invalid_auth",
+    "account_inactive",
+    "token_revoked",
+    "token_expired",
+    "missing_scope",
+    "no_permission",
+];
+const SLACK_RATE_LIMITED_CODE: &str = "ratelimited";
+/// Code of a Slack response with `ok: false` and without the error.
+const SLACK_UNKNOWN_CODE: &str = "unknown_error";
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Setting of a client builder is missing or invalid.
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+    /// Unsuccessful response without a more specific variant.
+    #[error("{service} responded with {status}: {body}")]
+    Http {
+        service: String,
+        status: u16,
+        body: String,
+    },
+    /// Unsuccessful response of Azure DevOps with its error, e.g.
+    /// `GitPullRequestNotFoundException`.
+    #[error("Azure DevOps responded with {status} {code}: {message}")]
+    Azure {
+        status: u16,
+        code: String,
+        message: String,
+    },
+    /// Slack response with `ok: false`, e.g. `channel_not_found`.
+    #[error("Slack responded with error {code}")]
+    Slack { code: String },
+    /// Response which does not match the expected structure at the path.
+    #[error("Unable to deserialize the {service} response at {path}: {message}")]
+    Deserialize {
+        service: String,
+        path: String,
+        message: String,
+    },
+    /// Token is invalid, expired or lacks the permission.
+    #[error("{service} rejected the token with {status}: {message}")]
+    Auth {
+        service: String,
+        status: u16,
+        message: String,
+    },
+    #[error("{service} did not find the resource: {message}")]
+    NotFound { service: String, message: String },
+    /// Requests were rate limited even after the retries.
+    #[error("{service} rate limited the requests")]
+    RateLimited {
+        service: String,
+        retry_after: Option<Duration>,
+    },
+    /// Failure without a variant of its own, with the chain of its causes.
+    #[error("{0}")]
+    Other(String),
+}
+
+/// Error of the Azure DevOps response.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AzureError {
+    type_key: String,
+    message: String,
+}
+
+impl Error {
+    /// Returns the error carried in the chain of the report.
+    pub fn find(report: &color_eyre::Report) -> Option<&Error> {
+        report.chain().find_map(|e| e.downcast_ref::<Error>())
+    }
+
+    /// Status of the unsuccessful response which caused the error.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Http { status, .. } | Error::Azure { status, .. } => Some(*status),
+            Error::Auth { status, .. } => Some(*status),
+            Error::NotFound { .. } => Some(StatusCode::NOT_FOUND.as_u16()),
+            Error::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS.as_u16()),
+            _ => None,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Error::NotFound { .. })
+    }
+
+    /// Whether the request may succeed when it is repeated later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::RateLimited { .. } => true,
+            Error::Http { status, .. } | Error::Azure { status, .. } => {
+                StatusCode::from_u16(*status).is_ok_and(|s| s.is_server_error())
+            }
+            _ => false,
+        }
+    }
+
+    /// Error of the unsuccessful response. Azure DevOps errors are parsed from the
+    /// body, other bodies are kept as text.
+    pub(crate) async fn from_response(service: &str, response: Response) -> Self {
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        let body = response.text().await.unwrap_or_default();
+        let azure_error = serde_json::from_str::<AzureError>(&body).ok();
+        let message = match &azure_error {
+            Some(error) => format!("{}: {}", error.type_key, error.message),
+            None => body.clone(),
+        };
+        let service = service.to_string();
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Error::Auth {
+                service,
+                status: status.as_u16(),
+                message,
+            },
+            StatusCode::NOT_FOUND => Error::NotFound { service, message },
+            StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+                service,
+                retry_after,
+            },
+            status => match azure_error {
+                Some(error) => Error::Azure {
+                    status: status.as_u16(),
+                    code: error.type_key,
+                    message: error.message,
+                },
+                None => Error::Http {
+                    service,
+                    status: status.as_u16(),
+                    body,
+                },
+            },
+        }
+    }
+
+    /// Error of the Slack response with `ok: false`.
+    pub(crate) fn from_slack_code(code: Option<&str>) -> Self {
+        let code = code.unwrap_or(SLACK_UNKNOWN_CODE);
+        let service = "slack".to_string();
+        if SLACK_AUTH_CODES.contains(&code) {
+            Error::Auth {
+                service,
+                status: StatusCode::OK.as_u16(),
+                message: code.to_string(),
+            }
+        } else if code == SLACK_RATE_LIMITED_CODE {
+            Error::RateLimited {
+                service,
+                retry_after: None,
+            }
+        } else {
+            Error::Slack {
+                code: code.to_string(),
+            }
+        }
+    }
+}
+
+impl From<color_eyre::Report> for Error {
+    fn from(report: color_eyre::Report) -> Self {
+        match report.downcast::<Error>() {
+            Ok(error) => error,
+            Err(report) => Error::Other(format!("{report:#}")),
+        }
+    }
+}
+
+/// Deserializes the successful response, reporting the path of the value which does
+/// not match `T`.
+pub(crate) async fn json<T: DeserializeOwned>(
+    service: &str,
+    response: Response,
+) -> color_eyre::Result<T> {
+    let bytes = response.bytes().await?;
+    let deserializer = &mut serde_json::Deserializer::from_slice(&bytes);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| deserialize_error(service, e))
+}
+
+/// Deserializes the value of the response, reporting the path of the value which
+/// does not match `T`.
+pub(crate) fn from_value<T: DeserializeOwned>(
+    service: &str,
+    value: serde_json::Value,
+) -> color_eyre::Result<T> {
+    serde_path_to_error::deserialize(value).map_err(|e| deserialize_error(service, e))
+}
+
+fn deserialize_error(
+    service: &str,
+    error: serde_path_to_error::Error<serde_json::Error>,
+) -> color_eyre::Report {
+    let error = Error::Deserialize {
+        service: service.to_string(),
+        path: error.path().to_string(),
+        message: error.into_inner().to_string(),
+    };
+    error.into()
+}
+
+/// Returns the response if it is successful, otherwise its error.
+pub(crate) async fn error_for_status(
+    service: &str,
+    response: Response,
+) -> color_eyre::Result<Response> {
+    match response.status().is_success() {
+        true => Ok(response),
+        false => Err(Error::from_response(service, response).await.into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn response(status: u16, body: serde_json::Value) -> Response {
+        let response = http::Response::builder()
+            .status(status)
+            .header(RETRY_AFTER, "5")
+            .body(body.to_string())
+            .unwrap();
+        Response::from(response)
+    }
+
+    #[tokio::test]
+    async fn errors_classified_by_status_and_body() {
+        let azure_error = json!({
+            "typeKey": "GitPullRequestNotFoundException",
+            "message": "The pull request does not exist.",
+        });
+        let error = Error::from_response("azure", response(404, azure_error.clone())).await;
+        assert!(error.is_not_found(), "{error:?}");
+        assert!(error
+            .to_string()
+            .contains("GitPullRequestNotFoundException"));
+
+        let error = Error::from_response("azure", response(409, azure_error)).await;
+        assert!(
+            matches!(&error, Error::Azure { status: 409, code, .. } if code == "GitPullRequestNotFoundException"),
+            "{error:?}"
+        );
+
+        let error = Error::from_response("azure", response(401, json!(""))).await;
+        assert!(
+            matches!(error, Error::Auth { status: 401, .. }),
+            "{error:?}"
+        );
+
+        let error = Error::from_response("gitlab", response(429, json!(""))).await;
+        assert!(
+            matches!(error, Error::RateLimited { retry_after: Some(d), .. } if d.as_secs() == 5),
+            "{error:?}"
+        );
+        assert!(error.is_retryable());
+
+        let error = Error::from_response("gitlab", response(503, json!("down"))).await;
+        assert!(
+            matches!(error, Error::Http { status: 503, .. }),
+            "{error:?}"
+        );
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn slack_codes_classified() {
+        assert!(matches!(
+            Error::from_slack_code(Some("invalid_auth")),
+            Error::Auth { .. }
+        ));
+        assert!(matches!(
+            Error::from_slack_code(Some("ratelimited")),
+            Error::RateLimited { .. }
+        ));
+        assert!(matches!(
+            Error::from_slack_code(None),
+            Error::Slack { code } if code == SLACK_UNKNOWN_CODE
+        ));
+    }
+
+    #[tokio::test]
+    async fn deserialization_error_has_path() {
+        #[derive(Deserialize, Debug)]
+        struct Item {
+            #[allow(dead_code)]
+            id: u32,
+        }
+        let body = json!({ "value": [{ "id": 1 }, { "id": "two" }] });
+
+        let report =
+            json::<std::collections::HashMap<String, Vec<Item>>>("azure", response(200, body))
+                .await
+                .unwrap_err();
+
+        let error = Error::find(&report);
+        assert!(
+            matches!(error, Some(Error::Deserialize { path, .. }) if path == "value[1].id"),
+            "{error:?}"
+        );
+    }
+}
@@ -0,0 +1,751 @@
+use crate::azure::api::{
+    found, AzurePullRequestChangesService, AzurePullRequestService, AzurePullRequestsService,
+    AzureRepositoryService, AzureTeamService, Identifier, NewPullRequestReviewer, PullRequest,
+    PullRequestAuthor, PullRequestRepository, PullRequestReviewer, PullRequestSearchCriteria,
+    PullRequestStatus, Team, TeamMember, Vote,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use futures::{StreamExt, TryFutureExt, TryStreamExt};
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+const TOKEN_HEADER: &str = "PRIVATE-TOKEN";
+const NEXT_PAGE_HEADER: &str = "x-next-page";
+const PAGE_SIZE: usize = 100;
+/// Maximum number of the merge requests completed with their reviewers concurrently.
+const MERGE_REQUESTS_LIMIT: usize = 8;
+
+#[derive(Deserialize, Debug, Clone)]
+struct User {
+    id: u64,
+    name: String,
+    #[serde(default)]
+    state: Option<String>,
+    #[serde(default)]
+    public_email: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Group {
+    full_path: String,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum MergeRequestState {
+    Opened,
+    Closed,
+    Locked,
+    Merged,
+}
+
+#[derive(Deserialize, Debug)]
+struct MergeRequest {
+    iid: usize,
+    project_id: u64,
+    title: String,
+    web_url: Url,
+    author: User,
+    created_at: DateTime<Utc>,
+    state: MergeRequestState,
+    #[serde(default)]
+    detailed_merge_status: Option<String>,
+    #[serde(default)]
+    reviewers: Vec<User>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MergeRequestReviewer {
+    user: User,
+    #[serde(default)]
+    state: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Approval {
+    user: User,
+}
+
+#[derive(Deserialize, Debug)]
+struct Approvals {
+    #[serde(default)]
+    approved_by: Vec<Approval>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApprovalRule {
+    name: String,
+    #[serde(default)]
+    eligible_approvers: Vec<User>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Diff {
+    new_path: String,
+}
+
+#[derive(Serialize, Debug)]
+struct UpdateMergeRequest {
+    reviewer_ids: Vec<u64>,
+}
+
+/// GitLab REST API v4 client.
+///
+/// Implements the same services as `AzureApi`, so merge requests can be processed
+/// by the existing reviewer requests provider and add reviewers service.
+/// Teams are GitLab groups identified by their full path. A team identifier in the
+/// `<project path>#<approval rule name>` form is resolved to the eligible approvers
+/// of the project approval rule.
+pub struct GitLabApi<'a> {
+    token: &'a str,
+    base_url: &'a Url,
+    group: &'a str,
+    client: Client,
+}
+
+impl<'a> GitLabApi<'a> {
+    pub fn new(token: &'a str, base_url: &'a Url, group: &'a str) -> Self {
+        Self {
+            token,
+            base_url,
+            group,
+            client: crate::http::client(),
+        }
+    }
+
+    fn make_url<'s>(&self, segments: impl IntoIterator<Item = &'s str>) -> Result<Url> {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| color_eyre::Report::msg("GitLab base URL can not be a base."))?
+            .pop_if_empty()
+            .extend(["api", "v4"])
+            .extend(segments);
+        Ok(url)
+    }
+
+    async fn obtain_single_item<T: DeserializeOwned>(&self, url: Url) -> Result<T> {
+        let response = self
+            .send_request(url, |client, url| {
+                tracing::debug!("Executing GET request with url: {url}.");
+                client.get(url)
+            })
+            .await?;
+        crate::error::json("gitlab", response).await
+    }
+
+    async fn obtain_list<T: DeserializeOwned>(&self, url: Url) -> Result<Vec<T>> {
+        self.obtain_first_items(url, usize::MAX).await
+    }
+
+    /// Reads the pages until `count` items are received or the last page.
+    async fn obtain_first_items<T: DeserializeOwned>(
+        &self,
+        url: Url,
+        count: usize,
+    ) -> Result<Vec<T>> {
+        let per_page = count.clamp(1, PAGE_SIZE).to_string();
+        let mut items = Vec::new();
+        let mut page = "1".to_string();
+        while items.len() < count {
+            let mut page_url = url.clone();
+            page_url
+                .query_pairs_mut()
+                .extend_pairs([("per_page", per_page.as_str()), ("page", page.as_str())]);
+            let response = self
+                .send_request(page_url, |client, url| {
+                    tracing::debug!("Executing GET request with url: {url}.");
+                    client.get(url)
+                })
+                .await?;
+            let next_page = response
+                .headers()
+                .get(NEXT_PAGE_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .filter(|v| !v.is_empty())
+                .map(str::to_string);
+            items.extend(crate::error::json::<Vec<T>>("gitlab", response).await?);
+            match next_page {
+                Some(next_page) => page = next_page,
+                None => break,
+            }
+        }
+        items.truncate(count);
+        Ok(items)
+    }
+
+    async fn send_request<F>(
+        &self,
+        url: Url,
+        request_builder_factory: F,
+    ) -> Result<reqwest::Response>
+    where
+        F: FnOnce(&Client, Url) -> RequestBuilder,
+    {
+        let request = request_builder_factory(&self.client, url)
+            .header(TOKEN_HEADER, self.token)
+            .build()?;
+
+        let response = crate::http::execute(&self.client, "gitlab", request).await?;
+        crate::error::error_for_status("gitlab", response).await
+    }
+
+    async fn obtain_merge_request(&self, project: &str, iid: &str) -> Result<MergeRequest> {
+        let url = self.make_url(["projects", project, "merge_requests", iid])?;
+        self.obtain_single_item::<MergeRequest>(url).await
+    }
+
+    async fn complete_pull_request(
+        &self,
+        project: &str,
+        merge_request: MergeRequest,
+    ) -> Result<PullRequest> {
+        let iid = merge_request.iid.to_string();
+        let reviewers_url = self.make_url([
+            "projects",
+            project,
+            "merge_requests",
+            iid.as_str(),
+            "reviewers",
+        ])?;
+        let approvals_url = self.make_url([
+            "projects",
+            project,
+            "merge_requests",
+            iid.as_str(),
+            "approvals",
+        ])?;
+        let reviewers = self.obtain_single_item::<Vec<MergeRequestReviewer>>(reviewers_url);
+        let approvals = self.obtain_single_item::<Approvals>(approvals_url);
+        let (reviewers, approvals) = futures::future::try_join(reviewers, approvals).await?;
+        Ok(merge_request.into_pull_request(reviewers, approvals))
+    }
+
+    async fn approval_rule_members(&self, project: &str, rule_name: &str) -> Result<Vec<User>> {
+        tracing::info!("Requesting approval rule {rule_name} in project {project}.");
+        let url = self.make_url(["projects", project, "approval_rules"])?;
+        let rule = self
+            .obtain_list::<ApprovalRule>(url)
+            .await?
+            .into_iter()
+            .find(|rule| rule.name == rule_name);
+        match rule {
+            Some(rule) => Ok(rule.eligible_approvers),
+            None => {
+                tracing::warn!("Approval rule {rule_name} was not found in project {project}.");
+                Ok(vec![])
+            }
+        }
+    }
+
+    async fn group_members(&self, group: &str) -> Result<Vec<User>> {
+        tracing::info!("Requesting group {group} members.");
+        let url = self.make_url(["groups", group, "members", "all"])?;
+        self.obtain_list::<User>(url).await
+    }
+}
+
+#[async_trait]
+impl<'a> AzureTeamService for GitLabApi<'a> {
+    async fn team_members(&self, team_id: Identifier) -> Result<Vec<TeamMember>> {
+        let users = match team_id.0.split_once('#') {
+            Some((project, rule_name)) => self.approval_rule_members(project, rule_name).await?,
+            None => self.group_members(&team_id.0).await?,
+        };
+        let members = users
+            .into_iter()
+            .filter(User::is_active)
+            .map(|user| TeamMember {
+                id: user.identifier(),
+                name: user.name,
+                is_container: false,
+                mail_address: user.public_email,
+            })
+            .collect();
+        Ok(members)
+    }
+
+    async fn get_teams(&self) -> Result<Vec<Team>> {
+        tracing::info!("Requesting group {} and its subgroups.", self.group);
+        let group_url = self.make_url(["groups", self.group])?;
+        let descendants_url = self.make_url(["groups", self.group, "descendant_groups"])?;
+        let group = self.obtain_single_item::<Group>(group_url);
+        let descendants = self.obtain_list::<Group>(descendants_url);
+        let (group, descendants) = futures::future::try_join(group, descendants).await?;
+        let teams = std::iter::once(group)
+            .chain(descendants)
+            .map(|group| Team {
+                name: group.full_path,
+            })
+            .collect();
+        Ok(teams)
+    }
+}
+
+#[async_trait]
+impl<'a> AzurePullRequestService for GitLabApi<'a> {
+    async fn obtain_pull_request(
+        &self,
+        repository_id: &str,
+        pull_request_id: &str,
+    ) -> Result<PullRequest> {
+        tracing::info!("Requesting merge request {pull_request_id} in project {repository_id}.");
+        let merge_request = self
+            .obtain_merge_request(repository_id, pull_request_id)
+            .await?;
+        self.complete_pull_request(repository_id, merge_request)
+            .await
+    }
+
+    /// GitLab has no optional reviewers, so only required reviewers are assigned.
+    async fn add_reviewers_to_pull_request(
+        &self,
+        repository_id: &str,
+        request_id: &str,
+        reviewers: Vec<NewPullRequestReviewer>,
+    ) -> Result<()> {
+        let (required, optional): (Vec<_>, Vec<_>) =
+            reviewers.into_iter().partition(|r| r.is_required);
+        if !optional.is_empty() {
+            tracing::info!("GitLab does not support optional reviewers. Skipped: {optional:?}");
+        }
+        if required.is_empty() {
+            tracing::info!("No required reviewers to assign to merge request {request_id}.");
+            return Ok(());
+        }
+        let merge_request = self.obtain_merge_request(repository_id, request_id).await?;
+        let mut reviewer_ids = merge_request
+            .reviewers
+            .iter()
+            .map(|r| r.id)
+            .collect::<Vec<_>>();
+        for reviewer in required {
+            let id = reviewer.id.0.parse::<u64>()?;
+            if !reviewer_ids.contains(&id) {
+                reviewer_ids.push(id);
+            }
+        }
+        tracing::info!("Assigning reviewers {reviewer_ids:?} to merge request {request_id} in project {repository_id}.");
+        let url = self.make_url(["projects", repository_id, "merge_requests", request_id])?;
+        let body = UpdateMergeRequest { reviewer_ids };
+        self.send_request(url, |client, url| {
+            tracing::debug!("Executing PUT request with url: {url}.");
+            client.put(url).json(&body)
+        })
+        .map_ok(|_| ())
+        .await
+    }
+}
+
+#[async_trait]
+impl<'a> AzureRepositoryService for GitLabApi<'a> {
+    async fn repository_exists(&self, repository_id: &str) -> Result<bool> {
+        tracing::info!("Requesting project {repository_id}.");
+        let url = self.make_url(["projects", repository_id])?;
+        let project = self.obtain_single_item::<serde_json::Value>(url).await;
+        found(project).map(|v| v.is_some())
+    }
+}
+
+#[async_trait]
+impl<'a> AzurePullRequestsService for GitLabApi<'a> {
+    async fn obtain_pull_requests<F>(
+        &self,
+        repository_id: &str,
+        search_creteria: PullRequestSearchCriteria,
+        filter: F,
+    ) -> Result<Vec<PullRequest>>
+    where
+        F: Fn(&PullRequestReviewer) -> bool,
+        F: Send,
+    {
+        let mut url = self.make_url(["projects", repository_id, "merge_requests"])?;
+        let (key, value) = match &search_creteria {
+            PullRequestSearchCriteria::Reviewer(id) => ("reviewer_id", id.0.as_str()),
+            PullRequestSearchCriteria::Creator(id) => ("author_id", id.0.as_str()),
+        };
+        url.query_pairs_mut()
+            .extend_pairs([("state", "opened"), (key, value)]);
+        let skip_drafts = matches!(search_creteria, PullRequestSearchCriteria::Reviewer(_));
+        let merge_requests = self
+            .obtain_list::<MergeRequest>(url)
+            .await?
+            .into_iter()
+            .filter(|r| !(skip_drafts && r.is_draft()))
+            .collect::<Vec<_>>();
+        let requests: Vec<PullRequest> = futures::stream::iter(merge_requests)
+            .map(|r| self.complete_pull_request(repository_id, r))
+            .buffered(MERGE_REQUESTS_LIMIT)
+            .try_collect()
+            .await?;
+        let requests = requests
+            .into_iter()
+            .filter(|v| v.reviewers.iter().any(&filter))
+            .collect();
+        Ok(requests)
+    }
+}
+
+#[async_trait]
+impl<'a> AzurePullRequestChangesService for GitLabApi<'a> {
+    async fn obtain_completed_pull_requests(
+        &self,
+        repository_id: &str,
+        count: usize,
+    ) -> Result<Vec<PullRequest>> {
+        tracing::info!("Requesting {count} merged merge requests in project {repository_id}.");
+        let mut url = self.make_url(["projects", repository_id, "merge_requests"])?;
+        url.query_pairs_mut()
+            .extend_pairs([("state", "merged"), ("order_by", "updated_at")]);
+        let merge_requests = self.obtain_first_items::<MergeRequest>(url, count).await?;
+        // Every merge request takes two more requests, so only a few run at once.
+        futures::stream::iter(merge_requests)
+            .map(|r| self.complete_pull_request(repository_id, r))
+            .buffered(MERGE_REQUESTS_LIMIT)
+            .try_collect()
+            .await
+    }
+
+    async fn obtain_changed_files(
+        &self,
+        repository_id: &str,
+        pull_request_id: usize,
+    ) -> Result<Vec<String>> {
+        let iid = pull_request_id.to_string();
+        let url = self.make_url([
+            "projects",
+            repository_id,
+            "merge_requests",
+            iid.as_str(),
+            "diffs",
+        ])?;
+        let diffs = self.obtain_list::<Diff>(url).await?;
+        Ok(diffs.into_iter().map(|diff| diff.new_path).collect())
+    }
+}
+
+impl User {
+    fn identifier(&self) -> Identifier {
+        Identifier(self.id.to_string())
+    }
+
+    fn is_active(&self) -> bool {
+        self.state.as_deref().is_none_or(|state| state == "active")
+    }
+}
+
+impl MergeRequest {
+    fn is_draft(&self) -> bool {
+        self.detailed_merge_status.as_deref() == Some("draft_status")
+    }
+
+    fn status(&self) -> PullRequestStatus {
+        if self.detailed_merge_status.as_deref() == Some("not_open") {
+            return PullRequestStatus::NotSet;
+        }
+        match self.state {
+            MergeRequestState::Opened => PullRequestStatus::Active,
+            MergeRequestState::Closed => PullRequestStatus::Abandoned,
+            MergeRequestState::Merged => PullRequestStatus::Completed,
+            MergeRequestState::Locked => PullRequestStatus::NotSet,
+        }
+    }
+
+    fn into_pull_request(
+        self,
+        reviewers: Vec<MergeRequestReviewer>,
+        approvals: Approvals,
+    ) -> PullRequest {
+        let status = self.status();
+        let reviewers = reviewers
+            .into_iter()
+            .map(|reviewer| {
+                let approved = approvals
+                    .approved_by
+                    .iter()
+                    .any(|a| a.user.id == reviewer.user.id);
+                let vote = match reviewer.state.as_deref() {
+                    _ if approved => Vote::Approved,
+                    Some("requested_changes") => Vote::WaitingForAuthor,
+                    _ => Vote::NoVote,
+                };
+                PullRequestReviewer {
+                    id: reviewer.user.identifier(),
+                    name: reviewer.user.name,
+                    is_required: true,
+                    vote,
+                    has_declined: false,
+                    is_container: false,
+                    votes: vec![],
+                }
+            })
+            .collect();
+        PullRequest {
+            id: self.iid,
+            title: self.title,
+            url: self.web_url,
+            created_by: PullRequestAuthor {
+                id: self.author.identifier(),
+                name: self.author.name,
+            },
+            creation_date: self.created_at,
+            reviewers,
+            status,
+            repository: Some(PullRequestRepository {
+                id: self.project_id.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const TOKEN: &str = "fake_token";
+    const GROUP: &str = "mobile";
+    const PROJECT: &str = "mobile/app";
+
+    fn user(id: u64) -> serde_json::Value {
+        json!({ "id": id, "name": format!("User {id}"), "username": format!("user{id}"), "state": "active" })
+    }
+
+    fn merge_request(iid: usize, detailed_merge_status: &str) -> serde_json::Value {
+        json!({
+            "iid": iid,
+            "project_id": 42,
+            "title": format!("Merge request {iid}"),
+            "web_url": format!("https://gitlab.example.com/mobile/app/-/merge_requests/{iid}"),
+            "author": user(1),
+            "created_at": "2023-07-01T10:00:00.000Z",
+            "state": "opened",
+            "detailed_merge_status": detailed_merge_status,
+            "reviewers": [user(2), user(3)],
+        })
+    }
+
+    async fn mount_details(server: &MockServer, iid: usize) {
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/api/v4/projects/mobile%2Fapp/merge_requests/{iid}/reviewers"
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                { "user": user(2), "state": "requested_changes" },
+                { "user": user(3), "state": "reviewed" },
+            ])))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/api/v4/projects/mobile%2Fapp/merge_requests/{iid}/approvals"
+            )))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "approved_by": [{ "user": user(3) }] })),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn reviewer_merge_requests_mapped_to_pull_requests() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/projects/mobile%2Fapp/merge_requests"))
+            .and(query_param("state", "opened"))
+            .and(query_param("reviewer_id", "2"))
+            .and(header(TOKEN_HEADER, TOKEN))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                merge_request(7, "not_approved"),
+                merge_request(8, "draft_status"),
+            ])))
+            .mount(&server)
+            .await;
+        mount_details(&server, 7).await;
+
+        let base_url = Url::parse(&server.uri())?;
+        let api = GitLabApi::new(TOKEN, &base_url, GROUP);
+        let requests = api
+            .obtain_pull_requests(
+                PROJECT,
+                PullRequestSearchCriteria::Reviewer(Identifier("2".to_string())),
+                |_| true,
+            )
+            .await?;
+
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert_eq!(request.id, 7);
+        assert_eq!(request.status, PullRequestStatus::Active);
+        assert_eq!(request.created_by.id, Identifier("1".to_string()));
+        let votes = request
+            .reviewers
+            .iter()
+            .map(|r| (r.id.0.as_str(), &r.vote, r.is_required))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            votes,
+            vec![
+                ("2", &Vote::WaitingForAuthor, true),
+                ("3", &Vote::Approved, true)
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn merged_merge_requests_paged_up_to_count() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/projects/mobile%2Fapp/merge_requests"))
+            .and(query_param("state", "merged"))
+            .and(query_param("per_page", "3"))
+            .and(query_param("page", "1"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(NEXT_PAGE_HEADER, "2")
+                    .set_body_json(json!([
+                        merge_request(1, "not_open"),
+                        merge_request(2, "not_open"),
+                    ])),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/projects/mobile%2Fapp/merge_requests"))
+            .and(query_param("per_page", "3"))
+            .and(query_param("page", "2"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(NEXT_PAGE_HEADER, "3")
+                    .set_body_json(json!([
+                        merge_request(3, "not_open"),
+                        merge_request(4, "not_open"),
+                    ])),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        for iid in 1..=3 {
+            mount_details(&server, iid).await;
+        }
+
+        let base_url = Url::parse(&server.uri())?;
+        let api = GitLabApi::new(TOKEN, &base_url, GROUP);
+        let requests = api.obtain_completed_pull_requests(PROJECT, 3).await?;
+
+        let ids = requests.iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2, 3]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn approval_rule_resolved_to_team_members() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/projects/mobile%2Fapp/approval_rules"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                { "name": "Backend", "eligible_approvers": [user(4)] },
+                { "name": "Code owners", "eligible_approvers": [user(5), user(6)] },
+            ])))
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&server.uri())?;
+        let api = GitLabApi::new(TOKEN, &base_url, GROUP);
+        let members = api
+            .team_members(Identifier("mobile/app#Code owners".to_string()))
+            .await?;
+
+        let ids = members.iter().map(|m| m.id.0.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["5", "6"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn subgroups_listed_as_teams() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/groups/mobile"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "full_path": "mobile" })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/groups/mobile/descendant_groups"))
+            .and(query_param("page", "1"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(NEXT_PAGE_HEADER, "2")
+                    .set_body_json(json!([{ "full_path": "mobile/ios" }])),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/groups/mobile/descendant_groups"))
+            .and(query_param("page", "2"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(NEXT_PAGE_HEADER, "")
+                    .set_body_json(json!([{ "full_path": "mobile/android" }])),
+            )
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&format!("{}/", server.uri()))?;
+        let api = GitLabApi::new(TOKEN, &base_url, GROUP);
+        let teams = api.get_teams().await?;
+
+        let names = teams.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["mobile", "mobile/ios", "mobile/android"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_required_reviewers_assigned() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/projects/mobile%2Fapp/merge_requests/7"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(merge_request(7, "not_approved")),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/api/v4/projects/mobile%2Fapp/merge_requests/7"))
+            .and(body_json(json!({ "reviewer_ids": [2, 3, 4] })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(merge_request(7, "not_approved")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&server.uri())?;
+        let api = GitLabApi::new(TOKEN, &base_url, GROUP);
+        let reviewers = vec![
+            NewPullRequestReviewer {
+                id: Identifier("4".to_string()),
+                is_required: true,
+            },
+            NewPullRequestReviewer {
+                id: Identifier("3".to_string()),
+                is_required: true,
+            },
+            NewPullRequestReviewer {
+                id: Identifier("5".to_string()),
+                is_required: false,
+            },
+        ];
+        api.add_reviewers_to_pull_request(PROJECT, "7", reviewers)
+            .await
+    }
+}
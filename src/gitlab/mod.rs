@@ -0,0 +1,47 @@
+use self::api::GitLabApi;
+use crate::azure::{
+    AddReviewersService, AddReviewersServiceImpl, AzureConfigChecker,
+    AzureReviewerRequestsProvider, ConfigChecker, ReviewerRequestsProvider, ReviewersConfig,
+};
+use url::Url;
+
+mod api;
+
+pub fn make_pull_requests_provider<'a>(
+    token: &'a str,
+    base_url: &'a Url,
+    team_name: &'a str,
+    repositories: Vec<String>,
+) -> impl ReviewerRequestsProvider + 'a {
+    let api = GitLabApi::new(token, base_url, team_name);
+    AzureReviewerRequestsProvider::new(api, team_name, repositories)
+}
+
+pub fn make_add_reviewers_service<'a>(
+    token: &'a str,
+    base_url: &'a Url,
+    team_name: &'a str,
+    pull_request_id: String,
+    repository_id: String,
+    reviewers_config: ReviewersConfig,
+) -> impl AddReviewersService + 'a {
+    let api = GitLabApi::new(token, base_url, team_name);
+    AddReviewersServiceImpl::new(
+        api,
+        team_name,
+        pull_request_id,
+        repository_id,
+        reviewers_config,
+    )
+}
+
+pub fn make_config_checker<'a>(
+    token: &'a str,
+    base_url: &'a Url,
+    team_name: &'a str,
+    reviewers_teams: Vec<&'a str>,
+    repositories: Vec<String>,
+) -> impl ConfigChecker + 'a {
+    let api = GitLabApi::new(token, base_url, team_name);
+    AzureConfigChecker::new(api, team_name, reviewers_teams, repositories)
+}
@@ -0,0 +1,167 @@
+use crate::metrics::{endpoint, metrics};
+use crate::recording::{self, recording, Recording, RequestKey};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, Method, Request, Response, StatusCode};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Retries of a rate limited or temporarily unavailable request.
+const MAX_RETRIES: u32 = 2;
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// HTTP client shared by all APIs, so connections are reused across profiles.
+pub fn client() -> Client {
+    // Pooled connections are bound to the runtime which opened them, and every
+    // test runs in its own runtime.
+    if cfg!(test) {
+        return Client::new();
+    }
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(Client::new).clone()
+}
+
+/// Executes the request to the service, recording it in the metrics. Rate limited
+/// requests, and temporarily unavailable idempotent requests, are retried after the
+/// `Retry-After` delay.
+///
+/// Responses are saved or served from the directory of the `--record` and
+/// `--replay` options.
+pub async fn execute(
+    client: &Client,
+    service: &'static str,
+    request: Request,
+) -> color_eyre::Result<Response> {
+    match recording() {
+        None => Ok(execute_with_retries(client, service, request).await?),
+        Some(Recording::Record(dir)) => {
+            let key = RequestKey::new(&request);
+            let response = execute_with_retries(client, service, request).await?;
+            recording::record(dir, service, &key, response).await
+        }
+        Some(Recording::Replay(dir)) => recording::replay(dir, service, &RequestKey::new(&request)),
+    }
+}
+
+async fn execute_with_retries(
+    client: &Client,
+    service: &'static str,
+    request: Request,
+) -> reqwest::Result<Response> {
+    let endpoint = endpoint(request.url());
+    let method = request.method().clone();
+    let mut request = request;
+    let mut retries = 0;
+    loop {
+        let retry_request = match retries < MAX_RETRIES {
+            true => request.try_clone(),
+            false => None,
+        };
+        let response = client.execute(request).await;
+        let status = match &response {
+            Ok(response) => response.status().as_u16().to_string(),
+            Err(_) => "error".to_string(),
+        };
+        metrics()
+            .http_requests
+            .with_label_values(&[service, &endpoint, &status])
+            .inc();
+        let response = response?;
+        let Some(retry_request) =
+            retry_request.filter(|_| is_retryable(&method, response.status()))
+        else {
+            return Ok(response);
+        };
+        let delay = retry_delay(&response);
+        tracing::warn!(
+            "Request to {service} {endpoint} failed with {}, retrying in {delay:?}.",
+            response.status()
+        );
+        metrics()
+            .http_retries
+            .with_label_values(&[service, &endpoint])
+            .inc();
+        tokio::time::sleep(delay).await;
+        request = retry_request;
+        retries += 1;
+    }
+}
+
+/// Rate limited requests were not processed, while an unavailable gateway may have
+/// passed the request on, so only idempotent requests are retried then, e.g. not a
+/// posted message.
+fn is_retryable(method: &Method, status: StatusCode) -> bool {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT => {
+            matches!(*method, Method::GET | Method::PUT | Method::DELETE)
+        }
+        _ => false,
+    }
+}
+
+/// Delay of the `Retry-After` header in seconds, capped to keep runs bounded.
+fn retry_delay(response: &Response) -> Duration {
+    response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_RETRY_DELAY)
+        .min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn rate_limited_request_retried() -> color_eyre::Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = client();
+        let request = client.get(format!("{}/api/users", server.uri())).build()?;
+        let response = execute(&client, "test", request).await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let retries = metrics()
+            .http_retries
+            .with_label_values(&["test", "api/users"])
+            .get();
+        assert_eq!(retries, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unavailable_post_request_not_retried() -> color_eyre::Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "0"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = client();
+        let request = client
+            .post(format!("{}/api/messages", server.uri()))
+            .build()?;
+        let response = execute(&client, "test", request).await?;
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        Ok(())
+    }
+}
@@ -1,62 +1,631 @@
+//! Reviewporter reminds reviewers about the pull requests waiting for them.
+//!
+//! The functions of this crate run the commands of the `reviewporter` binary from
+//! a config file. The [`client`] module provides clients built in code, for tools
+//! which query pull requests or add reviewers themselves.
 use self::azure::AddReviewersService;
 use self::azure::ReviewerRequestsProvider;
+pub use azure::api::{
+    Identifier, PullRequest, PullRequestAuthor, PullRequestReviewer, PullRequestStatus, TeamMember,
+    Vote,
+};
+pub use azure::{
+    AuthorAffinity, FormattedReviewerRequests, MemberError, MessageFormat,
+    OptionalReviewersStrategy, RepoRequests, ReviewerRequests, TeamRequests,
+};
+use azure::{ConfigChecker, RebalanceReviewersService, ReviewStatsProvider, StatsPeriod};
+use chrono::{Days, NaiveDate, TimeZone, Utc};
+use cli::{ReportFormat, StatsFormat};
 use color_eyre::{Report, Result};
-use config::Config;
+use config::{Config, Profile};
+use diagnostics::Diagnostics;
+pub use error::Error;
+use futures::FutureExt;
+use metrics::metrics;
+use notifier::Notifier;
+pub use notifier::{DirectoryUser, UserDirectory};
+pub use recording::{set_recording, Recording};
+use report::ReviewReport;
+use stats::StatsReport;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::{io::Read, path::Path};
+use std::future::Future;
+use std::io::BufWriter;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+pub use summary::{MemberFailure, ProfileFailure, RunSummary, Stage};
 
 mod azure;
+mod backend;
+mod bitbucket;
 pub mod cli;
+pub mod client;
 mod config;
+mod diagnostics;
+mod email;
+mod error;
+mod gitlab;
+mod http;
+mod mattermost;
+mod metrics;
+mod notifier;
+mod recording;
+mod report;
+mod secret;
 mod slack;
+mod stats;
+mod summary;
+mod teams;
+mod template;
+mod webhook;
 
+/// User directories obtained by the profiles, so profiles sharing a directory
+/// request it only once.
+#[derive(Default)]
+struct DirectoryCache {
+    directories: HashMap<String, UserDirectory>,
+}
+
+impl DirectoryCache {
+    async fn obtain(&mut self, config: &Config, notifier: &dyn Notifier) -> Result<&UserDirectory> {
+        let key = config.directory_key();
+        if !self.directories.contains_key(&key) {
+            let directory = notifier.obtain_directory().await?;
+            match &directory {
+                UserDirectory::Listed(users) => {
+                    tracing::info!("User directory has {} users.", users.len())
+                }
+                UserDirectory::Open => tracing::info!("User directory is open."),
+            }
+            tracing::debug!("User directory: {directory:?}");
+            self.directories.insert(key.clone(), directory);
+        }
+        Ok(&self.directories[&key])
+    }
+}
+
+/// Adds reviewers to the pull request in every profile of its repository. A failed
+/// profile does not stop the others, the command fails at the end.
 pub async fn add_reviewers(
     config_path: &Path,
+    profile: Option<&str>,
+    pull_request_id: String,
+    repository_id: String,
+    seed: Option<u64>,
+) -> Result<(), Error> {
+    Ok(run_add_reviewers(config_path, profile, pull_request_id, repository_id, seed).await?)
+}
+
+async fn run_add_reviewers(
+    config_path: &Path,
+    profile: Option<&str>,
     pull_request_id: String,
     repository_id: String,
+    seed: Option<u64>,
 ) -> Result<()> {
-    let config: Config = config_path.try_into()?;
+    let profiles = load_profiles(config_path, profile)?;
+    let mut directories = DirectoryCache::default();
+    let mut handled_repositories = HashSet::new();
+    let mut errors = Vec::new();
+    for profile in &profiles {
+        let config = &profile.config;
+        if !config.has_pull_request_reviewers() {
+            tracing::info!("Profile {} has no reviewers config.", profile.name);
+            continue;
+        }
+        let result = async {
+            if !profile_contains_repository(config, &repository_id).await? {
+                tracing::info!(
+                    "Repository {repository_id} is not in profile {}.",
+                    profile.name
+                );
+                return Ok(());
+            }
+            if !handled_repositories.insert(config.repository_key(&repository_id)) {
+                tracing::info!(
+                    "Reviewers of repository {repository_id} were added by another profile."
+                );
+                return Ok(());
+            }
+            tracing::info!("Adding reviewers for profile {}.", profile.name);
 
-    let slack_api = config.slack_api();
-    let users = slack_api.obtain_users().await?;
-    tracing::info!("Slack users: {users:?}");
+            let notifier = config.notifier()?;
+            let directory = directories.obtain(config, notifier.as_ref()).await?;
 
-    let add_reviewers_service = config.add_reviewers_service(pull_request_id, repository_id);
-    add_reviewers_service
-        .add_reviewers(|name| !users.contains_key(name))
-        .await
+            let add_reviewers_service =
+                config.add_reviewers_service(pull_request_id.clone(), repository_id.clone(), seed);
+            add_reviewers_service
+                .add_reviewers(|member| {
+                    directory
+                        .available_user_id(&member.name, member.mail_address.as_deref())
+                        .is_none()
+                })
+                .await
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::error!(
+                "Adding reviewers for profile {} failed: {e:?}",
+                profile.name
+            );
+            errors.push(e);
+        }
+    }
+    match errors.len() {
+        0 if handled_repositories.is_empty() => Err(Report::msg(format!(
+            "Repository {repository_id} is not in any profile."
+        ))),
+        0 => Ok(()),
+        // Keeps the exit code of the first error, e.g. of a rejected token.
+        count => Err(errors
+            .remove(0)
+            .wrap_err(format!("Adding reviewers failed for {count} profiles."))),
+    }
 }
 
-pub async fn send_reports(repositories: Vec<String>, config_path: &Path) -> Result<()> {
-    let config: Config = config_path.try_into()?;
+/// Whether the repository is listed in the profile or found by its repository
+/// discovery. A profile with neither takes every repository. Repositories are
+/// compared by name ignoring case, so a repository given by its id is only taken
+/// by profiles without repositories.
+async fn profile_contains_repository(config: &Config, repository_id: &str) -> Result<bool> {
+    let repositories = config.repositories(&[]);
+    if !repositories.is_empty() {
+        return Ok(repositories
+            .iter()
+            .any(|r| r.eq_ignore_ascii_case(repository_id)));
+    }
+    if !config.has_repository_discovery() {
+        return Ok(true);
+    }
+    let repositories = config.discover_repositories().await?;
+    Ok(repositories
+        .iter()
+        .any(|r| r.eq_ignore_ascii_case(repository_id)))
+}
 
-    let slack_api = config.slack_api();
-    let users = slack_api.obtain_users().await?;
-    tracing::info!("Slack users: {users:?}");
+/// Replaces the unavailable required reviewers of the active pull requests in every
+/// profile with reviewers.
+pub async fn rebalance_reviewers(
+    config_path: &Path,
+    profile: Option<&str>,
+    repositories: Vec<String>,
+) -> Result<(), Error> {
+    Ok(run_rebalance_reviewers(config_path, profile, repositories).await?)
+}
 
+async fn run_rebalance_reviewers(
+    config_path: &Path,
+    profile: Option<&str>,
+    repositories: Vec<String>,
+) -> Result<()> {
+    let profiles = load_profiles(config_path, profile)?;
+    let mut directories = DirectoryCache::default();
+    for profile in &profiles {
+        let config = &profile.config;
+        if !config.has_pull_request_reviewers() {
+            tracing::info!("Profile {} has no reviewers config.", profile.name);
+            continue;
+        }
+        tracing::info!("Rebalancing reviewers for profile {}.", profile.name);
+
+        let notifier = config.notifier()?;
+        let directory = directories.obtain(config, notifier.as_ref()).await?;
+        let repositories = profile_repositories(config, &repositories).await?;
+        config
+            .rebalance_reviewers_service(repositories)?
+            .rebalance_reviewers(|member| {
+                directory.is_unavailable(&member.name, member.mail_address.as_deref())
+            })
+            .await?;
+    }
+    Ok(())
+}
+
+/// Sends the reports of every profile. Every message is attempted, members who
+/// were not sent their report and profiles which failed are listed in the summary.
+/// The run fails only if every profile failed.
+pub async fn send_reports(
+    repositories: Vec<String>,
+    config_path: &Path,
+    profile: Option<&str>,
+) -> Result<RunSummary, Error> {
+    Ok(run_send_reports(repositories, config_path, profile).await?)
+}
+
+async fn run_send_reports(
+    repositories: Vec<String>,
+    config_path: &Path,
+    profile: Option<&str>,
+) -> Result<RunSummary> {
+    let profiles = load_profiles(config_path, profile)?;
+    let mut directories = DirectoryCache::default();
+    let mut summary = RunSummary::default();
+    let mut profile_errors = Vec::new();
+    for profile in &profiles {
+        tracing::info!("Sending reports for profile {}.", profile.name);
+        let result = send_profile(profile, &repositories, &mut directories, &mut summary).await;
+        if let Err(e) = result {
+            tracing::error!("Sending reports for profile {} failed: {e:?}", profile.name);
+            summary.fail_profile(&profile.name, &e);
+            profile_errors.push(e);
+        }
+    }
+    if !profile_errors.is_empty() && profile_errors.len() == profiles.len() {
+        // Keeps the exit code of the error, e.g. of a rejected token.
+        return Err(profile_errors.remove(0));
+    }
+    match summary.is_success() {
+        true => tracing::info!("All messages were sent."),
+        false => tracing::warn!(
+            "{} members and {} profiles failed.",
+            summary.failures.len(),
+            summary.profile_failures.len()
+        ),
+    }
+    Ok(summary)
+}
+
+async fn send_profile(
+    profile: &Profile,
+    repositories: &[String],
+    directories: &mut DirectoryCache,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    let config = &profile.config;
+    let notifier = config.notifier()?;
+    let directory = directories.obtain(config, notifier.as_ref()).await?;
+    let repositories = profile_repositories(config, repositories).await?;
+    send_profile_reports(profile, notifier.as_ref(), directory, repositories, summary).await
+}
+
+async fn send_profile_reports(
+    profile: &Profile,
+    notifier: &dyn Notifier,
+    directory: &UserDirectory,
+    repositories: Vec<String>,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    let config = &profile.config;
+    let email_notifier = config.email_notifier()?;
     let pull_requests_provider = config.pull_requests_provider(repositories);
-    let send_requests = pull_requests_provider
-        .pull_requests(|name| users.contains_key(name))
-        .await?
-        .into_iter()
-        .filter_map(|r| {
-            let Some(id) = users.get(&r.reviewer_name) else {
-                return None;
-            };
-            let request = slack_api.send_message(id.clone(), r.to_string());
-            Some(request)
-        });
-    futures::future::try_join_all(send_requests).await?;
-    tracing::info!("All messages were sent.");
+    let unmatched = Mutex::new(Vec::new());
+    let team_requests = pull_requests_provider
+        .pull_requests(|member| {
+            let email = member.mail_address.as_deref();
+            let is_listed = directory.contains(&member.name, email);
+            if !is_listed {
+                metrics()
+                    .unmatched_users
+                    .with_label_values(&[&profile.name])
+                    .inc();
+                if email_notifier.is_none() {
+                    unmatched.lock().unwrap().push(member.name.clone());
+                }
+            }
+            // Pull requests of unmatched members are fetched to tell whether they
+            // missed a report.
+            directory.available_user_id(&member.name, email).is_some() || !is_listed
+        })
+        .await?;
+    let unmatched = unmatched.into_inner().unwrap();
+    for requests in &team_requests.requests {
+        if unmatched.contains(&requests.reviewer_name) && !requests.is_empty() {
+            let error = "not found in the user directory";
+            summary.fail(
+                &profile.name,
+                &requests.reviewer_name,
+                Stage::MatchUser,
+                error,
+            );
+        }
+    }
+    for failure in team_requests.failures {
+        let stage = Stage::FetchPullRequests;
+        summary.fail(&profile.name, &failure.member, stage, failure.error);
+    }
+
+    let reviewer_requests = team_requests.requests;
+    for requests in &reviewer_requests {
+        let waiting = requests
+            .waiting_for_review()
+            .iter()
+            .map(|r| r.pull_requests().len())
+            .sum::<usize>();
+        metrics()
+            .waiting_pull_requests
+            .with_label_values(&[&profile.name, &requests.reviewer_name])
+            .set(waiting as i64);
+    }
+    let mut send_requests = Vec::new();
+    for r in &reviewer_requests {
+        let email = r.reviewer_email.as_deref();
+        if let Some(id) = directory.available_user_id(&r.reviewer_name, email) {
+            let message = count_message("chat", notifier.send_message(id, r));
+            send_requests.push(message.map(move |result| (r, result)).boxed());
+            continue;
+        }
+        let Some(email_notifier) = email_notifier.as_ref() else {
+            continue;
+        };
+        let Some(email) = email else {
+            tracing::info!("{} has no mail address to send email to.", r.reviewer_name);
+            let error = "no mail address to send email to";
+            summary.fail(&profile.name, &r.reviewer_name, Stage::MatchUser, error);
+            continue;
+        };
+        let message = count_message("email", email_notifier.send_message(email, r));
+        send_requests.push(message.map(move |result| (r, result)).boxed());
+    }
+    for (requests, result) in futures::future::join_all(send_requests).await {
+        match result {
+            Ok(()) => summary.sent += 1,
+            Err(e) => {
+                tracing::error!("Sending to {} failed: {e:?}", requests.reviewer_name);
+                let stage = Stage::SendMessage;
+                summary.fail(&profile.name, &requests.reviewer_name, stage, e);
+            }
+        }
+    }
     Ok(())
 }
 
-impl TryFrom<&Path> for Config {
-    type Error = Report;
-    fn try_from(value: &Path) -> std::result::Result<Self, Report> {
-        let mut file = File::open(value)?;
-        let mut content = String::new();
-        file.read_to_string(&mut content)?;
-        toml::from_str::<Config>(&content).map_err(Report::from)
+async fn count_message(channel: &str, message: impl Future<Output = Result<()>>) -> Result<()> {
+    let result = message.await;
+    let label = if result.is_ok() { "sent" } else { "failed" };
+    metrics()
+        .messages
+        .with_label_values(&[channel, label])
+        .inc();
+    result
+}
+
+/// Sends the reports every interval and serves the metrics on the address.
+pub async fn serve(
+    config_path: &Path,
+    profile: Option<&str>,
+    repositories: Vec<String>,
+    listen: SocketAddr,
+    interval: Duration,
+) -> Result<(), Error> {
+    Ok(run_serve(config_path, profile, repositories, listen, interval).await?)
+}
+
+async fn run_serve(
+    config_path: &Path,
+    profile: Option<&str>,
+    repositories: Vec<String>,
+    listen: SocketAddr,
+    interval: Duration,
+) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    tracing::info!("Serving metrics on http://{listen}/metrics.");
+    let runs = async {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let timer = metrics().run_duration.start_timer();
+            metrics().waiting_pull_requests.reset();
+            let result = send_reports(repositories.clone(), config_path, profile).await;
+            timer.observe_duration();
+            match result {
+                Ok(summary) if summary.is_success() => {
+                    metrics().runs.with_label_values(&["success"]).inc()
+                }
+                Ok(summary) => {
+                    tracing::warn!("Sending reports partially failed: {summary}");
+                    metrics().runs.with_label_values(&["partial_failure"]).inc();
+                }
+                Err(e) => {
+                    tracing::error!("Sending reports failed: {e:?}");
+                    metrics().runs.with_label_values(&["failure"]).inc();
+                }
+            }
+        }
+    };
+    tokio::select! {
+        result = metrics::serve(listener) => result,
+        never = runs => never,
+    }
+}
+
+/// Writes the pull requests waiting for reviewers of every profile in the format,
+/// to the output file or the standard output. Nothing is sent to reviewers.
+pub async fn report(
+    config_path: &Path,
+    profile: Option<&str>,
+    repositories: Vec<String>,
+    format: ReportFormat,
+    output: Option<&Path>,
+) -> Result<(), Error> {
+    Ok(run_report(config_path, profile, repositories, format, output).await?)
+}
+
+async fn run_report(
+    config_path: &Path,
+    profile: Option<&str>,
+    repositories: Vec<String>,
+    format: ReportFormat,
+    output: Option<&Path>,
+) -> Result<()> {
+    let profiles = load_profiles(config_path, profile)?;
+    let mut profile_requests = Vec::with_capacity(profiles.len());
+    for profile in &profiles {
+        tracing::info!("Collecting pull requests for profile {}.", profile.name);
+        let config = &profile.config;
+        let repositories = profile_repositories(config, &repositories).await?;
+        let team_requests = config
+            .pull_requests_provider(repositories)
+            .pull_requests(|_| true)
+            .await?;
+        for failure in &team_requests.failures {
+            tracing::warn!(
+                "Pull requests of {} are missing in the report: {}",
+                failure.member,
+                failure.error
+            );
+        }
+        profile_requests.push((profile.name.clone(), team_requests.requests));
+    }
+    let report = ReviewReport::new(&profile_requests, Utc::now());
+    match output {
+        Some(path) => {
+            tracing::info!("Writing report to {path:?}.");
+            report.write(format, BufWriter::new(File::create(path)?))
+        }
+        None => report.write(format, std::io::stdout().lock()),
+    }
+}
+
+/// Prints review statistics of the pull requests created from `since` to `until`
+/// inclusive, for every profile.
+pub async fn stats(
+    config_path: &Path,
+    profile: Option<&str>,
+    repositories: Vec<String>,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    format: StatsFormat,
+) -> Result<(), Error> {
+    Ok(run_stats(config_path, profile, repositories, since, until, format).await?)
+}
+
+async fn run_stats(
+    config_path: &Path,
+    profile: Option<&str>,
+    repositories: Vec<String>,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    format: StatsFormat,
+) -> Result<()> {
+    let until = until.unwrap_or_else(|| Utc::now().date_naive());
+    let since = since.unwrap_or(until - Days::new(30));
+    let period = StatsPeriod {
+        since: Utc.from_utc_datetime(&since.and_time(Default::default())),
+        until: Utc.from_utc_datetime(&(until + Days::new(1)).and_time(Default::default())),
+    };
+    let profiles = load_profiles(config_path, profile)?;
+    let mut report = StatsReport::new(since, until);
+    for profile in &profiles {
+        tracing::info!("Collecting review statistics for profile {}.", profile.name);
+        let config = &profile.config;
+        let repositories = profile_repositories(config, &repositories).await?;
+        let stats = config
+            .review_stats_provider(repositories)?
+            .review_stats(period)
+            .await?;
+        report.add_profile(profile.name.clone(), stats);
+    }
+    report.write(format, std::io::stdout().lock())
+}
+
+/// Checks the config and the access to the configured services, printing the
+/// results. Fails if any error was found.
+pub async fn check_config(
+    config_path: &Path,
+    profile: Option<&str>,
+    repositories: Vec<String>,
+) -> Result<(), Error> {
+    Ok(run_check_config(config_path, profile, repositories).await?)
+}
+
+async fn run_check_config(
+    config_path: &Path,
+    profile: Option<&str>,
+    repositories: Vec<String>,
+) -> Result<()> {
+    let content = std::fs::read_to_string(config_path)?;
+    let profiles = Profile::parse_all(&content, profile)?;
+    let mut errors_count = 0;
+    for profile in profiles {
+        let diagnostics =
+            check_profile(profile.config, profile.unknown_keys, &repositories).await?;
+        println!("Profile {}:", profile.name);
+        print!("{diagnostics}");
+        errors_count += diagnostics.errors_count();
+    }
+    match errors_count {
+        0 => Ok(()),
+        count => Err(Report::msg(format!("Config check found {count} errors."))),
+    }
+}
+
+async fn check_profile(
+    config: Config,
+    unknown_keys: Vec<String>,
+    repositories: &[String],
+) -> Result<Diagnostics> {
+    let mut diagnostics = Diagnostics::default();
+    for key in unknown_keys {
+        diagnostics.error(format!("Unknown config key {key}."));
+    }
+    if let Err(e) = config.validate() {
+        diagnostics.error(e.to_string());
+        return Ok(diagnostics);
+    }
+
+    let notifier = config.notifier()?;
+    match notifier.check_access().await {
+        Ok(problems) if problems.is_empty() => diagnostics.ok("Notifier token is valid."),
+        Ok(problems) => problems.into_iter().for_each(|p| diagnostics.error(p)),
+        Err(e) => diagnostics.error(format!("Unable to check notifier token: {e}")),
+    }
+    let directory = match notifier.obtain_directory().await {
+        Ok(directory) => {
+            if let UserDirectory::Listed(users) = &directory {
+                diagnostics.ok(format!("User directory has {} users.", users.len()));
+            }
+            Some(directory)
+        }
+        Err(e) => {
+            diagnostics.error(format!("Unable to obtain user directory: {e}"));
+            None
+        }
+    };
+    if let Some(email_notifier) = config.email_notifier()? {
+        if let Err(e) = email_notifier.test_connection().await {
+            diagnostics.error(format!("Unable to connect to SMTP server: {e}"));
+        }
+    }
+
+    let repositories = match profile_repositories(&config, repositories).await {
+        Ok(repositories) => repositories,
+        Err(e) => {
+            diagnostics.error(format!("Unable to discover repositories: {e}"));
+            vec![]
+        }
+    };
+    let checker = config.config_checker(repositories);
+    diagnostics.extend(checker.check(directory.as_ref()).await);
+    Ok(diagnostics)
+}
+
+/// Repositories given on the command line, listed in the config or discovered,
+/// in that order of precedence.
+async fn profile_repositories(config: &Config, repositories: &[String]) -> Result<Vec<String>> {
+    let repositories = config.repositories(repositories);
+    if !repositories.is_empty() {
+        return Ok(repositories);
+    }
+    config.discover_repositories().await
+}
+
+fn load_profiles(config_path: &Path, profile: Option<&str>) -> Result<Vec<Profile>> {
+    let content = std::fs::read_to_string(config_path)?;
+    let profiles = Profile::parse_all(&content, profile)?;
+    for profile in &profiles {
+        for key in &profile.unknown_keys {
+            tracing::warn!(
+                "Unknown config key {key} in profile {} is ignored.",
+                profile.name
+            );
+        }
+        profile
+            .config
+            .validate()
+            .map_err(|e| e.wrap_err(format!("Invalid profile {}", profile.name)))?;
     }
+    Ok(profiles)
 }
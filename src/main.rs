@@ -1,46 +1,184 @@
 use clap::Parser;
 use color_eyre::Result;
-use reviewporter::cli::{Cli, Command};
+use reviewporter::cli::{Cli, Command, LogFormat};
+use reviewporter::Recording;
+use std::process::ExitCode;
+use std::time::Duration;
 use tracing_subscriber::filter::EnvFilter;
 use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
+
+/// Exit code of a run in which some members were not sent their reports. Fatal
+/// errors, e.g. an invalid configuration, exit with 1.
+const PARTIAL_FAILURE_EXIT_CODE: u8 = 2;
+/// Exit code of a run which failed because a service rejected the token.
+const AUTH_FAILURE_EXIT_CODE: u8 = 3;
+/// Exit code of a run which failed because a service rate limited the requests,
+/// so it may succeed when repeated later.
+const RATE_LIMITED_EXIT_CODE: u8 = 4;
+
+/// Log levels used when `RUST_LOG` is not set.
+const DEFAULT_LOG_FILTER: &str = "warn,reviewporter=info";
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     let cli = Cli::parse();
-    configure_logging()?;
+    let telemetry = configure_logging(&cli)?;
 
-    tracing::info!("Config path: {:?}", cli.config);
-    match cli.command {
+    tracing::info!("Config path: {}", cli.config.display());
+    if let Some(dir) = cli.record.clone() {
+        tracing::info!("Recording responses to {}.", dir.display());
+        reviewporter::set_recording(Recording::Record(dir))?;
+    }
+    if let Some(dir) = cli.replay.clone() {
+        tracing::info!("Replaying responses from {}.", dir.display());
+        reviewporter::set_recording(Recording::Replay(dir))?;
+    }
+    let result = run(cli).await;
+    telemetry.shutdown();
+    let exit_code = match result.as_ref().err().and_then(reviewporter::Error::find) {
+        Some(reviewporter::Error::Auth { .. }) => AUTH_FAILURE_EXIT_CODE,
+        Some(reviewporter::Error::RateLimited { .. }) => RATE_LIMITED_EXIT_CODE,
+        _ => return result,
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {e:?}");
+    }
+    Ok(ExitCode::from(exit_code))
+}
+
+async fn run(cli: Cli) -> Result<ExitCode> {
+    let profile = cli.profile.as_deref();
+    let result = match cli.command {
         Command::AddReviewers {
             repository,
             request_id,
-        } => reviewporter::add_reviewers(&cli.config, request_id, repository).await,
+            seed,
+        } => reviewporter::add_reviewers(&cli.config, profile, request_id, repository, seed).await,
+        Command::RebalanceReviewers { repositories } => {
+            reviewporter::rebalance_reviewers(&cli.config, profile, repositories).await
+        }
         Command::SendReports { repositories } => {
-            reviewporter::send_reports(repositories, &cli.config).await
+            let summary = reviewporter::send_reports(repositories, &cli.config, profile).await?;
+            print!("{summary}");
+            if !summary.is_success() {
+                return Ok(ExitCode::from(PARTIAL_FAILURE_EXIT_CODE));
+            }
+            Ok(())
+        }
+        Command::CheckConfig { repositories } => {
+            reviewporter::check_config(&cli.config, profile, repositories).await
+        }
+        Command::Report {
+            format,
+            output,
+            repositories,
+        } => {
+            reviewporter::report(
+                &cli.config,
+                profile,
+                repositories,
+                format,
+                output.as_deref(),
+            )
+            .await
+        }
+        Command::Serve {
+            listen,
+            interval,
+            repositories,
+        } => {
+            let interval = Duration::from_secs(interval * 60);
+            reviewporter::serve(&cli.config, profile, repositories, listen, interval).await
+        }
+        Command::Stats {
+            since,
+            until,
+            format,
+            repositories,
+        } => reviewporter::stats(&cli.config, profile, repositories, since, until, format).await,
+    };
+    result?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Exporter of the traces, which must be flushed before exit.
+struct Telemetry {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Telemetry {
+    fn shutdown(self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to export traces: {e}");
+            }
         }
     }
 }
 
-fn configure_logging() -> Result<()> {
+fn configure_logging(cli: &Cli) -> Result<Telemetry> {
     if std::env::var("RUST_LIB_BACKTRACE").is_err() {
         std::env::set_var("RUST_LIB_BACKTRACE", "1")
     }
     color_eyre::install()?;
 
-    if std::env::var("RUST_LOG").is_err() {
-        std::env::set_var("RUST_LOG", "debug")
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let output = match cli.log_format {
+        LogFormat::Text => {
+            let format = fmt::format()
+                .with_source_location(false)
+                .with_file(false)
+                .with_target(false)
+                .with_timer(fmt::time::SystemTime)
+                .compact();
+            fmt::layer().event_format(format).boxed()
+        }
+        LogFormat::Json => fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    };
+    let registry = tracing_subscriber::registry().with(filter).with(output);
+
+    #[cfg(feature = "otlp")]
+    {
+        let provider = cli
+            .otlp_endpoint
+            .as_deref()
+            .map(otlp_tracer_provider)
+            .transpose()?;
+        let layer = provider.as_ref().map(|provider| {
+            use opentelemetry::trace::TracerProvider;
+            tracing_opentelemetry::layer().with_tracer(provider.tracer("reviewporter"))
+        });
+        registry.with(layer).init();
+        Ok(Telemetry { provider })
+    }
+    #[cfg(not(feature = "otlp"))]
+    {
+        registry.init();
+        Ok(Telemetry {})
     }
-    let format = fmt::format()
-        .with_source_location(false)
-        .with_file(false)
-        .with_target(false)
-        .with_timer(fmt::time::SystemTime)
-        .compact();
-
-    fmt::fmt()
-        .event_format(format)
-        .with_env_filter(EnvFilter::from_default_env())
-        .init();
-
-    Ok(())
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_tracer_provider(endpoint: &str) -> Result<opentelemetry_sdk::trace::SdkTracerProvider> {
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+    let resource = opentelemetry_sdk::Resource::builder()
+        .with_service_name("reviewporter")
+        .build();
+    Ok(opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build())
 }
@@ -0,0 +1,245 @@
+use crate::azure::{MessageFormat, ReviewerRequests};
+use crate::notifier::{DirectoryUser, Notifier, UserDirectory};
+use crate::template::MessageTemplate;
+use async_trait::async_trait;
+use color_eyre::Result;
+use reqwest::{header::AUTHORIZATION, Client, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::sync::OnceCell;
+use url::Url;
+
+const PAGE_SIZE: usize = 200;
+
+/// Mattermost notifier sending direct messages through the REST API v4.
+///
+/// Users are resolved by email, so the token must belong to a bot or a user allowed
+/// to see email addresses of the team members.
+pub struct MattermostApi<'a> {
+    token: &'a str,
+    team_id: &'a str,
+    base_url: &'a Url,
+    client: Client,
+    sender_id: OnceCell<String>,
+    template: &'a MessageTemplate,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct NotifyProps {
+    #[serde(default)]
+    auto_responder_active: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct User {
+    id: String,
+    username: String,
+    #[serde(default)]
+    email: String,
+    #[serde(default)]
+    first_name: String,
+    #[serde(default)]
+    last_name: String,
+    #[serde(default)]
+    notify_props: NotifyProps,
+}
+
+#[derive(Deserialize)]
+struct Channel {
+    id: String,
+}
+
+#[derive(Serialize)]
+struct NewPost<'a> {
+    channel_id: &'a str,
+    message: String,
+}
+
+#[async_trait]
+impl<'a> Notifier for MattermostApi<'a> {
+    async fn obtain_directory(&self) -> Result<UserDirectory> {
+        let users = self
+            .obtain_team_users()
+            .await?
+            .into_iter()
+            .map(|user| DirectoryUser {
+                is_available: user.notify_props.auto_responder_active != "true",
+                name: user.display_name(),
+                email: Some(user.email).filter(|v| !v.is_empty()),
+                id: user.id,
+            })
+            .collect();
+        Ok(UserDirectory::Listed(users))
+    }
+
+    async fn send_message(&self, user_id: &str, requests: &ReviewerRequests) -> Result<()> {
+        tracing::info!("Sending message to {user_id}.");
+        let sender_id = self
+            .sender_id
+            .get_or_try_init(|| self.obtain_sender_id())
+            .await?;
+
+        let url = self.make_url(["channels", "direct"])?;
+        let members = [sender_id.as_str(), user_id];
+        let channel = self
+            .send_request::<Channel>(self.client.post(url).json(&members))
+            .await?;
+
+        let url = self.make_url(["posts"])?;
+        let post = NewPost {
+            channel_id: &channel.id,
+            message: self.template.render(requests, MessageFormat::Markdown)?,
+        };
+        self.send_request::<serde_json::Value>(self.client.post(url).json(&post))
+            .await?;
+        tracing::info!("Message successfully sent to {user_id}.");
+        Ok(())
+    }
+}
+
+impl<'a> MattermostApi<'a> {
+    pub fn new(token: &'a str, team_id: &'a str, base_url: &'a Url) -> Self {
+        Self {
+            token,
+            team_id,
+            base_url,
+            client: crate::http::client(),
+            sender_id: OnceCell::new(),
+            template: MessageTemplate::built_in(),
+        }
+    }
+
+    pub fn with_template(mut self, template: &'a MessageTemplate) -> Self {
+        self.template = template;
+        self
+    }
+
+    fn make_url<'s>(&self, segments: impl IntoIterator<Item = &'s str>) -> Result<Url> {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| color_eyre::Report::msg("Mattermost base URL can not be a base."))?
+            .pop_if_empty()
+            .extend(["api", "v4"])
+            .extend(segments);
+        Ok(url)
+    }
+
+    async fn obtain_sender_id(&self) -> Result<String> {
+        let url = self.make_url(["users", "me"])?;
+        self.send_request::<User>(self.client.get(url))
+            .await
+            .map(|user| user.id)
+    }
+
+    async fn obtain_team_users(&self) -> Result<Vec<User>> {
+        tracing::info!("Requesting users of team {}.", self.team_id);
+        let mut users = Vec::new();
+        for page in 0.. {
+            let mut url = self.make_url(["users"])?;
+            url.query_pairs_mut().extend_pairs([
+                ("in_team", self.team_id),
+                ("active", "true"),
+                ("per_page", PAGE_SIZE.to_string().as_str()),
+                ("page", page.to_string().as_str()),
+            ]);
+            tracing::debug!("Executing GET request with url: {url}");
+            let page = self.send_request::<Vec<User>>(self.client.get(url)).await?;
+            let is_last_page = page.len() < PAGE_SIZE;
+            users.extend(page);
+            if is_last_page {
+                break;
+            }
+        }
+        Ok(users)
+    }
+
+    async fn send_request<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let request = request
+            .header(AUTHORIZATION, format!("Bearer {}", self.token))
+            .build()?;
+        let response = crate::http::execute(&self.client, "mattermost", request).await?;
+        let response = crate::error::error_for_status("mattermost", response).await?;
+        crate::error::json("mattermost", response).await
+    }
+}
+
+impl User {
+    fn display_name(&self) -> String {
+        let name = format!("{} {}", self.first_name, self.last_name);
+        match name.trim() {
+            "" => self.username.clone(),
+            name => name.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, body_partial_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn users_resolved_by_email() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/users"))
+            .and(query_param("in_team", "team"))
+            .and(query_param("page", "0"))
+            .and(header("authorization", "Bearer token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                { "id": "1", "username": "alice", "email": "alice@example.com", "first_name": "Alice", "last_name": "Smith" },
+                {
+                    "id": "2",
+                    "username": "bob",
+                    "email": "bob@example.com",
+                    "notify_props": { "auto_responder_active": "true" },
+                },
+            ])))
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&server.uri())?;
+        let api = MattermostApi::new("token", "team", &base_url);
+        let directory = api.obtain_directory().await?;
+
+        let id = directory.available_user_id("Alice S.", Some("Alice@example.com"));
+        assert_eq!(id, Some("1"));
+        assert_eq!(directory.available_user_id("bob", None), None);
+        assert!(directory.contains("Bob", Some("bob@example.com")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn direct_message_posted() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/users/me"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "id": "bot", "username": "bot" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v4/channels/direct"))
+            .and(body_json(json!(["bot", "1"])))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "id": "channel" })))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v4/posts"))
+            .and(body_partial_json(json!({ "channel_id": "channel" })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "id": "post" })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&server.uri())?;
+        let api = MattermostApi::new("token", "team", &base_url);
+        let requests = ReviewerRequests::empty("Alice".to_string());
+        api.send_message("1", &requests).await?;
+        api.send_message("1", &requests).await
+    }
+}
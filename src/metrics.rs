@@ -0,0 +1,166 @@
+//! Prometheus metrics, exposed on `/metrics` by the `serve` command.
+//!
+//! Metrics are recorded by every command, but only the service exposes them.
+use axum::{http::header::CONTENT_TYPE, routing::get, Router};
+use color_eyre::Result;
+use prometheus::{
+    core::Collector, Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGaugeVec,
+    Opts, Registry, TextEncoder,
+};
+use std::sync::OnceLock;
+use tokio::net::TcpListener;
+use url::Url;
+
+pub struct Metrics {
+    registry: Registry,
+    /// Requests to the services by `service`, `endpoint` and `status`.
+    pub http_requests: IntCounterVec,
+    /// Retried requests by `service` and `endpoint`.
+    pub http_retries: IntCounterVec,
+    /// Messages by `channel` (`chat` or `email`) and `result` (`sent` or `failed`).
+    pub messages: IntCounterVec,
+    pub reviewers_added: IntCounter,
+    /// Team members without a user in the user directory, by `profile`.
+    pub unmatched_users: IntCounterVec,
+    /// Runs of the `serve` command by `result`: `success`, `partial_failure` when some
+    /// members or profiles failed, or `failure`.
+    pub runs: IntCounterVec,
+    pub run_duration: Histogram,
+    /// Pull requests waiting for the review by `profile` and `reviewer`.
+    pub waiting_pull_requests: IntGaugeVec,
+}
+
+/// Metrics shared by the whole process.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics::new().expect("Failed to register metrics"))
+}
+
+impl Metrics {
+    fn new() -> Result<Self> {
+        let registry = Registry::new_custom(Some("reviewporter".to_string()), None)?;
+        let metrics = Self {
+            http_requests: IntCounterVec::new(
+                Opts::new("http_requests_total", "HTTP requests to the services."),
+                &["service", "endpoint", "status"],
+            )?,
+            http_retries: IntCounterVec::new(
+                Opts::new(
+                    "http_retries_total",
+                    "Retried HTTP requests to the services.",
+                ),
+                &["service", "endpoint"],
+            )?,
+            messages: IntCounterVec::new(
+                Opts::new("messages_total", "Messages sent to reviewers."),
+                &["channel", "result"],
+            )?,
+            reviewers_added: IntCounter::new(
+                "reviewers_added_total",
+                "Reviewers added to pull requests.",
+            )?,
+            unmatched_users: IntCounterVec::new(
+                Opts::new(
+                    "unmatched_users_total",
+                    "Team members not found in the user directory.",
+                ),
+                &["profile"],
+            )?,
+            runs: IntCounterVec::new(Opts::new("runs_total", "Runs of the reports."), &["result"])?,
+            run_duration: Histogram::with_opts(
+                HistogramOpts::new("run_duration_seconds", "Duration of the report runs.")
+                    .buckets(vec![1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0]),
+            )?,
+            waiting_pull_requests: IntGaugeVec::new(
+                Opts::new(
+                    "waiting_pull_requests",
+                    "Open pull requests waiting for the review of the reviewer.",
+                ),
+                &["profile", "reviewer"],
+            )?,
+            registry,
+        };
+        let collectors: [Box<dyn Collector>; 8] = [
+            Box::new(metrics.http_requests.clone()),
+            Box::new(metrics.http_retries.clone()),
+            Box::new(metrics.messages.clone()),
+            Box::new(metrics.reviewers_added.clone()),
+            Box::new(metrics.unmatched_users.clone()),
+            Box::new(metrics.runs.clone()),
+            Box::new(metrics.run_duration.clone()),
+            Box::new(metrics.waiting_pull_requests.clone()),
+        ];
+        for collector in collectors {
+            metrics.registry.register(collector)?;
+        }
+        Ok(metrics)
+    }
+
+    /// Metrics in the Prometheus text format.
+    pub fn encode(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+/// Serves the metrics on `/metrics` until the server fails.
+pub async fn serve(listener: TcpListener) -> Result<()> {
+    let router = Router::new().route("/metrics", get(metrics_handler));
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+async fn metrics_handler() -> ([(axum::http::HeaderName, &'static str); 1], String) {
+    let body = metrics().encode().unwrap_or_else(|e| {
+        tracing::error!("Failed to encode metrics: {e}");
+        String::new()
+    });
+    ([(CONTENT_TYPE, prometheus::TEXT_FORMAT)], body)
+}
+
+/// Path of the URL with identifiers replaced by `{id}`, so endpoints have a
+/// bounded number of label values.
+pub fn endpoint(url: &Url) -> String {
+    let segments = url.path_segments().into_iter().flatten();
+    segments
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| match is_identifier(segment) {
+            true => "{id}",
+            false => segment,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn is_identifier(segment: &str) -> bool {
+    let is_uuid = segment.len() == 36 && segment.chars().all(|c| c.is_ascii_hexdigit() || c == '-');
+    is_uuid || segment.parse::<u64>().is_ok() || segment.contains([':', '@', '(', '%'])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn identifiers_removed_from_endpoint() -> Result<()> {
+        let url = Url::parse(
+            "https://dev.azure.com/org/project/_apis/git/repositories/repo/pullrequests/7/threads?api-version=6.0",
+        )?;
+        assert_eq!(
+            endpoint(&url),
+            "org/project/_apis/git/repositories/repo/pullrequests/{id}/threads"
+        );
+        let url = Url::parse("https://graph.microsoft.com/v1.0/chats/19:chat/messages")?;
+        assert_eq!(endpoint(&url), "v1.0/chats/{id}/messages");
+        Ok(())
+    }
+
+    #[test]
+    fn metrics_encoded_in_text_format() -> Result<()> {
+        metrics().reviewers_added.inc();
+        let text = metrics().encode()?;
+        assert!(text.contains("# TYPE reviewporter_reviewers_added_total counter"));
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use crate::azure::ReviewerRequests;
+use async_trait::async_trait;
+use color_eyre::Result;
+
+/// User of a chat directory who can receive messages.
+#[derive(Debug, Clone)]
+pub struct DirectoryUser {
+    pub id: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub is_available: bool,
+}
+
+/// Users a notifier can deliver messages to.
+#[derive(Debug)]
+pub enum UserDirectory {
+    /// Users listed by the chat service.
+    Listed(Vec<DirectoryUser>),
+    /// The notifier has no directory and accepts any user, addressed by name.
+    Open,
+}
+
+/// Delivers reviewer requests to users of a chat service.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Looks up users of the directory together with their availability.
+    async fn obtain_directory(&self) -> Result<UserDirectory>;
+
+    async fn send_message(&self, user_id: &str, requests: &ReviewerRequests) -> Result<()>;
+
+    /// Verifies the credentials and returns problems which would prevent sending
+    /// messages, like missing token scopes.
+    async fn check_access(&self) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+}
+
+impl UserDirectory {
+    /// Finds a user by the mail address or, if there is no such address in the
+    /// directory, by the name.
+    pub fn find(&self, name: &str, email: Option<&str>) -> Option<&DirectoryUser> {
+        let Self::Listed(users) = self else {
+            return None;
+        };
+        let by_email = email.and_then(|email| {
+            users.iter().find(|user| {
+                user.email
+                    .as_deref()
+                    .is_some_and(|v| v.eq_ignore_ascii_case(email))
+            })
+        });
+        by_email.or_else(|| users.iter().find(|user| user.name == name))
+    }
+
+    pub fn contains(&self, name: &str, email: Option<&str>) -> bool {
+        matches!(self, Self::Open) || self.find(name, email).is_some()
+    }
+
+    /// Whether the user is known to be unavailable. Users missing from the directory
+    /// are not.
+    pub fn is_unavailable(&self, name: &str, email: Option<&str>) -> bool {
+        self.find(name, email)
+            .is_some_and(|user| !user.is_available)
+    }
+
+    /// Returns the id to send messages to if the user is known and available.
+    pub fn available_user_id<'a>(&'a self, name: &'a str, email: Option<&str>) -> Option<&'a str> {
+        match self {
+            Self::Open => Some(name),
+            Self::Listed(_) => self
+                .find(name, email)
+                .filter(|user| user.is_available)
+                .map(|user| user.id.as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn user(id: &str, name: &str, email: Option<&str>, is_available: bool) -> DirectoryUser {
+        DirectoryUser {
+            id: id.to_string(),
+            name: name.to_string(),
+            email: email.map(str::to_string),
+            is_available,
+        }
+    }
+
+    #[test]
+    fn users_matched_by_email_before_name() {
+        let directory = UserDirectory::Listed(vec![
+            user("1", "Alice", Some("alice@example.com"), true),
+            user("2", "Alice Smith", Some("ALICE.SMITH@example.com"), true),
+            user("3", "Bob", None, false),
+        ]);
+
+        let id = directory.available_user_id("Alice", Some("alice.smith@example.com"));
+        assert_eq!(id, Some("2"));
+        let id = directory.available_user_id("Alice", Some("unknown@example.com"));
+        assert_eq!(id, Some("1"));
+        assert_eq!(directory.available_user_id("Bob", None), None);
+        assert!(directory.contains("Bob", None));
+        assert!(!directory.contains("Carol", None));
+        assert!(directory.is_unavailable("Bob", None));
+        assert!(!directory.is_unavailable("Carol", None));
+    }
+
+    #[test]
+    fn open_directory_accepts_everyone() {
+        let directory = UserDirectory::Open;
+        assert_eq!(directory.available_user_id("Carol", None), Some("Carol"));
+        assert!(directory.contains("Carol", None));
+        assert!(!directory.is_unavailable("Carol", None));
+    }
+}
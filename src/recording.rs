@@ -0,0 +1,331 @@
+//! Recording of the service responses and their replay.
+//!
+//! With `--record <dir>` every response is saved to a JSON file in the directory,
+//! with tokens and mail addresses scrubbed. With `--replay <dir>` the saved
+//! responses are served instead of sending the requests, so a failing run can be
+//! reproduced without access to the services. Requests are matched by the service,
+//! the method and the URL without the host.
+use crate::metrics::endpoint;
+use crate::Error;
+use color_eyre::{Report, Result};
+use reqwest::{Request, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use url::{Position, Url};
+
+/// Replacement of the scrubbed secrets.
+const SCRUBBED: &str = "***";
+/// Domain of the scrubbed mail addresses, which are not scrubbed again.
+const SCRUBBED_EMAIL_DOMAIN: &str = "example.com";
+/// Keys of the secret values in the responses and queries.
+const SECRET_KEYS: [&str; 3] = ["token", "password", "secret"];
+/// Response headers which are not saved, because they are secret or describe the
+/// original encoding of the body.
+const SKIPPED_HEADERS: [&str; 6] = [
+    "authorization",
+    "proxy-authorization",
+    "set-cookie",
+    "content-length",
+    "transfer-encoding",
+    "connection",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recording {
+    /// Saves the responses to the directory.
+    Record(PathBuf),
+    /// Serves the responses saved in the directory.
+    Replay(PathBuf),
+}
+
+static RECORDING: OnceLock<Recording> = OnceLock::new();
+
+/// Records or replays the responses of all requests of the process.
+pub fn set_recording(recording: Recording) -> Result<(), Error> {
+    RECORDING
+        .set(recording)
+        .map_err(|_| Error::Config("Recording is already configured.".to_string()))
+}
+
+pub(crate) fn recording() -> Option<&'static Recording> {
+    RECORDING.get()
+}
+
+/// Response saved with the request it answered.
+#[derive(Serialize, Deserialize, Debug)]
+struct Exchange {
+    method: String,
+    url: String,
+    status: u16,
+    headers: BTreeMap<String, String>,
+    body: Body,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Body {
+    Json(Value),
+    Text(String),
+}
+
+/// Saves the scrubbed response to the directory and returns the response, whose
+/// body was read to save it.
+pub(crate) async fn record(
+    dir: &Path,
+    service: &str,
+    request: &RequestKey,
+    response: Response,
+) -> Result<Response> {
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = response.bytes().await?;
+
+    let saved_headers = headers
+        .iter()
+        .filter(|(name, _)| !SKIPPED_HEADERS.contains(&name.as_str()))
+        .filter_map(|(name, value)| {
+            let value = scrub_emails(value.to_str().ok()?);
+            Some((name.to_string(), value))
+        })
+        .collect();
+    let text = String::from_utf8_lossy(&bytes);
+    let body = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(mut value) => {
+            scrub_json(&mut value);
+            Body::Json(value)
+        }
+        Err(_) => Body::Text(scrub_emails(&text)),
+    };
+    let exchange = Exchange {
+        method: request.method.clone(),
+        url: request.url.clone(),
+        status: status.as_u16(),
+        headers: saved_headers,
+        body,
+    };
+    let path = request.path(dir, service);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(&exchange)?)?;
+    tracing::debug!(
+        "Response of {} {} saved to {}.",
+        request.method,
+        request.url,
+        path.display()
+    );
+
+    let mut response = http::Response::builder().status(status.as_u16());
+    for (name, value) in &headers {
+        response = response.header(name, value);
+    }
+    Ok(Response::from(response.body(bytes)?))
+}
+
+/// Serves the response saved in the directory.
+pub(crate) fn replay(dir: &Path, service: &str, request: &RequestKey) -> Result<Response> {
+    let path = request.path(dir, service);
+    let content = std::fs::read_to_string(&path).map_err(|e| {
+        Report::msg(format!(
+            "No recorded {service} response for {} {} in {}: {e}",
+            request.method,
+            request.url,
+            dir.display()
+        ))
+    })?;
+    let exchange = serde_json::from_str::<Exchange>(&content)?;
+    tracing::debug!(
+        "Replaying {} {} from {}.",
+        request.method,
+        request.url,
+        path.display()
+    );
+
+    let mut response = http::Response::builder().status(exchange.status);
+    for (name, value) in &exchange.headers {
+        response = response.header(name, value);
+    }
+    let body = match exchange.body {
+        Body::Json(value) => serde_json::to_vec(&value)?,
+        Body::Text(text) => text.into_bytes(),
+    };
+    Ok(Response::from(response.body(body)?))
+}
+
+/// Scrubbed method and URL of the request, which identify the saved response.
+#[derive(Debug)]
+pub(crate) struct RequestKey {
+    method: String,
+    url: String,
+}
+
+impl RequestKey {
+    pub(crate) fn new(request: &Request) -> Self {
+        Self {
+            method: request.method().to_string(),
+            url: scrub_url(request.url()),
+        }
+    }
+
+    /// File of the response, named after the endpoint for readability.
+    fn path(&self, dir: &Path, service: &str) -> PathBuf {
+        let hash = hex::encode(Sha256::digest(format!("{} {}", self.method, self.url)));
+        let endpoint = Url::parse("http://localhost")
+            .and_then(|base| base.join(&self.url))
+            .map(|url| endpoint(&url).replace('/', "_"))
+            .unwrap_or_default();
+        let name = format!("{}-{endpoint}-{}.json", self.method, &hash[..12]);
+        dir.join(service).join(name)
+    }
+}
+
+/// Path and query of the URL with secrets and mail addresses scrubbed.
+fn scrub_url(url: &Url) -> String {
+    let mut url = url.clone();
+    let pairs = url
+        .query_pairs()
+        .map(|(key, value)| match is_secret_key(&key) {
+            true => (key.into_owned(), SCRUBBED.to_string()),
+            false => (key.into_owned(), scrub_emails(&value)),
+        })
+        .collect::<Vec<_>>();
+    if !pairs.is_empty() {
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    scrub_emails(&url[Position::BeforePath..])
+}
+
+fn scrub_json(value: &mut Value) {
+    match value {
+        Value::String(text) => *text = scrub_emails(text),
+        Value::Array(values) => values.iter_mut().for_each(scrub_json),
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match is_secret_key(key) && value.is_string() {
+                    true => *value = Value::String(SCRUBBED.to_string()),
+                    false => scrub_json(value),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SECRET_KEYS.iter().any(|secret| key.contains(secret))
+}
+
+/// Replaces the mail addresses with addresses derived from their hash, so the same
+/// user has the same address in all responses.
+fn scrub_emails(text: &str) -> String {
+    let is_local = |c: char| c.is_ascii_alphanumeric() || "._%+-".contains(c);
+    let is_domain = |c: char| c.is_ascii_alphanumeric() || ".-".contains(c);
+    let mut scrubbed = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find('@') {
+        let start = rest[..at]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !is_local(*c))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let domain_end = rest[at + 1..]
+            .find(|c| !is_domain(c))
+            .map_or(rest.len(), |i| at + 1 + i);
+        let domain = rest[at + 1..domain_end].trim_end_matches('.');
+        let end = at + 1 + domain.len();
+        let email = &rest[start..end];
+        if start == at || !domain.contains('.') || domain == SCRUBBED_EMAIL_DOMAIN {
+            scrubbed.push_str(&rest[..end]);
+        } else {
+            let hash = hex::encode(Sha256::digest(email.to_lowercase()));
+            scrubbed.push_str(&rest[..start]);
+            scrubbed.push_str(&format!("user-{}@{SCRUBBED_EMAIL_DOMAIN}", &hash[..8]));
+        }
+        rest = &rest[end..];
+    }
+    scrubbed.push_str(rest);
+    scrubbed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn emails_scrubbed_consistently() {
+        let text = "Alice <alice@contoso.com>, ALICE@contoso.com and bob@contoso.com.";
+        let scrubbed = scrub_emails(text);
+
+        assert!(!scrubbed.contains("contoso"), "{scrubbed}");
+        assert_eq!(scrub_emails(&scrubbed), scrubbed);
+        let addresses = scrubbed
+            .split([' ', '<', '>', ','])
+            .filter(|v| v.contains('@'))
+            .map(|v| v.trim_end_matches('.'))
+            .collect::<Vec<_>>();
+        assert_eq!(addresses.len(), 3);
+        assert_eq!(addresses[0], addresses[1]);
+        assert_ne!(addresses[0], addresses[2]);
+        assert_eq!(scrub_emails("@here and a@b"), "@here and a@b");
+    }
+
+    #[tokio::test]
+    async fn recorded_response_replayed_without_secrets() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [{ "uniqueName": "alice@contoso.com", "accessToken": "pat-0123" }],
+            })))
+            .mount(&server)
+            .await;
+        let dir = tempfile::tempdir()?;
+        let client = reqwest::Client::new();
+        let url = format!(
+            "{}/org/_apis/teams?user=alice@contoso.com&token=pat-0123",
+            server.uri()
+        );
+        let request = client.get(&url).build()?;
+        let key = RequestKey::new(&request);
+
+        let response = client.execute(request).await?;
+        let recorded = record(dir.path(), "azure", &key, response).await?;
+        assert_eq!(
+            recorded.json::<Value>().await?["value"][0]["accessToken"],
+            "pat-0123"
+        );
+
+        let saved = std::fs::read_dir(dir.path().join("azure"))?
+            .map(|entry| std::fs::read_to_string(entry?.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        assert_eq!(saved.len(), 1);
+        assert!(
+            !saved[0].contains("contoso") && !saved[0].contains("pat-0123"),
+            "{}",
+            saved[0]
+        );
+
+        let request = client
+            .get(url.replace(&server.uri(), "https://dev.azure.com"))
+            .build()?;
+        let replayed = replay(dir.path(), "azure", &RequestKey::new(&request))?;
+        let body = replayed.json::<Value>().await?;
+        assert_eq!(body["value"][0]["accessToken"], SCRUBBED);
+        assert!(body["value"][0]["uniqueName"]
+            .as_str()
+            .is_some_and(|v| v.ends_with("@example.com")));
+
+        let request = client
+            .get("https://dev.azure.com/org/_apis/projects")
+            .build()?;
+        assert!(replay(dir.path(), "azure", &RequestKey::new(&request)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,446 @@
+//! Serializable model of reviewer requests.
+//!
+//! The model is the documented payload of the webhook notifier:
+//!
+//! ```json
+//! {
+//!   "reviewer": { "name": "Alice", "email": "alice@example.com" },
+//!   "waiting_for_review": [
+//!     {
+//!       "repository": "app",
+//!       "pull_requests": [
+//!         {
+//!           "id": 42,
+//!           "title": "Add login screen",
+//!           "url": "https://dev.azure.com/org/project/_git/app/pullrequest/42",
+//!           "author": "Bob",
+//!           "created_at": "2023-07-01T10:00:00Z",
+//!           "age_minutes": 1440,
+//!           "reviewers": [
+//!             { "name": "Alice", "is_required": true, "has_declined": false, "vote": "no_vote" }
+//!           ]
+//!         }
+//!       ]
+//!     }
+//!   ],
+//!   "waiting_for_author": []
+//! }
+//! ```
+//!
+//! `waiting_for_review` holds pull requests waiting for the reviewer's review and
+//! `waiting_for_author` holds the reviewer's own pull requests where reviewers wait
+//! for the author. `vote` is one of `approved`, `approved_with_suggestions`, `no_vote`,
+//! `waiting_for_author`, `rejected` or `unknown`.
+//!
+//! The `report` command wraps reviewer reports of every profile into a document:
+//!
+//! ```json
+//! {
+//!   "schema_version": 1,
+//!   "generated_at": "2023-07-02T10:00:00Z",
+//!   "profiles": [{ "name": "default", "reviewers": [] }]
+//! }
+//! ```
+//!
+//! Fields are only added to the schema, any other change increments `schema_version`.
+use crate::azure::api::{PullRequest, PullRequestReviewer, Vote};
+use crate::azure::{RepoRequests, ReviewerRequests};
+use crate::cli::ReportFormat;
+use crate::template::format_age;
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use serde::Serialize;
+use std::io::Write;
+use url::Url;
+
+const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Debug)]
+pub struct ReviewReport<'a> {
+    pub schema_version: u32,
+    pub generated_at: DateTime<Utc>,
+    pub profiles: Vec<ProfileReport<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProfileReport<'a> {
+    pub name: &'a str,
+    pub reviewers: Vec<ReviewerReport<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReviewerReport<'a> {
+    pub reviewer: ReportReviewer<'a>,
+    pub waiting_for_review: Vec<RepositoryReport<'a>>,
+    pub waiting_for_author: Vec<RepositoryReport<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReportReviewer<'a> {
+    pub name: &'a str,
+    pub email: Option<&'a str>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RepositoryReport<'a> {
+    pub repository: &'a str,
+    pub pull_requests: Vec<PullRequestReport<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PullRequestReport<'a> {
+    pub id: usize,
+    pub title: &'a str,
+    pub url: &'a Url,
+    pub author: &'a str,
+    pub created_at: DateTime<Utc>,
+    pub age_minutes: i64,
+    pub reviewers: Vec<ReviewerVoteReport<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReviewerVoteReport<'a> {
+    pub name: &'a str,
+    pub is_required: bool,
+    pub has_declined: bool,
+    pub vote: VoteReport,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteReport {
+    Approved,
+    ApprovedWithSuggestions,
+    NoVote,
+    WaitingForAuthor,
+    Rejected,
+    Unknown,
+}
+
+/// Row of the CSV report, one per pull request of a reviewer.
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    profile: &'a str,
+    reviewer: &'a str,
+    reviewer_email: Option<&'a str>,
+    queue: &'static str,
+    repository: &'a str,
+    pull_request_id: usize,
+    title: &'a str,
+    url: &'a Url,
+    author: &'a str,
+    created_at: DateTime<Utc>,
+    age_minutes: i64,
+    waiting_reviewers: String,
+}
+
+impl<'a> ReviewReport<'a> {
+    pub fn new(profiles: &'a [(String, Vec<ReviewerRequests>)], now: DateTime<Utc>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            generated_at: now,
+            profiles: profiles
+                .iter()
+                .map(|(name, requests)| ProfileReport {
+                    name,
+                    reviewers: requests
+                        .iter()
+                        .map(|r| ReviewerReport::new(r, now))
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn write(&self, format: ReportFormat, writer: impl Write) -> Result<()> {
+        match format {
+            ReportFormat::Json => self.write_json(writer),
+            ReportFormat::Csv => self.write_csv(writer),
+            ReportFormat::Markdown => self.write_markdown(writer),
+        }
+    }
+
+    fn write_json(&self, mut writer: impl Write) -> Result<()> {
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    fn write_csv(&self, writer: impl Write) -> Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for profile in &self.profiles {
+            for reviewer in &profile.reviewers {
+                let queues = [
+                    ("waiting_for_review", &reviewer.waiting_for_review),
+                    ("waiting_for_author", &reviewer.waiting_for_author),
+                ];
+                for (queue, repositories) in queues {
+                    for repository in repositories {
+                        for pull_request in &repository.pull_requests {
+                            writer.serialize(CsvRow {
+                                profile: profile.name,
+                                reviewer: reviewer.reviewer.name,
+                                reviewer_email: reviewer.reviewer.email,
+                                queue,
+                                repository: repository.repository,
+                                pull_request_id: pull_request.id,
+                                title: pull_request.title,
+                                url: pull_request.url,
+                                author: pull_request.author,
+                                created_at: pull_request.created_at,
+                                age_minutes: pull_request.age_minutes,
+                                waiting_reviewers: pull_request.waiting_reviewers().join("; "),
+                            })?;
+                        }
+                    }
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn write_markdown(&self, mut writer: impl Write) -> Result<()> {
+        writeln!(writer, "# Pull requests waiting for action")?;
+        for profile in &self.profiles {
+            writeln!(writer, "\n## {}", escape_markdown(profile.name))?;
+            if profile.reviewers.is_empty() {
+                writeln!(writer, "\nNo pull requests are waiting.")?;
+            }
+            for reviewer in &profile.reviewers {
+                writeln!(writer, "\n### {}", escape_markdown(reviewer.reviewer.name))?;
+                if !reviewer.waiting_for_review.is_empty() {
+                    writeln!(writer, "\nWaiting for review:\n")?;
+                    writeln!(writer, "| Repository | Pull request | Author | Age |")?;
+                    writeln!(writer, "|---|---|---|---|")?;
+                    for (repository, pull_request) in pull_requests(&reviewer.waiting_for_review) {
+                        writeln!(
+                            writer,
+                            "| {} | {} | {} | {} |",
+                            escape_markdown(repository),
+                            pull_request.markdown_link(),
+                            escape_markdown(pull_request.author),
+                            format_age(pull_request.age_minutes)
+                        )?;
+                    }
+                }
+                if !reviewer.waiting_for_author.is_empty() {
+                    writeln!(writer, "\nWaiting for the author:\n")?;
+                    writeln!(
+                        writer,
+                        "| Repository | Pull request | Waiting reviewers | Age |"
+                    )?;
+                    writeln!(writer, "|---|---|---|---|")?;
+                    for (repository, pull_request) in pull_requests(&reviewer.waiting_for_author) {
+                        writeln!(
+                            writer,
+                            "| {} | {} | {} | {} |",
+                            escape_markdown(repository),
+                            pull_request.markdown_link(),
+                            escape_markdown(&pull_request.waiting_reviewers().join(", ")),
+                            format_age(pull_request.age_minutes)
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn pull_requests<'r, 'a>(
+    repositories: &'r [RepositoryReport<'a>],
+) -> impl Iterator<Item = (&'a str, &'r PullRequestReport<'a>)> {
+    repositories.iter().flat_map(|repository| {
+        repository
+            .pull_requests
+            .iter()
+            .map(|pull_request| (repository.repository, pull_request))
+    })
+}
+
+/// Escapes characters which break Markdown table cells and links.
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+}
+
+impl<'a> ReviewerReport<'a> {
+    pub fn new(requests: &'a ReviewerRequests, now: DateTime<Utc>) -> Self {
+        let repositories = |repositories: &'a [RepoRequests]| {
+            repositories
+                .iter()
+                .map(|r| RepositoryReport::new(r, now))
+                .collect()
+        };
+        Self {
+            reviewer: ReportReviewer {
+                name: &requests.reviewer_name,
+                email: requests.reviewer_email.as_deref(),
+            },
+            waiting_for_review: repositories(requests.waiting_for_review()),
+            waiting_for_author: repositories(requests.waiting_by_reviewers()),
+        }
+    }
+}
+
+impl<'a> RepositoryReport<'a> {
+    fn new(repository: &'a RepoRequests, now: DateTime<Utc>) -> Self {
+        Self {
+            repository: repository.repo_id(),
+            pull_requests: repository
+                .pull_requests()
+                .iter()
+                .map(|r| PullRequestReport::new(r, now))
+                .collect(),
+        }
+    }
+}
+
+impl<'a> PullRequestReport<'a> {
+    fn new(pull_request: &'a PullRequest, now: DateTime<Utc>) -> Self {
+        Self {
+            id: pull_request.id,
+            title: &pull_request.title,
+            url: &pull_request.url,
+            author: &pull_request.created_by.name,
+            created_at: pull_request.creation_date,
+            age_minutes: (now - pull_request.creation_date).num_minutes(),
+            reviewers: pull_request
+                .reviewers
+                .iter()
+                .map(ReviewerVoteReport::from)
+                .collect(),
+        }
+    }
+}
+
+impl<'a> PullRequestReport<'a> {
+    /// Names of the reviewers waiting for the author.
+    fn waiting_reviewers(&self) -> Vec<&'a str> {
+        self.reviewers
+            .iter()
+            .filter(|r| r.vote == VoteReport::WaitingForAuthor)
+            .map(|r| r.name)
+            .collect()
+    }
+
+    fn markdown_link(&self) -> String {
+        format!("[{}]({})", escape_markdown(self.title), self.url)
+    }
+}
+
+impl<'a> From<&'a PullRequestReviewer> for ReviewerVoteReport<'a> {
+    fn from(value: &'a PullRequestReviewer) -> Self {
+        Self {
+            name: &value.name,
+            is_required: value.is_required,
+            has_declined: value.has_declined,
+            vote: VoteReport::from(&value.vote),
+        }
+    }
+}
+
+impl From<&Vote> for VoteReport {
+    fn from(value: &Vote) -> Self {
+        match value {
+            Vote::Approved => Self::Approved,
+            Vote::ApprovedWithSuggestions => Self::ApprovedWithSuggestions,
+            Vote::NoVote => Self::NoVote,
+            Vote::WaitingForAuthor => Self::WaitingForAuthor,
+            Vote::Rejected => Self::Rejected,
+            Vote::Unknown => Self::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn pull_request(id: usize, title: &str) -> PullRequest {
+        serde_json::from_value(json!({
+            "pullRequestId": id,
+            "title": title,
+            "url": format!("https://dev.azure.com/pr/{id}"),
+            "createdBy": { "id": "1", "displayName": "Anna" },
+            "creationDate": "2023-07-01T10:00:00Z",
+            "status": "active",
+            "reviewers": [
+                { "id": "2", "displayName": "Bob", "isRequired": true, "vote": -5, "hasDeclined": false },
+                { "id": "3", "displayName": "Eve", "vote": 0, "hasDeclined": false },
+            ],
+        }))
+        .unwrap()
+    }
+
+    fn profiles() -> Vec<(String, Vec<ReviewerRequests>)> {
+        let waiting_for_review =
+            RepoRequests::new("api".to_string(), vec![pull_request(1, "Fix | pipe")]);
+        let waiting_by_reviewers =
+            RepoRequests::new("web".to_string(), vec![pull_request(2, "Add [docs]")]);
+        let requests = ReviewerRequests::new(
+            "Anna".to_string(),
+            vec![waiting_for_review],
+            vec![waiting_by_reviewers],
+        );
+        vec![("default".to_string(), vec![requests])]
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2023-07-02T11:30:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn csv_has_row_per_pull_request() -> Result<()> {
+        let profiles = profiles();
+        let mut output = Vec::new();
+        ReviewReport::new(&profiles, now()).write(ReportFormat::Csv, &mut output)?;
+
+        let output = String::from_utf8(output)?;
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("profile,reviewer,reviewer_email,queue,repository,"));
+        assert_eq!(
+            lines[1],
+            "default,Anna,,waiting_for_review,api,1,Fix | pipe,https://dev.azure.com/pr/1,Anna,\
+            2023-07-01T10:00:00Z,1530,Bob"
+        );
+        assert!(lines[2].starts_with("default,Anna,,waiting_for_author,web,2,"));
+        Ok(())
+    }
+
+    #[test]
+    fn markdown_has_table_per_queue() -> Result<()> {
+        let profiles = profiles();
+        let mut output = Vec::new();
+        ReviewReport::new(&profiles, now()).write(ReportFormat::Markdown, &mut output)?;
+
+        let output = String::from_utf8(output)?;
+        assert!(output.contains("\n## default\n\n### Anna\n\nWaiting for review:\n"));
+        assert!(output
+            .contains("| api | [Fix \\| pipe](https://dev.azure.com/pr/1) | Anna | 1d 1h 30m |"));
+        assert!(output
+            .contains("| web | [Add \\[docs\\]](https://dev.azure.com/pr/2) | Bob | 1d 1h 30m |"));
+        Ok(())
+    }
+
+    #[test]
+    fn json_has_schema_version() -> Result<()> {
+        let profiles = profiles();
+        let mut output = Vec::new();
+        ReviewReport::new(&profiles, now()).write(ReportFormat::Json, &mut output)?;
+
+        let report: serde_json::Value = serde_json::from_slice(&output)?;
+        assert_eq!(report["schema_version"], 1);
+        assert_eq!(report["profiles"][0]["name"], "default");
+        let reviewer = &report["profiles"][0]["reviewers"][0];
+        assert_eq!(
+            reviewer["waiting_for_review"][0]["pull_requests"][0]["age_minutes"],
+            1530
+        );
+        Ok(())
+    }
+}
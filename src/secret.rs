@@ -0,0 +1,238 @@
+//! Secret values of the configuration.
+//!
+//! Every secret field (`token`, `password` and `secret`) can be written in one of
+//! the following forms:
+//!
+//! ```toml
+//! token = "${AZURE_TOKEN}"                   # Environment variable interpolation
+//! token_file = "/run/secrets/azure_token"    # Content of the file
+//! token_command = "pass show azure/token"    # Standard output of a shell command
+//! ```
+//!
+//! Values of the webhook `headers` table support the interpolation as well.
+use color_eyre::{Report, Result};
+use serde::Deserialize;
+use std::fmt;
+use std::process::Command;
+
+const SECRET_KEYS: [&str; 3] = ["token", "password", "secret"];
+const INTERPOLATED_TABLES: [&str; 1] = ["headers"];
+
+/// String which is never printed in the `Debug` output.
+#[derive(Deserialize, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+/// Other keys of the secret written with the key, e.g. `token_file` and
+/// `token_command` for `token`, which are replaced when a profile sets the key.
+pub fn other_sources(key: &str) -> Vec<String> {
+    SECRET_KEYS
+        .into_iter()
+        .map(|secret| {
+            [
+                secret.to_string(),
+                format!("{secret}_file"),
+                format!("{secret}_command"),
+            ]
+        })
+        .find(|sources| sources.iter().any(|source| source == key))
+        .map(|sources| sources.into_iter().filter(|source| source != key).collect())
+        .unwrap_or_default()
+}
+
+/// Replaces secret references in the parsed config with the referenced values.
+pub fn resolve_secrets(value: &mut toml::Value) -> Result<()> {
+    match value {
+        toml::Value::Table(table) => resolve_table(table),
+        toml::Value::Array(values) => values.iter_mut().try_for_each(resolve_secrets),
+        _ => Ok(()),
+    }
+}
+
+fn resolve_table(table: &mut toml::Table) -> Result<()> {
+    for key in SECRET_KEYS {
+        let file_key = format!("{key}_file");
+        let command_key = format!("{key}_command");
+        let sources_count = [key, &file_key, &command_key]
+            .into_iter()
+            .filter(|k| table.contains_key(*k))
+            .count();
+        if sources_count > 1 {
+            return Err(Report::msg(format!(
+                "Only one of `{key}`, `{file_key}` or `{command_key}` can be set."
+            )));
+        }
+
+        let resolved = if let Some(path) = table.remove(&file_key) {
+            read_file(&file_key, &path)?
+        } else if let Some(command) = table.remove(&command_key) {
+            run_command(&command_key, &command)?
+        } else if let Some(toml::Value::String(value)) = table.get(key) {
+            interpolate(value)?
+        } else {
+            continue;
+        };
+        table.insert(key.to_string(), toml::Value::String(resolved));
+    }
+
+    for (key, value) in table.iter_mut() {
+        match value {
+            toml::Value::Table(headers) if INTERPOLATED_TABLES.contains(&key.as_str()) => {
+                for (_, value) in headers.iter_mut() {
+                    if let toml::Value::String(value) = value {
+                        *value = interpolate(value)?;
+                    }
+                }
+            }
+            value => resolve_secrets(value)?,
+        }
+    }
+    Ok(())
+}
+
+fn read_file(key: &str, path: &toml::Value) -> Result<String> {
+    let path = path
+        .as_str()
+        .ok_or_else(|| Report::msg(format!("`{key}` must be a string.")))?;
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| Report::msg(format!("Unable to read `{key}` {path}: {e}")))?;
+    Ok(trim_line_end(content))
+}
+
+fn run_command(key: &str, command: &toml::Value) -> Result<String> {
+    let command = command
+        .as_str()
+        .ok_or_else(|| Report::msg(format!("`{key}` must be a string.")))?;
+    let output = Command::new("sh").arg("-c").arg(command).output()?;
+    if !output.status.success() {
+        return Err(Report::msg(format!(
+            "`{key}` failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(trim_line_end(String::from_utf8(output.stdout)?))
+}
+
+fn trim_line_end(mut value: String) -> String {
+    let len = value.trim_end_matches(['\r', '\n']).len();
+    value.truncate(len);
+    value
+}
+
+/// Replaces `${NAME}` with the value of the `NAME` environment variable.
+/// `$${` is written as a literal `${`.
+fn interpolate(value: &str) -> Result<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(escaped) = rest.strip_prefix("$${") {
+            result.push_str("${");
+            rest = escaped;
+        } else if let Some(reference) = rest.strip_prefix("${") {
+            let end = reference
+                .find('}')
+                .ok_or_else(|| Report::msg(format!("Unterminated `${{` in \"{value}\".")))?;
+            let name = &reference[..end];
+            let variable = std::env::var(name)
+                .map_err(|_| Report::msg(format!("Environment variable {name} is not set.")))?;
+            result.push_str(&variable);
+            rest = &reference[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    fn resolve(content: &str) -> Result<toml::Value> {
+        let mut value = toml::from_str::<toml::Value>(content)?;
+        resolve_secrets(&mut value)?;
+        Ok(value)
+    }
+
+    #[test]
+    fn secrets_resolved_from_sources() -> Result<()> {
+        // An existing variable, so the environment of the parallel tests is not changed.
+        let path_variable = std::env::var("PATH")?;
+        let mut file = tempfile::NamedTempFile::new()?;
+        writeln!(file, "from-file")?;
+
+        let value = resolve(&format!(
+            r#"
+            [azure]
+            token = "${{PATH}}"
+            project = "${{NOT_INTERPOLATED}}"
+            [slack]
+            token_file = "{}"
+            [email]
+            password_command = "echo from-command"
+            [webhook.headers]
+            Authorization = "Bearer ${{PATH}} $${{literal}}"
+            "#,
+            file.path().display()
+        ))?;
+
+        assert_eq!(
+            value["azure"]["token"].as_str(),
+            Some(path_variable.as_str())
+        );
+        assert_eq!(
+            value["azure"]["project"].as_str(),
+            Some("${NOT_INTERPOLATED}")
+        );
+        assert_eq!(value["slack"]["token"].as_str(), Some("from-file"));
+        assert!(value["slack"].get("token_file").is_none());
+        assert_eq!(value["email"]["password"].as_str(), Some("from-command"));
+        assert_eq!(
+            value["webhook"]["headers"]["Authorization"].as_str(),
+            Some(format!("Bearer {path_variable} ${{literal}}").as_str())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn other_sources_of_secret_listed() {
+        assert_eq!(other_sources("token"), ["token_file", "token_command"]);
+        assert_eq!(
+            other_sources("password_file"),
+            ["password", "password_command"]
+        );
+        assert!(other_sources("project").is_empty());
+    }
+
+    #[test]
+    fn invalid_references_rejected() {
+        assert!(resolve("[azure]\ntoken = \"${REVIEWPORTER_TEST_MISSING}\"").is_err());
+        assert!(resolve("[azure]\ntoken = \"${UNTERMINATED\"").is_err());
+        assert!(resolve("[azure]\ntoken = \"a\"\ntoken_file = \"b\"").is_err());
+        assert!(resolve("[azure]\ntoken_command = \"exit 1\"").is_err());
+    }
+
+    #[test]
+    fn debug_output_redacted() {
+        let secret = Secret("password".to_string());
+        assert_eq!(format!("{secret:?}"), "Secret(***)");
+    }
+}
@@ -1,24 +1,37 @@
+use crate::azure::{MessageFormat, ReviewerRequests};
+use crate::notifier::{DirectoryUser, Notifier, UserDirectory};
+use crate::template::MessageTemplate;
+use async_trait::async_trait;
+use color_eyre::eyre::WrapErr;
 use color_eyre::Result;
 use futures::TryFutureExt;
 use reqwest::{header::AUTHORIZATION, Client};
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::instrument;
 use url::Url;
 
-pub struct SlackApi<'a> {
-    token: &'a str,
-    team_id: &'a str,
-    usergroup_id: &'a str,
+#[derive(Clone)]
+pub struct SlackApi {
+    token: String,
+    team_id: String,
+    usergroup_id: String,
     base_url: Url,
     client: Client,
+    template: Arc<MessageTemplate>,
 }
 
 #[derive(Deserialize, Debug)]
 struct User {
     #[serde(default)]
     id: String,
-    #[serde(rename = "real_name")]
+    #[serde(default, rename = "real_name")]
     pub name: String,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
     status_text: String,
 }
 
@@ -33,8 +46,18 @@ struct UserContainer {
     profile: User,
 }
 
+/// Scopes used to read the usergroup, match users and send messages.
+const REQUIRED_SCOPES: [&str; 4] = [
+    "usergroups:read",
+    "users.profile:read",
+    "users:read.email",
+    "chat:write",
+];
+const SCOPES_HEADER: &str = "x-oauth-scopes";
+
 #[derive(Deserialize, Debug)]
 struct UsergroupUsers {
+    #[serde(default)]
     users: Vec<String>,
 }
 
@@ -44,31 +67,121 @@ struct PostMessagePayload {
     channel: String,
 }
 
-#[derive(Deserialize)]
-struct PostMessageResponse {
-    ok: bool,
-    error: Option<String>,
+/// Whether the response was received but could not be deserialized.
+fn is_decode_error(error: &color_eyre::Report) -> bool {
+    let error = crate::Error::find(error);
+    error.is_some_and(|e| matches!(e, crate::Error::Deserialize { .. }))
+}
+
+/// Deserializes the body of the response, or returns its error if it is not `ok`.
+fn parse_response<T: DeserializeOwned>(body: Value) -> Result<T> {
+    if body["ok"].as_bool() != Some(true) {
+        let code = body["error"].as_str();
+        return Err(crate::Error::from_slack_code(code).into());
+    }
+    crate::error::from_value("slack", body)
 }
 
-impl<'a> SlackApi<'a> {
-    pub fn new(token: &'a str, team_id: &'a str, usergroup_id: &'a str) -> Self {
+#[async_trait]
+impl Notifier for SlackApi {
+    #[instrument(skip_all, fields(usergroup = %self.usergroup_id))]
+    async fn obtain_directory(&self) -> Result<UserDirectory> {
+        let user_list = self.obtain_user_list().await?;
+        let requests = user_list
+            .into_iter()
+            .map(|user_id| self.obtain_user_info(user_id));
+
+        let users = futures::future::join_all(requests)
+            .await
+            .into_iter()
+            .filter_map(|user| match user {
+                Err(e) if is_decode_error(&e) => {
+                    tracing::warn!("Skipped user with invalid profile: {e}.");
+                    None
+                }
+                user => Some(user),
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .map(|user| DirectoryUser {
+                is_available: !user.is_on_vacation(),
+                id: user.id,
+                name: user.name,
+                email: user.email,
+            });
+
+        Ok(UserDirectory::Listed(users.collect()))
+    }
+
+    #[instrument(skip(self, requests))]
+    async fn send_message(&self, user_id: &str, requests: &ReviewerRequests) -> Result<()> {
+        let message = self
+            .template
+            .render(requests, MessageFormat::SlackMarkdown)?;
+        self.post_message(user_id, message).await
+    }
+
+    #[instrument(skip_all)]
+    async fn check_access(&self) -> Result<Vec<String>> {
+        let url = self.base_url.join("auth.test")?;
+        let request = self
+            .client
+            .post(url)
+            .header(AUTHORIZATION, format!("Bearer {}", self.token))
+            .build()?;
+        let response = crate::http::execute(&self.client, "slack", request).await?;
+        let response = crate::error::error_for_status("slack", response).await?;
+        let scopes = response
+            .headers()
+            .get(SCOPES_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .split(',')
+            .map(|v| v.trim().to_string())
+            .collect::<Vec<_>>();
+        let body = crate::error::json::<Value>("slack", response).await?;
+        if let Err(e) = parse_response::<IgnoredAny>(body) {
+            return Ok(vec![format!("Slack token is not valid: {e}.")]);
+        }
+        let problems = REQUIRED_SCOPES
+            .into_iter()
+            .filter(|scope| !scopes.iter().any(|v| v == scope))
+            .map(|scope| format!("Slack token is missing the {scope} scope."))
+            .collect();
+        Ok(problems)
+    }
+}
+
+impl SlackApi {
+    pub fn new(token: &str, team_id: &str, usergroup_id: &str) -> Self {
         Self {
-            token,
-            team_id,
-            usergroup_id,
+            token: token.to_string(),
+            team_id: team_id.to_string(),
+            usergroup_id: usergroup_id.to_string(),
             base_url: Url::parse("https://slack.com/api/")
                 .expect("Failed to create Slack base URL"),
-            client: Client::new(),
+            client: crate::http::client(),
+            template: Arc::default(),
         }
     }
 
-    pub async fn send_message(&self, user_id: String, message: String) -> Result<()> {
+    pub fn with_base_url(mut self, base_url: Url) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    pub fn with_template(mut self, template: Arc<MessageTemplate>) -> Self {
+        self.template = template;
+        self
+    }
+
+    async fn post_message(&self, user_id: &str, message: String) -> Result<()> {
         let url = self.base_url.join("chat.postMessage")?;
         tracing::info!("Sending message to {user_id}.");
 
         let payload = PostMessagePayload {
             text: message,
-            channel: user_id.clone(),
+            channel: user_id.to_string(),
         };
         let request = self
             .client
@@ -77,35 +190,22 @@ impl<'a> SlackApi<'a> {
             .header(AUTHORIZATION, format!("Bearer {}", self.token))
             .build()?;
 
-        let response = self.client.execute(request).await?;
-        let response = response.json::<PostMessageResponse>().await?;
-        if response.ok {
-            tracing::info!("Message successfully sent to {user_id}.");
-            Ok(())
-        } else {
-            tracing::info!(
-                "Message sent to {user_id} failed with error: {:?}.",
-                response.error
-            );
-            Err(color_eyre::Report::msg(response.error.unwrap()))
+        let response = crate::http::execute(&self.client, "slack", request).await?;
+        let response = crate::error::error_for_status("slack", response).await?;
+        let body = crate::error::json::<Value>("slack", response).await?;
+        match parse_response::<IgnoredAny>(body) {
+            Ok(_) => {
+                tracing::info!("Message successfully sent to {user_id}.");
+                Ok(())
+            }
+            Err(e) => {
+                tracing::info!("Message sent to {user_id} failed with error: {e}.");
+                Err(e)
+            }
         }
     }
 
-    pub async fn obtain_users(&self) -> Result<HashMap<String, String>> {
-        let user_list = self.obtain_user_list().await?;
-        let requests = user_list
-            .into_iter()
-            .map(|user_id| self.obtain_user_info(user_id));
-
-        let iter = futures::future::try_join_all(requests)
-            .await?
-            .into_iter()
-            .filter(|user| !user.is_on_vacation())
-            .map(|u| (u.name, u.id));
-
-        Ok(iter.collect())
-    }
-
+    #[instrument(skip(self))]
     async fn obtain_user_info(&self, user_id: String) -> Result<User> {
         tracing::info!("Requesting user {user_id} info.");
         let mut url = self.base_url.join("users.profile.get")?;
@@ -124,16 +224,18 @@ impl<'a> SlackApi<'a> {
     async fn obtain_user_list(&self) -> Result<Vec<String>> {
         tracing::info!("Requesting users list in group {}.", self.usergroup_id);
         let mut url = self.base_url.join("usergroups.users.list")?;
-        let query = [("usergroup", self.usergroup_id)];
+        let query = [("usergroup", self.usergroup_id.as_str())];
         url.query_pairs_mut().extend_pairs(query);
 
-        self.make_request::<UsergroupUsers>(url)
-            .map_ok(|r| r.users)
-            .await
+        let response = self.make_request::<UsergroupUsers>(url).await;
+        let response = response.wrap_err_with(|| {
+            format!("Unable to obtain users of usergroup {}", self.usergroup_id)
+        })?;
+        Ok(response.users)
     }
 
     async fn make_request<T: DeserializeOwned>(&self, mut url: Url) -> Result<T> {
-        let query = [("team_id", self.team_id)];
+        let query = [("team_id", self.team_id.as_str())];
         url.query_pairs_mut().extend_pairs(query);
         tracing::debug!("Executing GET request with url: {url}");
 
@@ -143,11 +245,75 @@ impl<'a> SlackApi<'a> {
             .header(AUTHORIZATION, format!("Bearer {}", self.token))
             .build()?;
 
-        self.client
-            .execute(request)
-            .await?
-            .json()
+        let response = crate::http::execute(&self.client, "slack", request).await?;
+        let response = crate::error::error_for_status("slack", response).await?;
+        parse_response(crate::error::json("slack", response).await?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn users_with_invalid_profiles_skipped() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(path("/usergroups.users.list"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "ok": true,
+                "users": ["U1", "U2"],
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(path("/users.profile.get"))
+            .and(query_param("user", "U1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "ok": true,
+                "profile": { "real_name": "Alice" },
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(path("/users.profile.get"))
+            .and(query_param("user", "U2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "ok": true,
+                "profile": { "real_name": ["Bob"] },
+            })))
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&format!("{}/", server.uri()))?;
+        let api = SlackApi::new("token", "T1", "S1").with_base_url(base_url);
+        let directory = api.obtain_directory().await?;
+
+        assert_eq!(directory.available_user_id("Alice", None), Some("U1"));
+        assert!(!directory.contains("Bob", None));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_message_without_error_reported() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(path("/chat.postMessage"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": false })))
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&format!("{}/", server.uri()))?;
+        let api = SlackApi::new("token", "T1", "S1").with_base_url(base_url);
+        let error = api
+            .post_message("U1", "Hello".to_string())
             .await
-            .map_err(color_eyre::Report::new)
+            .unwrap_err();
+
+        let error = crate::Error::find(&error);
+        assert!(
+            matches!(error, Some(crate::Error::Slack { code }) if code == "unknown_error"),
+            "{error:?}"
+        );
+        Ok(())
     }
 }
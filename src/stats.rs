@@ -0,0 +1,198 @@
+//! Review statistics written by the `stats` command.
+//!
+//! The JSON output has the following structure, durations are in minutes:
+//!
+//! ```json
+//! {
+//!   "since": "2023-06-01",
+//!   "until": "2023-06-30",
+//!   "profiles": [{
+//!     "name": "default",
+//!     "team": {
+//!       "name": "Team",
+//!       "pull_requests": 10,
+//!       "time_to_first_vote": { "count": 8, "median_minutes": 95, "average_minutes": 130 },
+//!       "time_to_approval": { "count": 7, "median_minutes": 300, "average_minutes": 410 },
+//!       "queue_size": 2,
+//!       "declined": 1,
+//!       "waiting_for_author_round_trips": 3
+//!     },
+//!     "reviewers": []
+//!   }]
+//! }
+//! ```
+//!
+//! `reviewers` holds the same statistics for every team member who was asked to review.
+use crate::azure::{DurationStats, ReviewStats, Stats};
+use crate::cli::StatsFormat;
+use crate::template::format_age;
+use chrono::NaiveDate;
+use color_eyre::Result;
+use serde::Serialize;
+use std::io::Write;
+
+const TABLE_HEADER: [&str; 7] = [
+    "Reviewer",
+    "Pull requests",
+    "First vote (median)",
+    "Approval (median)",
+    "Queue",
+    "Declined",
+    "Round trips",
+];
+
+#[derive(Serialize, Debug)]
+pub struct StatsReport {
+    since: NaiveDate,
+    until: NaiveDate,
+    profiles: Vec<ProfileStats>,
+}
+
+#[derive(Serialize, Debug)]
+struct ProfileStats {
+    name: String,
+    #[serde(flatten)]
+    stats: ReviewStats,
+}
+
+impl StatsReport {
+    pub fn new(since: NaiveDate, until: NaiveDate) -> Self {
+        Self {
+            since,
+            until,
+            profiles: vec![],
+        }
+    }
+
+    pub fn add_profile(&mut self, name: String, stats: ReviewStats) {
+        self.profiles.push(ProfileStats { name, stats });
+    }
+
+    pub fn write(&self, format: StatsFormat, mut writer: impl Write) -> Result<()> {
+        match format {
+            StatsFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, self)?;
+                writeln!(writer)?;
+            }
+            StatsFormat::Table => self.write_tables(writer)?,
+        }
+        Ok(())
+    }
+
+    fn write_tables(&self, mut writer: impl Write) -> Result<()> {
+        for (index, profile) in self.profiles.iter().enumerate() {
+            if index > 0 {
+                writeln!(writer)?;
+            }
+            writeln!(
+                writer,
+                "Profile {}, pull requests created from {} to {}:",
+                profile.name, self.since, self.until
+            )?;
+            let team = &profile.stats.team;
+            let rows = std::iter::once(table_row(&format!("Team {}", team.name), team))
+                .chain(
+                    profile
+                        .stats
+                        .reviewers
+                        .iter()
+                        .map(|stats| table_row(&stats.name, stats)),
+                )
+                .collect::<Vec<_>>();
+            write_table(&mut writer, &rows)?;
+        }
+        Ok(())
+    }
+}
+
+fn table_row(name: &str, stats: &Stats) -> [String; 7] {
+    [
+        name.to_string(),
+        stats.pull_requests.to_string(),
+        format_median(&stats.time_to_first_vote),
+        format_median(&stats.time_to_approval),
+        stats.queue_size.to_string(),
+        stats.declined.to_string(),
+        stats.waiting_for_author_round_trips.to_string(),
+    ]
+}
+
+fn format_median(stats: &DurationStats) -> String {
+    stats
+        .median_minutes
+        .map(format_age)
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Writes the rows under the header, the first column aligned to the left and
+/// the others to the right.
+fn write_table(writer: &mut impl Write, rows: &[[String; 7]]) -> Result<()> {
+    let mut widths = TABLE_HEADER.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let header = TABLE_HEADER.map(str::to_string);
+    for row in std::iter::once(&header).chain(rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(column, (cell, width))| match column {
+                0 => format!("{cell:<width$}"),
+                _ => format!("{cell:>width$}"),
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(writer, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn table_aligned() -> Result<()> {
+        let mut report = StatsReport::new(
+            NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+        );
+        let reviewer = Stats {
+            name: "Alice".to_string(),
+            pull_requests: 3,
+            time_to_first_vote: DurationStats {
+                count: 2,
+                median_minutes: Some(90),
+                average_minutes: Some(90),
+            },
+            queue_size: 1,
+            ..Default::default()
+        };
+        let team = Stats {
+            name: "Dev".to_string(),
+            ..Default::default()
+        };
+        report.add_profile(
+            "default".to_string(),
+            ReviewStats {
+                team,
+                reviewers: vec![reviewer],
+            },
+        );
+
+        let mut output = Vec::new();
+        report.write(StatsFormat::Table, &mut output)?;
+
+        assert_eq!(
+            String::from_utf8(output)?,
+            "Profile default, pull requests created from 2023-06-01 to 2023-06-30:\n\
+            Reviewer  Pull requests  First vote (median)  Approval (median)  Queue  Declined  Round trips\n\
+            Team Dev              0                    -                  -      0         0            0\n\
+            Alice                 3               1h 30m                  -      1         0            0\n"
+        );
+        Ok(())
+    }
+}
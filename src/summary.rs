@@ -0,0 +1,133 @@
+//! Summary of the members who were not sent their reports and the profiles which failed.
+use std::fmt::{Display, Formatter};
+
+/// Step of the run in which a member failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Obtaining the member's pull requests from the code hosting.
+    FetchPullRequests,
+    /// Finding the member in the user directory of the notifier.
+    MatchUser,
+    /// Sending the message to the member.
+    SendMessage,
+}
+
+#[derive(Debug)]
+pub struct MemberFailure {
+    pub profile: String,
+    pub member: String,
+    pub stage: Stage,
+    pub error: String,
+}
+
+/// Profile whose reports could not be sent at all, e.g. because its user directory
+/// is unavailable.
+#[derive(Debug)]
+pub struct ProfileFailure {
+    pub profile: String,
+    pub error: String,
+}
+
+#[derive(Debug, Default)]
+pub struct RunSummary {
+    pub sent: usize,
+    pub failures: Vec<MemberFailure>,
+    pub profile_failures: Vec<ProfileFailure>,
+}
+
+impl RunSummary {
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty() && self.profile_failures.is_empty()
+    }
+
+    pub fn fail_profile(&mut self, profile: &str, error: impl Display) {
+        self.profile_failures.push(ProfileFailure {
+            profile: profile.to_string(),
+            error: error.to_string(),
+        });
+    }
+
+    pub fn fail(&mut self, profile: &str, member: &str, stage: Stage, error: impl Display) {
+        self.failures.push(MemberFailure {
+            profile: profile.to_string(),
+            member: member.to_string(),
+            stage,
+            error: error.to_string(),
+        });
+    }
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let stage = match self {
+            Stage::FetchPullRequests => "fetching pull requests",
+            Stage::MatchUser => "matching the user",
+            Stage::SendMessage => "sending the message",
+        };
+        write!(f, "{stage}")
+    }
+}
+
+impl Display for RunSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} messages sent, {} members failed.",
+            self.sent,
+            self.failures.len()
+        )?;
+        match self.profile_failures.len() {
+            0 => writeln!(f)?,
+            count => writeln!(f, " {count} profiles failed.")?,
+        }
+        for failure in &self.profile_failures {
+            writeln!(f, "- Profile {} failed: {}", failure.profile, failure.error)?;
+        }
+        for failure in &self.failures {
+            writeln!(
+                f,
+                "- {} ({}) failed {}: {}",
+                failure.member, failure.profile, failure.stage, failure.error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn failures_listed_by_stage() {
+        let mut summary = RunSummary {
+            sent: 3,
+            ..Default::default()
+        };
+        assert!(summary.is_success());
+        summary.fail("default", "Anna", Stage::SendMessage, "channel_not_found");
+        summary.fail(
+            "web",
+            "Bob",
+            Stage::MatchUser,
+            "not found in the user directory",
+        );
+
+        assert!(!summary.is_success());
+        assert_eq!(
+            summary.to_string(),
+            "3 messages sent, 2 members failed.\n\
+            - Anna (default) failed sending the message: channel_not_found\n\
+            - Bob (web) failed matching the user: not found in the user directory\n"
+        );
+
+        summary.fail_profile("mobile", "Slack rejected the token with 401: invalid_auth");
+        assert_eq!(
+            summary.to_string(),
+            "3 messages sent, 2 members failed. 1 profiles failed.\n\
+            - Profile mobile failed: Slack rejected the token with 401: invalid_auth\n\
+            - Anna (default) failed sending the message: channel_not_found\n\
+            - Bob (web) failed matching the user: not found in the user directory\n"
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use crate::azure::{MessageFormat, ReviewerRequests};
+use crate::notifier::{DirectoryUser, Notifier, UserDirectory};
+use crate::template::MessageTemplate;
+use async_trait::async_trait;
+use color_eyre::Result;
+use reqwest::{header::AUTHORIZATION, Client, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use url::Url;
+
+const PRESENCES_BATCH_SIZE: usize = 650;
+
+/// Microsoft Teams notifier backed by Microsoft Graph chat messages.
+///
+/// The token must be a delegated token of the sender account with the
+/// `GroupMember.Read.All`, `Presence.Read.All` and `Chat.Create`/`ChatMessage.Send`
+/// permissions. Messages are sent to one-on-one chats between the sender and the user.
+pub struct TeamsApi<'a> {
+    token: &'a str,
+    group_id: &'a str,
+    sender_id: &'a str,
+    base_url: Url,
+    client: Client,
+    template: &'a MessageTemplate,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphList<T> {
+    value: Vec<T>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<Url>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GraphUser {
+    id: String,
+    display_name: String,
+    #[serde(default)]
+    mail: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct OutOfOfficeSettings {
+    #[serde(default)]
+    is_out_of_office: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Presence {
+    id: String,
+    #[serde(default)]
+    out_of_office_settings: OutOfOfficeSettings,
+}
+
+#[derive(Serialize)]
+struct PresencesRequest<'a> {
+    ids: &'a [String],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NewChat {
+    chat_type: &'static str,
+    members: Vec<NewChatMember>,
+}
+
+#[derive(Serialize)]
+struct NewChatMember {
+    #[serde(rename = "@odata.type")]
+    odata_type: &'static str,
+    roles: Vec<&'static str>,
+    #[serde(rename = "user@odata.bind")]
+    user: String,
+}
+
+#[derive(Deserialize)]
+struct Chat {
+    id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageBody {
+    content_type: &'static str,
+    content: String,
+}
+
+#[derive(Serialize)]
+struct NewChatMessage {
+    body: MessageBody,
+}
+
+#[async_trait]
+impl<'a> Notifier for TeamsApi<'a> {
+    async fn obtain_directory(&self) -> Result<UserDirectory> {
+        let users = self.obtain_group_members().await?;
+        let ids = users.iter().map(|u| u.id.clone()).collect::<Vec<_>>();
+        let requests = ids
+            .chunks(PRESENCES_BATCH_SIZE)
+            .map(|ids| self.obtain_presences(ids));
+        let out_of_office = futures::future::try_join_all(requests)
+            .await?
+            .into_iter()
+            .flatten()
+            .filter(|p| p.out_of_office_settings.is_out_of_office)
+            .map(|p| p.id)
+            .collect::<Vec<_>>();
+
+        let users = users.into_iter().map(|user| DirectoryUser {
+            is_available: !out_of_office.contains(&user.id),
+            id: user.id,
+            name: user.display_name,
+            email: user.mail,
+        });
+        Ok(UserDirectory::Listed(users.collect()))
+    }
+
+    async fn send_message(&self, user_id: &str, requests: &ReviewerRequests) -> Result<()> {
+        tracing::info!("Sending message to {user_id}.");
+        let chat = self.obtain_chat(user_id).await?;
+        let url = self.base_url.join(&format!("chats/{}/messages", chat.id))?;
+        let payload = NewChatMessage {
+            body: MessageBody {
+                content_type: "html",
+                content: self.template.render(requests, MessageFormat::Html)?,
+            },
+        };
+        self.send_request::<serde_json::Value>(self.client.post(url).json(&payload))
+            .await?;
+        tracing::info!("Message successfully sent to {user_id}.");
+        Ok(())
+    }
+}
+
+impl<'a> TeamsApi<'a> {
+    pub fn new(token: &'a str, group_id: &'a str, sender_id: &'a str) -> Self {
+        Self {
+            token,
+            group_id,
+            sender_id,
+            base_url: Url::parse("https://graph.microsoft.com/v1.0/")
+                .expect("Failed to create Microsoft Graph base URL"),
+            client: crate::http::client(),
+            template: MessageTemplate::built_in(),
+        }
+    }
+
+    pub fn with_template(mut self, template: &'a MessageTemplate) -> Self {
+        self.template = template;
+        self
+    }
+
+    #[cfg(test)]
+    fn with_base_url(mut self, base_url: Url) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    async fn obtain_group_members(&self) -> Result<Vec<GraphUser>> {
+        tracing::info!("Requesting members of group {}.", self.group_id);
+        let mut url = self.base_url.join(&format!(
+            "groups/{}/members/microsoft.graph.user",
+            self.group_id
+        ))?;
+        url.query_pairs_mut()
+            .extend_pairs([("$select", "id,displayName,mail")]);
+
+        let mut users = Vec::new();
+        let mut next_url = Some(url);
+        while let Some(url) = next_url {
+            tracing::debug!("Executing GET request with url: {url}");
+            let page = self
+                .send_request::<GraphList<GraphUser>>(self.client.get(url))
+                .await?;
+            users.extend(page.value);
+            next_url = page.next_link;
+        }
+        Ok(users)
+    }
+
+    async fn obtain_presences(&self, ids: &[String]) -> Result<Vec<Presence>> {
+        tracing::info!("Requesting presence of {} users.", ids.len());
+        let url = self.base_url.join("communications/getPresencesByUserId")?;
+        let request = self.client.post(url).json(&PresencesRequest { ids });
+        self.send_request::<GraphList<Presence>>(request)
+            .await
+            .map(|list| list.value)
+    }
+
+    /// Creating a one-on-one chat returns the existing chat if there is one.
+    async fn obtain_chat(&self, user_id: &str) -> Result<Chat> {
+        let url = self.base_url.join("chats")?;
+        let members = [self.sender_id, user_id]
+            .into_iter()
+            .map(|id| -> Result<NewChatMember> {
+                Ok(NewChatMember {
+                    odata_type: "#microsoft.graph.aadUserConversationMember",
+                    roles: vec!["owner"],
+                    user: self.base_url.join(&format!("users('{id}')"))?.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let payload = NewChat {
+            chat_type: "oneOnOne",
+            members,
+        };
+        self.send_request::<Chat>(self.client.post(url).json(&payload))
+            .await
+    }
+
+    async fn send_request<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let request = request
+            .header(AUTHORIZATION, format!("Bearer {}", self.token))
+            .build()?;
+        let response = crate::http::execute(&self.client, "teams", request).await?;
+        let response = crate::error::error_for_status("teams", response).await?;
+        crate::error::json("teams", response).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn out_of_office_users_unavailable() -> Result<()> {
+        let server = MockServer::start().await;
+        let next_link = format!(
+            "{}/groups/group/members/microsoft.graph.user/next",
+            server.uri()
+        );
+        Mock::given(method("GET"))
+            .and(path("/groups/group/members/microsoft.graph.user"))
+            .and(header("authorization", "Bearer token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [{ "id": "1", "displayName": "Alice" }],
+                "@odata.nextLink": next_link,
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/groups/group/members/microsoft.graph.user/next"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [{ "id": "2", "displayName": "Bob" }],
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/communications/getPresencesByUserId"))
+            .and(body_partial_json(json!({ "ids": ["1", "2"] })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    { "id": "1", "availability": "Available" },
+                    { "id": "2", "availability": "Offline", "outOfOfficeSettings": { "isOutOfOffice": true } },
+                ],
+            })))
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&format!("{}/", server.uri()))?;
+        let api = TeamsApi::new("token", "group", "sender").with_base_url(base_url);
+        let directory = api.obtain_directory().await?;
+
+        assert_eq!(directory.available_user_id("Alice", None), Some("1"));
+        assert_eq!(directory.available_user_id("Bob", None), None);
+        assert!(directory.contains("Bob", None));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn message_posted_to_one_on_one_chat() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chats"))
+            .and(body_partial_json(json!({ "chatType": "oneOnOne" })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "id": "19:chat" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chats/19:chat/messages"))
+            .and(body_partial_json(
+                json!({ "body": { "contentType": "html", "content": "Hey!<br>\nJust a friendly reminder that there are " } }),
+            ))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "id": "1" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&format!("{}/", server.uri()))?;
+        let api = TeamsApi::new("token", "group", "sender").with_base_url(base_url);
+        let requests = ReviewerRequests::empty("Alice".to_string());
+        api.send_message("user", &requests).await
+    }
+}
@@ -0,0 +1,168 @@
+//! Message templates.
+//!
+//! Templates use the minijinja (Jinja2) syntax. The context is the reviewer report
+//! described in the `report` module extended with the `format` of the message:
+//! `slack_markdown`, `html`, `markdown` or `plain_text`.
+//!
+//! Besides the built-in filters and functions, templates can use:
+//! - `link(url, title)` renders a link in the format of the message;
+//! - `age` filter renders `age_minutes` as `1d 2h 3m`.
+//!
+//! Values are HTML escaped in HTML messages, and line breaks are converted to
+//! `<br>` there, so one template serves all notifiers.
+use crate::azure::{MessageFormat, ReviewerRequests};
+use crate::report::ReviewerReport;
+use chrono::Utc;
+use color_eyre::Result;
+use minijinja::{Environment, State, Value};
+use serde::Serialize;
+use std::fmt::Write;
+
+const TEMPLATE_NAME: &str = "message";
+const HTML_TEMPLATE_NAME: &str = "message.html";
+
+/// Renders reviewer requests to a message.
+#[derive(Debug, Default)]
+pub enum MessageTemplate {
+    /// Format of `ReviewerRequests::formatted`.
+    #[default]
+    BuiltIn,
+    Custom(Box<Environment<'static>>),
+}
+
+#[derive(Serialize)]
+struct TemplateContext<'a> {
+    #[serde(flatten)]
+    report: ReviewerReport<'a>,
+    format: &'static str,
+}
+
+impl MessageTemplate {
+    pub fn built_in() -> &'static Self {
+        static BUILT_IN: MessageTemplate = MessageTemplate::BuiltIn;
+        &BUILT_IN
+    }
+
+    pub fn new(source: String) -> Result<Self> {
+        let mut environment = Environment::new();
+        // The HTML template has the same source, its name turns on the HTML escaping.
+        environment.add_template_owned(TEMPLATE_NAME, source.clone())?;
+        environment.add_template_owned(HTML_TEMPLATE_NAME, source)?;
+        environment.add_function("link", link);
+        environment.add_filter("age", format_age);
+        Ok(Self::Custom(Box::new(environment)))
+    }
+
+    pub fn render(&self, requests: &ReviewerRequests, format: MessageFormat) -> Result<String> {
+        let Self::Custom(environment) = self else {
+            return Ok(requests.formatted(format).to_string());
+        };
+        let context = TemplateContext {
+            report: ReviewerReport::new(requests, Utc::now()),
+            format: format_name(format),
+        };
+        let name = match format {
+            MessageFormat::Html => HTML_TEMPLATE_NAME,
+            _ => TEMPLATE_NAME,
+        };
+        let message = environment
+            .get_template(name)?
+            .render(Value::from_serialize(&context))?;
+        match format {
+            MessageFormat::Html => Ok(message.replace('\n', "<br>\n")),
+            _ => Ok(message),
+        }
+    }
+}
+
+fn format_name(format: MessageFormat) -> &'static str {
+    match format {
+        MessageFormat::SlackMarkdown => "slack_markdown",
+        MessageFormat::Html => "html",
+        MessageFormat::Markdown => "markdown",
+        MessageFormat::PlainText => "plain_text",
+    }
+}
+
+fn link(state: &State, url: String, title: String) -> Value {
+    let format = state.lookup("format");
+    match format.as_ref().and_then(Value::as_str) {
+        Some("slack_markdown") => {
+            Value::from(format!("<{url}|{}>", html_escape::encode_text(&title)))
+        }
+        Some("html") => Value::from_safe_string(format!(
+            "<a href=\"{}\">{}</a>",
+            html_escape::encode_double_quoted_attribute(&url),
+            html_escape::encode_text(&title)
+        )),
+        Some("markdown") => Value::from(format!("[{}]({url})", title.replace(']', "\\]"))),
+        _ => Value::from(format!("{title} ({url})")),
+    }
+}
+
+pub fn format_age(minutes: i64) -> String {
+    let mut age = String::new();
+    for (value, label) in [
+        (minutes / (24 * 60), "d"),
+        (minutes / 60 % 24, "h"),
+        (minutes % 60, "m"),
+    ] {
+        if value > 0 {
+            let separator = if age.is_empty() { "" } else { " " };
+            write!(age, "{separator}{value}{label}").expect("Writing to string can not fail");
+        }
+    }
+    if age.is_empty() {
+        age.push_str("0m");
+    }
+    age
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEMPLATE: &str = "Hallo {{ reviewer.name }}!\n\
+        {% for repository in waiting_for_review %}{{ repository.repository }}: \
+        {{ repository.pull_requests | length }}{% endfor %}\n\
+        {{ link('https://example.com/pr/1', 'Fix <html> & [links]') }}";
+
+    #[test]
+    fn message_rendered_in_format() -> Result<()> {
+        let template = MessageTemplate::new(TEMPLATE.to_string())?;
+        let requests = ReviewerRequests::empty("Anna <a@b>".to_string());
+
+        let message = template.render(&requests, MessageFormat::Markdown)?;
+        assert_eq!(
+            message,
+            "Hallo Anna <a@b>!\n\n[Fix <html> & [links\\]](https://example.com/pr/1)"
+        );
+        let message = template.render(&requests, MessageFormat::Html)?;
+        assert_eq!(
+            message,
+            "Hallo Anna &lt;a@b&gt;!<br>\n<br>\n\
+            <a href=\"https://example.com/pr/1\">Fix &lt;html&gt; &amp; [links]</a>"
+        );
+        let message = template.render(&requests, MessageFormat::SlackMarkdown)?;
+        assert!(message.ends_with("<https://example.com/pr/1|Fix &lt;html&gt; &amp; [links]>"));
+        Ok(())
+    }
+
+    #[test]
+    fn built_in_template_is_default() -> Result<()> {
+        let requests = ReviewerRequests::empty("Anna".to_string());
+        let message = MessageTemplate::default().render(&requests, MessageFormat::PlainText)?;
+        assert_eq!(
+            message,
+            requests.formatted(MessageFormat::PlainText).to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn age_formatted() {
+        assert_eq!(format_age(0), "0m");
+        assert_eq!(format_age(61), "1h 1m");
+        assert_eq!(format_age(2 * 24 * 60 + 5), "2d 5m");
+    }
+}
@@ -0,0 +1,181 @@
+use crate::azure::{MessageFormat, ReviewerRequests};
+use crate::notifier::{Notifier, UserDirectory};
+use crate::report::ReviewerReport;
+use crate::template::MessageTemplate;
+use async_trait::async_trait;
+use chrono::Utc;
+use color_eyre::Result;
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::Client;
+use serde::Serialize;
+use sha2::Sha256;
+use url::Url;
+
+pub const SIGNATURE_HEADER: &str = "x-reviewporter-signature";
+
+/// Forwards reviewer requests to an HTTP endpoint.
+///
+/// Every reviewer's requests are sent in a separate `POST` request with the JSON
+/// payload described in the `report` module, extended with the `event` and the
+/// Markdown `text` fields. If a secret is configured, the body is signed with
+/// HMAC-SHA256 and the signature is sent in the `X-Reviewporter-Signature` header
+/// as `sha256=<hex digest>`.
+pub struct WebhookNotifier<'a> {
+    url: &'a Url,
+    headers: HeaderMap,
+    secret: Option<&'a str>,
+    client: Client,
+    template: &'a MessageTemplate,
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    event: &'static str,
+    #[serde(flatten)]
+    report: ReviewerReport<'a>,
+    text: String,
+}
+
+impl<'a> WebhookNotifier<'a> {
+    pub fn new<'h>(
+        url: &'a Url,
+        headers: impl IntoIterator<Item = (&'h str, &'h str)>,
+        secret: Option<&'a str>,
+    ) -> Result<Self> {
+        let headers = headers
+            .into_iter()
+            .map(|(name, value)| -> Result<_> {
+                Ok((
+                    HeaderName::from_bytes(name.as_bytes())?,
+                    HeaderValue::from_str(value)?,
+                ))
+            })
+            .collect::<Result<HeaderMap>>()?;
+        Ok(Self {
+            url,
+            headers,
+            secret,
+            client: crate::http::client(),
+            template: MessageTemplate::built_in(),
+        })
+    }
+
+    pub fn with_template(mut self, template: &'a MessageTemplate) -> Self {
+        self.template = template;
+        self
+    }
+}
+
+#[async_trait]
+impl<'a> Notifier for WebhookNotifier<'a> {
+    async fn obtain_directory(&self) -> Result<UserDirectory> {
+        Ok(UserDirectory::Open)
+    }
+
+    async fn send_message(&self, user_id: &str, requests: &ReviewerRequests) -> Result<()> {
+        tracing::info!("Forwarding requests of {user_id} to the webhook.");
+        let payload = WebhookPayload {
+            event: "reviewer_requests",
+            report: ReviewerReport::new(requests, Utc::now()),
+            text: self.template.render(requests, MessageFormat::Markdown)?,
+        };
+        let body = serde_json::to_vec(&payload)?;
+
+        let mut request = self
+            .client
+            .post(self.url.clone())
+            .headers(self.headers.clone())
+            .header(CONTENT_TYPE, "application/json");
+        if let Some(secret) = self.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, &body)?);
+        }
+        let request = request.body(body).build()?;
+        let response = crate::http::execute(&self.client, "webhook", request).await?;
+        crate::error::error_for_status("webhook", response).await?;
+        tracing::info!("Requests of {user_id} successfully forwarded.");
+        Ok(())
+    }
+}
+
+fn sign(secret: &str, body: &[u8]) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(body);
+    Ok(format!(
+        "sha256={}",
+        hex::encode(mac.finalize().into_bytes())
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    #[tokio::test]
+    async fn signed_payload_posted() -> Result<()> {
+        let server = MockServer::start().await;
+        let expected_body = json!({
+            "event": "reviewer_requests",
+            "reviewer": { "name": "Alice", "email": null },
+            "waiting_for_review": [],
+            "waiting_for_author": [],
+            "text": "Hey!\nJust a friendly reminder that there are ",
+        });
+        Mock::given(method("POST"))
+            .and(path("/hooks/reviews"))
+            .and(header("x-api-key", "key"))
+            .and(|request: &Request| {
+                let signature = sign("secret", &request.body).unwrap();
+                request
+                    .headers
+                    .get(SIGNATURE_HEADER)
+                    .is_some_and(|v| v.as_bytes() == signature.as_bytes())
+            })
+            .and(body_json(&expected_body))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let url = Url::parse(&format!("{}/hooks/reviews", server.uri()))?;
+        let notifier = WebhookNotifier::new(&url, [("X-Api-Key", "key")], Some("secret"))?;
+        let requests = ReviewerRequests::empty("Alice".to_string());
+        notifier.send_message("Alice", &requests).await
+    }
+
+    #[tokio::test]
+    async fn rejected_key_reported_as_auth_error() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("invalid key"))
+            .mount(&server)
+            .await;
+
+        let url = Url::parse(&format!("{}/hooks/reviews", server.uri()))?;
+        let notifier = WebhookNotifier::new(&url, [("X-Api-Key", "key")], None)?;
+        let requests = ReviewerRequests::empty("Alice".to_string());
+        let report = notifier
+            .send_message("Alice", &requests)
+            .await
+            .expect_err("rejected key");
+
+        assert!(matches!(
+            crate::error::Error::find(&report),
+            Some(crate::error::Error::Auth { service, status: 401, .. }) if service == "webhook"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn signature_is_hex_encoded_hmac() -> Result<()> {
+        let signature = sign("key", b"The quick brown fox jumps over the lazy dog")?;
+        assert_eq!(
+            signature,
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        Ok(())
+    }
+}
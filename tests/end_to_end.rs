@@ -0,0 +1,285 @@
+mod support;
+
+use reviewporter::client::{AzureClient, SlackClient};
+use serde_json::json;
+use support::{fixture, write_config, FakeAzure, FakeSlack, AZURE_TOKEN, SLACK_TOKEN};
+
+#[tokio::test]
+async fn reports_sent_to_available_reviewers() {
+    let azure = FakeAzure::start(fixture("azure.json")).await;
+    let slack = FakeSlack::start(fixture("slack.json"), &[]).await;
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = write_config(dir.path(), &azure, &slack, "");
+
+    let summary = reviewporter::send_reports(vec![], &config_path, None)
+        .await
+        .unwrap();
+
+    assert!(summary.is_success(), "{summary}");
+    assert_eq!(summary.sent, 2);
+    let mut messages = slack.messages().await;
+    messages.sort();
+    let channels = messages.iter().map(|(c, _)| c.as_str()).collect::<Vec<_>>();
+    assert_eq!(channels, ["U1", "U2"]);
+    let (_, alice) = &messages[0];
+    assert!(alice.contains("Add login page"), "{alice}");
+    assert!(alice.contains("Fix typo in README"), "{alice}");
+    assert!(
+        alice.contains("Project/_git/backend/pullrequest/1"),
+        "{alice}"
+    );
+    assert!(!alice.contains("Remove old API"), "{alice}");
+    let (_, bob) = &messages[1];
+    assert!(bob.contains("Add login page"), "{bob}");
+}
+
+#[tokio::test]
+async fn failed_message_reported_in_summary() {
+    let azure = FakeAzure::start(fixture("azure.json")).await;
+    let slack = FakeSlack::start(fixture("slack.json"), &["U2"]).await;
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = write_config(dir.path(), &azure, &slack, "");
+
+    let summary = reviewporter::send_reports(vec![], &config_path, None)
+        .await
+        .unwrap();
+
+    assert_eq!(summary.sent, 1);
+    assert_eq!(
+        summary.to_string(),
+        "1 messages sent, 1 members failed.\n\
+        - Bob (default) failed sending the message: Slack responded with error channel_not_found\n"
+    );
+}
+
+#[tokio::test]
+async fn unmatched_member_without_pull_requests_not_reported() {
+    let mut azure_fixture = fixture("azure.json");
+    azure_fixture["teams"]["Team"]
+        .as_array_mut()
+        .unwrap()
+        .push(json!({ "id": "dave", "displayName": "Dave", "uniqueName": "dave" }));
+    let azure = FakeAzure::start(azure_fixture).await;
+    let slack = FakeSlack::start(fixture("slack.json"), &[]).await;
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = write_config(dir.path(), &azure, &slack, "");
+
+    let summary = reviewporter::send_reports(vec![], &config_path, None)
+        .await
+        .unwrap();
+
+    // Dave is not in Slack, but no pull requests wait for him.
+    assert!(summary.is_success(), "{summary}");
+    assert_eq!(summary.sent, 2);
+}
+
+#[tokio::test]
+async fn failed_profile_reported_in_summary() {
+    let azure = FakeAzure::start(fixture("azure.json")).await;
+    let slack = FakeSlack::start(fixture("slack.json"), &[]).await;
+    let dir = tempfile::tempdir().unwrap();
+    let profiles = r#"
+[profiles.broken.slack]
+token = "revoked-token"
+
+[profiles.team]
+"#;
+    let config_path = write_config(dir.path(), &azure, &slack, profiles);
+
+    let summary = reviewporter::send_reports(vec![], &config_path, None)
+        .await
+        .unwrap();
+
+    assert_eq!(summary.sent, 2);
+    assert_eq!(summary.profile_failures.len(), 1);
+    assert_eq!(summary.profile_failures[0].profile, "broken");
+    assert!(!summary.is_success());
+}
+
+#[tokio::test]
+async fn rejected_token_reported_as_auth_error() {
+    let azure = FakeAzure::start(fixture("azure.json")).await;
+    let slack = FakeSlack::start(fixture("slack.json"), &[]).await;
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = write_config(dir.path(), &azure, &slack, "");
+    let config = std::fs::read_to_string(&config_path).unwrap();
+    std::fs::write(&config_path, config.replace(AZURE_TOKEN, "expired-token")).unwrap();
+
+    let result = reviewporter::send_reports(vec![], &config_path, None).await;
+
+    assert!(
+        matches!(result, Err(reviewporter::Error::Auth { .. })),
+        "{result:?}"
+    );
+}
+
+#[tokio::test]
+async fn reviewers_added_to_pull_request() {
+    let azure = FakeAzure::start(fixture("azure.json")).await;
+    let slack = FakeSlack::start(fixture("slack.json"), &[]).await;
+    let dir = tempfile::tempdir().unwrap();
+    let reviewers_config = r#"
+[azure.pull_request_reviewers]
+required_reviewers_count = 1
+teams = [{ name = "Team" }]
+"#;
+    let config_path = write_config(dir.path(), &azure, &slack, reviewers_config);
+
+    reviewporter::add_reviewers(
+        &config_path,
+        None,
+        "3".to_string(),
+        "backend".to_string(),
+        None,
+    )
+    .await
+    .unwrap();
+
+    let added = azure.added_reviewers(3).await;
+    assert_eq!(
+        added,
+        [json!([
+            { "id": "bob", "isRequired": true },
+            { "id": "carol", "isRequired": false },
+        ])]
+    );
+}
+
+#[tokio::test]
+async fn reviewers_added_once_for_profiles_sharing_repository() {
+    let azure = FakeAzure::start(fixture("azure.json")).await;
+    let slack = FakeSlack::start(fixture("slack.json"), &[]).await;
+    let dir = tempfile::tempdir().unwrap();
+    let reviewers_config = r#"
+[azure.pull_request_reviewers]
+required_reviewers_count = 1
+teams = [{ name = "Team" }]
+
+[profiles.mobile]
+
+[profiles.web]
+"#;
+    let config_path = write_config(dir.path(), &azure, &slack, reviewers_config);
+
+    reviewporter::add_reviewers(
+        &config_path,
+        None,
+        "3".to_string(),
+        "backend".to_string(),
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(azure.added_reviewers(3).await.len(), 1);
+}
+
+#[tokio::test]
+async fn reviewers_not_added_for_repository_outside_profiles() {
+    let azure = FakeAzure::start(fixture("azure.json")).await;
+    let slack = FakeSlack::start(fixture("slack.json"), &[]).await;
+    let dir = tempfile::tempdir().unwrap();
+    let reviewers_config = r#"
+[azure.pull_request_reviewers]
+required_reviewers_count = 1
+teams = [{ name = "Team" }]
+"#;
+    let config_path = write_config(dir.path(), &azure, &slack, reviewers_config);
+
+    let result = reviewporter::add_reviewers(
+        &config_path,
+        None,
+        "3".to_string(),
+        "frontend".to_string(),
+        None,
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert!(azure.added_reviewers(3).await.is_empty());
+}
+
+#[tokio::test]
+async fn unavailable_reviewer_replaced() {
+    let mut azure_fixture = fixture("azure.json");
+    azure_fixture["pullRequests"]["backend"][2]["reviewers"] = json!([
+        { "id": "carol", "displayName": "Carol", "isRequired": true, "vote": 0 },
+    ]);
+    let azure = FakeAzure::start(azure_fixture).await;
+    let slack = FakeSlack::start(fixture("slack.json"), &[]).await;
+    let dir = tempfile::tempdir().unwrap();
+    let reviewers_config = r#"
+[azure.pull_request_reviewers]
+required_reviewers_count = 1
+teams = [{ name = "Team" }]
+absent_reviewers = "demote"
+"#;
+    let config_path = write_config(dir.path(), &azure, &slack, reviewers_config);
+
+    reviewporter::rebalance_reviewers(&config_path, None, vec![])
+        .await
+        .unwrap();
+
+    // Carol is on vacation, Alice is the author.
+    assert_eq!(
+        azure.added_reviewers(3).await,
+        [json!([{ "id": "bob", "isRequired": true }])]
+    );
+    assert_eq!(
+        azure.updated_reviewers(3).await,
+        [json!({ "id": "carol", "isRequired": false })]
+    );
+    assert!(azure.removed_reviewers(3).await.is_empty());
+    for pull_request_id in [1, 2] {
+        assert!(azure.added_reviewers(pull_request_id).await.is_empty());
+        assert!(azure.updated_reviewers(pull_request_id).await.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn clients_built_without_config() {
+    let azure = FakeAzure::start(fixture("azure.json")).await;
+    let slack = FakeSlack::start(fixture("slack.json"), &[]).await;
+    let azure_client = AzureClient::builder()
+        .base_url(azure.base_url())
+        .token(AZURE_TOKEN)
+        .project("Project")
+        .team_name("Team")
+        .reviewers(1, [("Team".to_string(), None)])
+        .build()
+        .unwrap();
+    let slack_client = SlackClient::builder()
+        .base_url(slack.base_url())
+        .token(SLACK_TOKEN)
+        .team_id("T1")
+        .usergroup_id(support::SLACK_USERGROUP)
+        .template("{{ reviewer.name }}: {{ waiting_for_review | length }}")
+        .build()
+        .unwrap();
+
+    let directory = slack_client.directory().await.unwrap();
+    let team_requests = tokio::spawn(async move {
+        azure_client
+            .pull_requests(vec!["backend".to_string()], |_| true)
+            .await
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    for requests in &team_requests.requests {
+        let email = requests.reviewer_email.as_deref();
+        if let Some(id) = directory.available_user_id(&requests.reviewer_name, email) {
+            slack_client.send_report(id, requests).await.unwrap();
+        }
+    }
+
+    let mut messages = slack.messages().await;
+    messages.sort();
+    assert_eq!(
+        messages,
+        [
+            ("U1".to_string(), "Alice: 1".to_string()),
+            ("U2".to_string(), "Bob: 1".to_string()),
+        ]
+    );
+}
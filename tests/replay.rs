@@ -0,0 +1,35 @@
+//! Runs against the responses recorded with `--record` in `tests/fixtures/replay`.
+//! Recordings attached to bug reports are added to the directory the same way.
+use reviewporter::Recording;
+use std::path::Path;
+
+#[tokio::test]
+async fn reports_sent_from_recorded_responses() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay");
+    reviewporter::set_recording(Recording::Replay(fixtures)).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.toml");
+    let config = r#"
+repositories = ["backend"]
+
+[azure]
+base_url = "https://azure.invalid/"
+token = "azure-token"
+project = "Project"
+team_name = "Team"
+
+[slack]
+base_url = "https://slack.invalid/"
+token = "slack-token"
+team_id = "T1"
+usergroup_id = "S1"
+"#;
+    std::fs::write(&config_path, config).unwrap();
+
+    let summary = reviewporter::send_reports(vec![], &config_path, None)
+        .await
+        .unwrap();
+
+    assert!(summary.is_success(), "{summary}");
+    assert_eq!(summary.sent, 2);
+}
@@ -0,0 +1,311 @@
+//! Fake Azure DevOps and Slack servers for the end-to-end tests.
+//!
+//! The servers run in the test process and answer from the JSON fixtures in
+//! `tests/fixtures`. Requests they received are available for assertions.
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use wiremock::matchers::any;
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+pub const AZURE_TOKEN: &str = "azure-token";
+pub const SLACK_TOKEN: &str = "slack-token";
+pub const SLACK_USERGROUP: &str = "S1";
+
+pub fn fixture(name: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    let content = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read fixture {}: {e}", path.display()));
+    serde_json::from_str(&content).expect("Fixture must be valid JSON")
+}
+
+/// Azure DevOps server with the teams and pull requests of the fixture.
+///
+/// The fixture has `teams` with the members of every team and `pullRequests`
+/// with the pull requests of every repository.
+pub struct FakeAzure {
+    server: MockServer,
+}
+
+impl FakeAzure {
+    pub async fn start(fixture: Value) -> Self {
+        let server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(move |request: &Request| azure_response(&fixture, request))
+            .mount(&server)
+            .await;
+        Self { server }
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("{}/", self.server.uri())
+    }
+
+    /// Bodies of the requests adding reviewers to the pull request.
+    pub async fn added_reviewers(&self, pull_request_id: usize) -> Vec<Value> {
+        let suffix = format!("/pullrequests/{pull_request_id}/reviewers");
+        received(&self.server)
+            .await
+            .into_iter()
+            .filter(|r| r.method.as_str() == "POST" && r.url.path().ends_with(&suffix))
+            .map(|r| r.body_json().expect("Reviewers must be JSON"))
+            .collect()
+    }
+
+    /// Bodies of the requests updating reviewers of the pull request.
+    pub async fn updated_reviewers(&self, pull_request_id: usize) -> Vec<Value> {
+        self.reviewer_requests("PUT", pull_request_id)
+            .await
+            .into_iter()
+            .map(|r| r.body_json().expect("Reviewer must be JSON"))
+            .collect()
+    }
+
+    /// Ids of the reviewers removed from the pull request.
+    pub async fn removed_reviewers(&self, pull_request_id: usize) -> Vec<String> {
+        self.reviewer_requests("DELETE", pull_request_id)
+            .await
+            .into_iter()
+            .filter_map(|r| r.url.path_segments()?.next_back().map(str::to_string))
+            .collect()
+    }
+
+    async fn reviewer_requests(&self, method: &str, pull_request_id: usize) -> Vec<Request> {
+        let segment = format!("/pullrequests/{pull_request_id}/reviewers/");
+        received(&self.server)
+            .await
+            .into_iter()
+            .filter(|r| r.method.as_str() == method && r.url.path().contains(&segment))
+            .collect()
+    }
+}
+
+fn azure_response(fixture: &Value, request: &Request) -> ResponseTemplate {
+    let authorization = request.headers.get("authorization");
+    if authorization.and_then(|v| v.to_str().ok()) != Some(&format!("Bearer {AZURE_TOKEN}")) {
+        return ResponseTemplate::new(401);
+    }
+    if query(request, "api-version").is_none() {
+        return error(400, "The api-version query parameter is required.");
+    }
+    let segments = request.url.path_segments().unwrap().collect::<Vec<_>>();
+    let method = request.method.as_str();
+    match (method, segments.as_slice()) {
+        ("GET", ["_apis", "projects", _, "teams"]) => {
+            let teams = fixture["teams"].as_object().unwrap().keys();
+            list(teams.map(|name| json!({ "name": name })).collect())
+        }
+        ("GET", ["_apis", "projects", _, "teams", team, "members"]) => {
+            match fixture["teams"][team].as_array() {
+                Some(members) => list(
+                    members
+                        .iter()
+                        .map(|member| json!({ "identity": member }))
+                        .collect(),
+                ),
+                None => error(404, &format!("Team {team} does not exist.")),
+            }
+        }
+        ("GET", [_, "_apis", "git", "repositories", repository, "pullrequests"]) => {
+            let reviewer = query(request, "searchCriteria.reviewerId");
+            let creator = query(request, "searchCriteria.creatorId");
+            let requests = pull_requests(fixture, repository)
+                .filter(|r| r["status"] == "active")
+                .filter(|r| {
+                    reviewer.as_ref().is_none_or(|id| {
+                        let reviewers = r["reviewers"].as_array().unwrap();
+                        reviewers.iter().any(|v| v["id"] == id.as_str())
+                    })
+                })
+                .filter(|r| {
+                    let author = &r["createdBy"]["id"];
+                    creator.as_ref().is_none_or(|id| author == id.as_str())
+                })
+                .cloned()
+                .collect();
+            list(requests)
+        }
+        ("GET", [_, "_apis", "git", "repositories", repository, "pullrequests", id]) => {
+            match find_pull_request(fixture, repository, id) {
+                Some(request) => ResponseTemplate::new(200).set_body_json(request),
+                None => error(404, &format!("Pull request {id} does not exist.")),
+            }
+        }
+        (
+            "POST",
+            [_, "_apis", "git", "repositories", repository, "pullrequests", id, "reviewers"],
+        ) => match find_pull_request(fixture, repository, id) {
+            Some(_) => ResponseTemplate::new(200).set_body_json(json!({ "value": [] })),
+            None => error(404, &format!("Pull request {id} does not exist.")),
+        },
+        (
+            "PUT" | "DELETE",
+            [_, "_apis", "git", "repositories", repository, "pullrequests", id, "reviewers", reviewer],
+        ) => match find_pull_request(fixture, repository, id) {
+            Some(_) if method == "PUT" => {
+                ResponseTemplate::new(200).set_body_json(json!({ "id": reviewer }))
+            }
+            Some(_) => ResponseTemplate::new(204),
+            None => error(404, &format!("Pull request {id} does not exist.")),
+        },
+        ("GET", [_, "_apis", "git", "repositories", repository]) => {
+            match fixture["pullRequests"].get(repository) {
+                Some(_) => ResponseTemplate::new(200).set_body_json(json!({ "name": repository })),
+                None => error(404, &format!("Repository {repository} does not exist.")),
+            }
+        }
+        _ => error(
+            404,
+            &format!("Unknown endpoint {method} {}.", request.url.path()),
+        ),
+    }
+}
+
+fn pull_requests<'a>(fixture: &'a Value, repository: &str) -> impl Iterator<Item = &'a Value> {
+    let requests = fixture["pullRequests"][repository].as_array();
+    requests.into_iter().flatten()
+}
+
+fn find_pull_request<'a>(fixture: &'a Value, repository: &str, id: &str) -> Option<&'a Value> {
+    let id = id.parse::<u64>().ok()?;
+    pull_requests(fixture, repository).find(|r| r["pullRequestId"].as_u64() == Some(id))
+}
+
+fn list(values: Vec<Value>) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "count": values.len(), "value": values }))
+}
+
+fn error(status: u16, message: &str) -> ResponseTemplate {
+    ResponseTemplate::new(status).set_body_json(json!({ "message": message }))
+}
+
+/// Slack Web API with the usergroups and user profiles of the fixture.
+///
+/// The fixture has `usergroups` with the user ids of every usergroup and `users`
+/// with the profile of every user. Messages to the failing channels are rejected
+/// with the `channel_not_found` error.
+pub struct FakeSlack {
+    server: MockServer,
+}
+
+impl FakeSlack {
+    pub async fn start(fixture: Value, failing_channels: &[&str]) -> Self {
+        let server = MockServer::start().await;
+        let failing_channels = failing_channels
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        Mock::given(any())
+            .respond_with(move |request: &Request| {
+                slack_response(&fixture, &failing_channels, request)
+            })
+            .mount(&server)
+            .await;
+        Self { server }
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("{}/", self.server.uri())
+    }
+
+    /// Channels and texts of the posted messages in the order they were sent.
+    pub async fn messages(&self) -> Vec<(String, String)> {
+        received(&self.server)
+            .await
+            .into_iter()
+            .filter(|r| r.url.path() == "/chat.postMessage")
+            .map(|r| {
+                let payload = r.body_json::<Value>().expect("Message must be JSON");
+                let field = |name: &str| payload[name].as_str().unwrap_or_default().to_string();
+                (field("channel"), field("text"))
+            })
+            .collect()
+    }
+}
+
+fn slack_response(
+    fixture: &Value,
+    failing_channels: &[String],
+    request: &Request,
+) -> ResponseTemplate {
+    let authorization = request.headers.get("authorization");
+    if authorization.and_then(|v| v.to_str().ok()) != Some(&format!("Bearer {SLACK_TOKEN}")) {
+        return slack_error("invalid_auth");
+    }
+    match request.url.path() {
+        "/usergroups.users.list" => {
+            let usergroup = query(request, "usergroup").unwrap_or_default();
+            match fixture["usergroups"].get(&usergroup) {
+                Some(users) => slack_ok(json!({ "users": users })),
+                None => slack_error("no_such_subteam"),
+            }
+        }
+        "/users.profile.get" => {
+            let user = query(request, "user").unwrap_or_default();
+            match fixture["users"].get(&user) {
+                Some(profile) => slack_ok(json!({ "profile": profile })),
+                None => slack_error("user_not_found"),
+            }
+        }
+        "/chat.postMessage" => {
+            let payload = request.body_json::<Value>().unwrap_or_default();
+            let channel = payload["channel"].as_str().unwrap_or_default();
+            match failing_channels.iter().any(|c| c == channel) {
+                true => slack_error("channel_not_found"),
+                false => slack_ok(json!({ "channel": channel })),
+            }
+        }
+        _ => slack_error("unknown_method"),
+    }
+}
+
+fn slack_ok(mut body: Value) -> ResponseTemplate {
+    body["ok"] = json!(true);
+    ResponseTemplate::new(200).set_body_json(body)
+}
+
+fn slack_error(error: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "ok": false, "error": error }))
+}
+
+fn query(request: &Request, name: &str) -> Option<String> {
+    let mut pairs = request.url.query_pairs();
+    pairs
+        .find(|(key, _)| key == name)
+        .map(|(_, v)| v.into_owned())
+}
+
+async fn received(server: &MockServer) -> Vec<Request> {
+    server
+        .received_requests()
+        .await
+        .expect("Request recording must be enabled")
+}
+
+/// Writes the config of the Azure DevOps and Slack fakes, followed by the extra
+/// TOML, to the directory.
+pub fn write_config(dir: &Path, azure: &FakeAzure, slack: &FakeSlack, extra: &str) -> PathBuf {
+    let config = format!(
+        r#"
+repositories = ["backend"]
+
+[azure]
+base_url = "{azure_url}"
+token = "{AZURE_TOKEN}"
+project = "Project"
+team_name = "Team"
+
+[slack]
+base_url = "{slack_url}"
+token = "{SLACK_TOKEN}"
+team_id = "T1"
+usergroup_id = "{SLACK_USERGROUP}"
+{extra}"#,
+        azure_url = azure.base_url(),
+        slack_url = slack.base_url(),
+    );
+    let path = dir.join("config.toml");
+    std::fs::write(&path, config).expect("Failed to write config");
+    path
+}